@@ -0,0 +1,228 @@
+use gtk::prelude::*;
+use gtk::{Box as GtkBox, Button, DrawingArea, Frame, Inhibit, Orientation, ToggleButton};
+use neorisc_lib::debugger::Debugger;
+use neorisc_lib::interpreter::board::{
+    Buttons, Leds, SevenSegmentDisplay, Switches, BUTTON_COUNT, DIGIT_COUNT,
+};
+use std::cell::Cell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+const LED_COUNT: usize = 8;
+const SWITCH_COUNT: usize = 8;
+const LED_SIZE: i32 = 28;
+const DIGIT_WIDTH: f64 = 44.0;
+const DIGIT_HEIGHT: f64 = 72.0;
+const SEGMENT_THICKNESS: f64 = 6.0;
+
+// Segments a-g as (x, y, width, height) within a digit.
+const SEGMENTS: [(f64, f64, f64, f64); 7] = [
+    (10.0, 6.0, 22.0, SEGMENT_THICKNESS),
+    (32.0, 12.0, SEGMENT_THICKNESS, 22.0),
+    (32.0, 40.0, SEGMENT_THICKNESS, 22.0),
+    (10.0, 62.0, 22.0, SEGMENT_THICKNESS),
+    (4.0, 40.0, SEGMENT_THICKNESS, 22.0),
+    (4.0, 12.0, SEGMENT_THICKNESS, 22.0),
+    (10.0, 34.0, 22.0, SEGMENT_THICKNESS),
+];
+
+// Device registers shown by the panel, read while the debugger is locked.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct BoardState {
+    pub leds: u8,
+    // What each digit shows after the program's multiplexed scan, rightmost first.
+    pub digits: [u8; DIGIT_COUNT],
+}
+
+impl BoardState {
+    pub fn capture(debugger: &Debugger) -> Self {
+        let bus = &debugger.interpreter.bus;
+        Self {
+            leds: bus.device::<Leds>().map(|leds| leds.value).unwrap_or(0),
+            digits: bus
+                .device::<SevenSegmentDisplay>()
+                .map(|display| display.digits)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BoardInput {
+    Switch(usize, bool),
+    Button(usize, bool),
+}
+
+impl BoardInput {
+    pub fn apply(self, debugger: &mut Debugger) {
+        let bus = &mut debugger.interpreter.bus;
+        match self {
+            BoardInput::Switch(index, on) => {
+                if let Some(switches) = bus.device_mut::<Switches>() {
+                    switches.set_switch(index, on);
+                }
+            }
+            BoardInput::Button(index, pressed) => {
+                if let Some(buttons) = bus.device_mut::<Buttons>() {
+                    buttons.set_pressed(index, pressed);
+                }
+            }
+        }
+    }
+}
+
+pub struct BoardPanel {
+    pub widget: Frame,
+    state: Rc<Cell<BoardState>>,
+    leds: DrawingArea,
+    display: DrawingArea,
+    switches: Vec<ToggleButton>,
+    buttons: Vec<Button>,
+}
+
+fn draw_leds(cr: &cairo::Context, leds: u8) {
+    for position in 0..LED_COUNT {
+        // The leftmost LED is bit 7.
+        let bit = LED_COUNT - 1 - position;
+        if leds & (1 << bit) != 0 {
+            cr.set_source_rgb(0.95, 0.1, 0.1);
+        } else {
+            cr.set_source_rgb(0.3, 0.1, 0.1);
+        }
+        let center = LED_SIZE as f64 / 2.0;
+        cr.arc(
+            position as f64 * LED_SIZE as f64 + center,
+            center,
+            center - 4.0,
+            0.0,
+            2.0 * PI,
+        );
+        cr.fill();
+    }
+}
+
+fn draw_display(cr: &cairo::Context, digits: &[u8; DIGIT_COUNT]) {
+    cr.set_source_rgb(0.1, 0.1, 0.1);
+    cr.paint();
+    // DIG0 is the rightmost digit.
+    for (position, segments) in digits.iter().rev().enumerate() {
+        let x = position as f64 * DIGIT_WIDTH;
+        for (segment, &(sx, sy, width, height)) in SEGMENTS.iter().enumerate() {
+            set_segment_color(cr, segments & (1 << segment) != 0);
+            cr.rectangle(x + sx, sy, width, height);
+            cr.fill();
+        }
+        set_segment_color(cr, segments & 0x80 != 0);
+        cr.arc(x + 40.0, 65.0, 3.0, 0.0, 2.0 * PI);
+        cr.fill();
+    }
+}
+
+fn set_segment_color(cr: &cairo::Context, lit: bool) {
+    if lit {
+        cr.set_source_rgb(1.0, 0.15, 0.1);
+    } else {
+        cr.set_source_rgb(0.22, 0.12, 0.12);
+    }
+}
+
+impl BoardPanel {
+    pub fn new() -> Self {
+        let state = Rc::new(Cell::new(BoardState::default()));
+
+        let display = DrawingArea::new();
+        display.set_size_request(
+            (DIGIT_WIDTH * DIGIT_COUNT as f64) as i32,
+            DIGIT_HEIGHT as i32,
+        );
+        let drawn = state.clone();
+        display.connect_draw(move |_, cr| {
+            draw_display(cr, &drawn.get().digits);
+            Inhibit(false)
+        });
+
+        let leds = DrawingArea::new();
+        leds.set_size_request(LED_SIZE * LED_COUNT as i32, LED_SIZE);
+        let drawn = state.clone();
+        leds.connect_draw(move |_, cr| {
+            draw_leds(cr, drawn.get().leds);
+            Inhibit(false)
+        });
+
+        let switch_row = GtkBox::new(Orientation::Horizontal, 2);
+        let switches: Vec<ToggleButton> = (0..SWITCH_COUNT)
+            .map(|index| {
+                let switch = ToggleButton::with_label(&format!("SW{}", index));
+                switch_row.pack_end(&switch, false, false, 0);
+                switch
+            })
+            .collect();
+
+        let button_row = GtkBox::new(Orientation::Horizontal, 2);
+        let buttons: Vec<Button> = (0..BUTTON_COUNT)
+            .map(|index| {
+                let button = Button::with_label(&format!("BT{}", index));
+                button_row.pack_end(&button, false, false, 0);
+                button
+            })
+            .collect();
+
+        let layout = GtkBox::new(Orientation::Vertical, 6);
+        layout.set_border_width(6);
+        layout.pack_start(&display, false, false, 0);
+        layout.pack_start(&leds, false, false, 0);
+        layout.pack_start(&switch_row, false, false, 0);
+        layout.pack_start(&button_row, false, false, 0);
+        let widget = Frame::new(Some("Lab board"));
+        widget.add(&layout);
+
+        Self {
+            widget,
+            state,
+            leds,
+            display,
+            switches,
+            buttons,
+        }
+    }
+
+    pub fn connect_input<F: Fn(BoardInput) + Clone + 'static>(&self, callback: F) {
+        for (index, switch) in self.switches.iter().enumerate() {
+            let callback = callback.clone();
+            switch.connect_toggled(move |switch| {
+                callback(BoardInput::Switch(index, switch.get_active()))
+            });
+        }
+        for (index, button) in self.buttons.iter().enumerate() {
+            let pressed = callback.clone();
+            button.connect_button_press_event(move |_, _| {
+                pressed(BoardInput::Button(index, true));
+                Inhibit(false)
+            });
+            let released = callback.clone();
+            button.connect_button_release_event(move |_, _| {
+                released(BoardInput::Button(index, false));
+                Inhibit(false)
+            });
+        }
+    }
+
+    // Switch positions belong to the user, a freshly assembled program starts with them.
+    pub fn switches(&self) -> Vec<BoardInput> {
+        self.switches
+            .iter()
+            .enumerate()
+            .map(|(index, switch)| BoardInput::Switch(index, switch.get_active()))
+            .collect()
+    }
+
+    pub fn refresh(&self, state: BoardState) {
+        let previous = self.state.replace(state);
+        if previous.leds != state.leds {
+            self.leds.queue_draw();
+        }
+        if previous.digits != state.digits {
+            self.display.queue_draw();
+        }
+    }
+}
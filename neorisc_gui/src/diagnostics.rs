@@ -0,0 +1,74 @@
+use gtk::prelude::*;
+use gtk::{CellRendererText, ListStore, ScrolledWindow, TreeView, TreeViewColumn};
+use neorisc_lib::compiler::diagnostics::Diagnostic;
+
+const LINE_COLUMN: u32 = 0;
+const STAGE_COLUMN: u32 = 1;
+const MESSAGE_COLUMN: u32 = 2;
+
+pub struct DiagnosticsPanel {
+    pub widget: ScrolledWindow,
+    view: TreeView,
+    store: ListStore,
+}
+
+fn text_column(view: &TreeView, title: &str, column: u32) {
+    let renderer = CellRendererText::new();
+    let tree_column = TreeViewColumn::new();
+    tree_column.set_title(title);
+    tree_column.set_resizable(true);
+    tree_column.pack_start(&renderer, true);
+    tree_column.add_attribute(&renderer, "text", column as i32);
+    view.append_column(&tree_column);
+}
+
+impl DiagnosticsPanel {
+    pub fn new() -> Self {
+        let store = ListStore::new(&[glib::Type::U32, glib::Type::String, glib::Type::String]);
+        let view = TreeView::with_model(&store);
+        text_column(&view, "Line", LINE_COLUMN);
+        text_column(&view, "Stage", STAGE_COLUMN);
+        text_column(&view, "Message", MESSAGE_COLUMN);
+
+        let widget = ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        widget.set_size_request(-1, 120);
+        widget.add(&view);
+
+        Self {
+            widget,
+            view,
+            store,
+        }
+    }
+
+    pub fn set_diagnostics(&self, diagnostics: &[Diagnostic]) {
+        self.store.clear();
+        for diagnostic in diagnostics.iter() {
+            self.store.insert_with_values(
+                None,
+                &[LINE_COLUMN, STAGE_COLUMN, MESSAGE_COLUMN],
+                &[
+                    &(diagnostic.line as u32),
+                    &format!("{:?}", diagnostic.stage),
+                    &diagnostic.message,
+                ],
+            );
+        }
+    }
+
+    // Calls back with the 0-based source line of the activated diagnostic.
+    pub fn connect_line_activated<F: Fn(usize) + 'static>(&self, callback: F) {
+        let store = self.store.clone();
+        self.view.connect_row_activated(move |_, path, _| {
+            let line = store.get_iter(path).and_then(|iter| {
+                store
+                    .get_value(&iter, LINE_COLUMN as i32)
+                    .get_some::<u32>()
+                    .ok()
+            });
+            if let Some(line) = line {
+                callback((line as usize).saturating_sub(1));
+            }
+        });
+    }
+}
@@ -0,0 +1,195 @@
+use crate::highlight::{highlight_buffer_line, install_tags, underline_lines};
+use crate::style::CURRENT_BACKGROUND;
+use gtk::prelude::*;
+use gtk::{Inhibit, ScrolledWindow, TextBuffer, TextTag, TextTagTable, TextView, TextWindowType};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+const GUTTER_WIDTH: i32 = 48;
+const GUTTER_PADDING: i32 = 8;
+const BREAKPOINT_RADIUS: f64 = 5.0;
+const CURRENT_LINE_TAG: &str = "current-line";
+
+pub struct Editor {
+    pub view: TextView,
+    pub buffer: TextBuffer,
+    pub widget: ScrolledWindow,
+    // 0-based source lines with a breakpoint.
+    breakpoints: Rc<RefCell<BTreeSet<usize>>>,
+}
+
+fn draw_line_numbers(view: &TextView, cr: &cairo::Context, breakpoints: &BTreeSet<usize>) {
+    let window = match TextViewExt::get_window(view, TextWindowType::Left) {
+        Some(window) => window,
+        None => return,
+    };
+    if !gtk::cairo_should_draw_window(cr, &window) {
+        return;
+    }
+
+    gtk::cairo_transform_to_window(cr, view, &window);
+    let style = view.get_style_context();
+    let visible = view.get_visible_rect();
+    let (mut iter, _) = view.get_line_at_y(visible.y);
+    loop {
+        let (y, _) = view.get_line_yrange(&iter);
+        if y > visible.y + visible.height {
+            break;
+        }
+
+        let (_, window_y) = view.buffer_to_window_coords(TextWindowType::Left, 0, y);
+        if breakpoints.contains(&(iter.get_line() as usize)) {
+            let (_, height) = view.get_line_yrange(&iter);
+            cr.set_source_rgb(0.8, 0.0, 0.0);
+            cr.arc(
+                GUTTER_PADDING as f64,
+                window_y as f64 + height as f64 / 2.0,
+                BREAKPOINT_RADIUS,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            );
+            cr.fill();
+        }
+        let layout = view.create_pango_layout(Some(&(iter.get_line() + 1).to_string()));
+        let (width, _) = layout.get_pixel_size();
+        gtk::render_layout(
+            &style,
+            cr,
+            (GUTTER_WIDTH - width - GUTTER_PADDING) as f64,
+            window_y as f64,
+            &layout,
+        );
+
+        if !iter.forward_line() {
+            break;
+        }
+    }
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        let buffer = TextBuffer::new(None::<&TextTagTable>);
+        let view = TextView::with_buffer(&buffer);
+        view.set_monospace(true);
+        view.set_left_margin(GUTTER_PADDING);
+        view.set_border_window_size(TextWindowType::Left, GUTTER_WIDTH);
+        let breakpoints = Rc::new(RefCell::new(BTreeSet::new()));
+        let drawn = breakpoints.clone();
+        view.connect_draw(move |view, cr| {
+            draw_line_numbers(view, cr, &drawn.borrow());
+            Inhibit(false)
+        });
+
+        install_tags(&buffer);
+        // Edits mark the lines they touch, which are highlighted again once the buffer changed.
+        let dirty = Rc::new(RefCell::new(BTreeSet::new()));
+        let inserted = dirty.clone();
+        buffer.connect_insert_text(move |_, iter, text| {
+            let line = iter.get_line() as usize;
+            let added = text.matches('\n').count();
+            inserted.borrow_mut().extend(line..=line + added);
+        });
+        let deleted = dirty.clone();
+        buffer.connect_delete_range(move |_, start, _| {
+            deleted.borrow_mut().insert(start.get_line() as usize);
+        });
+        buffer.connect_changed(move |buffer| {
+            let lines = std::mem::take(&mut *dirty.borrow_mut());
+            for line in lines {
+                highlight_buffer_line(buffer, line);
+            }
+        });
+
+        let current = TextTag::new(Some(CURRENT_LINE_TAG));
+        current.set_property_paragraph_background(Some(CURRENT_BACKGROUND));
+        if let Some(table) = buffer.get_tag_table() {
+            table.add(&current);
+        }
+
+        let widget = ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        widget.add(&view);
+
+        Self {
+            view,
+            buffer,
+            widget,
+            breakpoints,
+        }
+    }
+
+    // Calls back with the 0-based line whose gutter was clicked.
+    pub fn connect_gutter_clicked<F: Fn(usize) + 'static>(&self, callback: F) {
+        self.view.connect_button_press_event(move |view, event| {
+            let gutter = TextViewExt::get_window(view, TextWindowType::Left);
+            if event.get_window() != gutter || event.get_button() != 1 {
+                return Inhibit(false);
+            }
+            let (_, y) = event.get_position();
+            let (_, buffer_y) = view.window_to_buffer_coords(TextWindowType::Left, 0, y as i32);
+            let (iter, _) = view.get_line_at_y(buffer_y);
+            callback(iter.get_line() as usize);
+            Inhibit(true)
+        });
+    }
+
+    // Underlines the tokens of the 0-based lines with diagnostics.
+    pub fn set_diagnostic_lines(&self, lines: &[usize]) {
+        underline_lines(&self.buffer, lines);
+    }
+
+    pub fn toggle_breakpoint(&self, line: usize) {
+        let mut breakpoints = self.breakpoints.borrow_mut();
+        if !breakpoints.remove(&line) {
+            breakpoints.insert(line);
+        }
+        self.view.queue_draw();
+    }
+
+    pub fn breakpoints(&self) -> Vec<usize> {
+        self.breakpoints.borrow().iter().copied().collect()
+    }
+
+    // Highlights the 0-based executing line, scrolling to it when asked.
+    pub fn set_current_line(&self, line: Option<usize>, scroll: bool) {
+        let (start, end) = self.buffer.get_bounds();
+        self.buffer
+            .remove_tag_by_name(CURRENT_LINE_TAG, &start, &end);
+        if let Some(line) = line {
+            let mut start = self.buffer.get_iter_at_line(line as i32);
+            let mut end = start.clone();
+            end.forward_line();
+            self.buffer
+                .apply_tag_by_name(CURRENT_LINE_TAG, &start, &end);
+            if scroll {
+                self.view.scroll_to_iter(&mut start, 0.1, false, 0.0, 0.0);
+            }
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let (start, end) = self.buffer.get_bounds();
+        self.buffer
+            .get_text(&start, &end, false)
+            .map(|text| text.to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn set_text(&self, text: &str) {
+        self.buffer.set_text(text);
+        self.buffer.place_cursor(&self.buffer.get_start_iter());
+        self.buffer.set_modified(false);
+    }
+
+    // Selects the 0-based line and scrolls it into view.
+    pub fn goto_line(&self, line: usize) {
+        let mut start = self.buffer.get_iter_at_line(line as i32);
+        let mut end = start.clone();
+        if !end.ends_line() {
+            end.forward_to_line_end();
+        }
+        self.buffer.select_range(&start, &end);
+        self.view.scroll_to_iter(&mut start, 0.1, false, 0.0, 0.0);
+        self.view.grab_focus();
+    }
+}
@@ -0,0 +1,115 @@
+use gtk::prelude::*;
+use gtk::{TextBuffer, TextIter, TextTag};
+use neorisc_lib::parser::highlight::{highlight_line, HighlightKind};
+
+const DIAGNOSTIC_TAG: &str = "diagnostic";
+const BOLD: i32 = 700;
+
+const KINDS: [HighlightKind; 12] = [
+    HighlightKind::Label,
+    HighlightKind::Instruction,
+    HighlightKind::Directive,
+    HighlightKind::Macro,
+    HighlightKind::Register,
+    HighlightKind::Immediate,
+    HighlightKind::Number,
+    HighlightKind::String,
+    HighlightKind::Symbol,
+    HighlightKind::Punctuation,
+    HighlightKind::Comment,
+    HighlightKind::Invalid,
+];
+
+fn tag_name(kind: HighlightKind) -> String {
+    format!("highlight-{:?}", kind).to_lowercase()
+}
+
+fn create_tag(kind: HighlightKind) -> TextTag {
+    let tag = TextTag::new(Some(&tag_name(kind)));
+    let color = match kind {
+        HighlightKind::Label => Some("#8f5902"),
+        HighlightKind::Instruction => Some("#204a87"),
+        HighlightKind::Directive | HighlightKind::Macro => Some("#75507b"),
+        HighlightKind::Register => Some("#ce5c00"),
+        HighlightKind::Immediate | HighlightKind::Number => Some("#4e9a06"),
+        HighlightKind::String => Some("#c17d11"),
+        HighlightKind::Symbol => Some("#3465a4"),
+        HighlightKind::Punctuation => None,
+        HighlightKind::Comment => Some("#888a85"),
+        HighlightKind::Invalid => Some("#cc0000"),
+    };
+    tag.set_property_foreground(color);
+    match kind {
+        HighlightKind::Label | HighlightKind::Instruction | HighlightKind::Directive => {
+            tag.set_property_weight(BOLD)
+        }
+        HighlightKind::Comment => tag.set_property_style(pango::Style::Italic),
+        _ => {}
+    }
+    tag
+}
+
+pub fn install_tags(buffer: &TextBuffer) {
+    if let Some(table) = buffer.get_tag_table() {
+        for kind in KINDS.iter() {
+            table.add(&create_tag(*kind));
+        }
+        let diagnostic = TextTag::new(Some(DIAGNOSTIC_TAG));
+        diagnostic.set_property_underline(pango::Underline::Error);
+        table.add(&diagnostic);
+    }
+}
+
+fn line_bounds(buffer: &TextBuffer, line: usize) -> Option<(TextIter, TextIter)> {
+    if line as i32 >= buffer.get_line_count() {
+        return None;
+    }
+    let start = buffer.get_iter_at_line(line as i32);
+    let mut end = start.clone();
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    Some((start, end))
+}
+
+// Applies the tag named by `tag` to each token of the 0-based line, if it names one.
+fn tag_tokens<F: Fn(HighlightKind) -> Option<String>>(buffer: &TextBuffer, line: usize, tag: F) {
+    let text = match line_bounds(buffer, line)
+        .and_then(|(start, end)| buffer.get_text(&start, &end, true))
+    {
+        Some(text) => text.to_string(),
+        None => return,
+    };
+    for span in highlight_line(&text) {
+        if let Some(name) = tag(span.kind) {
+            let start = buffer.get_iter_at_line_index(line as i32, span.span.start as i32);
+            let end = buffer.get_iter_at_line_index(line as i32, span.span.end as i32);
+            buffer.apply_tag_by_name(&name, &start, &end);
+        }
+    }
+}
+
+// Re-highlights one 0-based line, its diagnostic underline is dropped as it is stale now.
+pub fn highlight_buffer_line(buffer: &TextBuffer, line: usize) {
+    let (start, end) = match line_bounds(buffer, line) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    for kind in KINDS.iter() {
+        buffer.remove_tag_by_name(&tag_name(*kind), &start, &end);
+    }
+    buffer.remove_tag_by_name(DIAGNOSTIC_TAG, &start, &end);
+    tag_tokens(buffer, line, |kind| Some(tag_name(kind)));
+}
+
+// Underlines the code tokens of the 0-based diagnostic lines.
+pub fn underline_lines(buffer: &TextBuffer, lines: &[usize]) {
+    let (start, end) = buffer.get_bounds();
+    buffer.remove_tag_by_name(DIAGNOSTIC_TAG, &start, &end);
+    for line in lines.iter() {
+        tag_tokens(buffer, *line, |kind| match kind {
+            HighlightKind::Comment => None,
+            _ => Some(DIAGNOSTIC_TAG.to_string()),
+        });
+    }
+}
@@ -0,0 +1,293 @@
+use crate::style::FLASH_MILLIS;
+use gtk::prelude::*;
+use gtk::{Box as GtkBox, CheckButton, ComboBoxText, Entry, Frame, Grid, Label, Orientation};
+use neorisc_lib::debugger::Debugger;
+use neorisc_lib::interpreter::Flags;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ValueFormat {
+    Hex,
+    Decimal,
+    Signed,
+    Binary,
+}
+
+impl ValueFormat {
+    const ALL: [ValueFormat; 4] = [
+        ValueFormat::Hex,
+        ValueFormat::Decimal,
+        ValueFormat::Signed,
+        ValueFormat::Binary,
+    ];
+
+    pub fn format(self, value: u8) -> String {
+        match self {
+            ValueFormat::Hex => format!("0x{:02X}", value),
+            ValueFormat::Decimal => value.to_string(),
+            ValueFormat::Signed => (value as i8).to_string(),
+            ValueFormat::Binary => format!("0b{:08b}", value),
+        }
+    }
+
+    pub fn parse(self, text: &str) -> Option<u8> {
+        let text = text.trim();
+        match self {
+            ValueFormat::Hex => {
+                u8::from_str_radix(text.trim_start_matches("0x").trim_start_matches("0X"), 16).ok()
+            }
+            ValueFormat::Decimal => text.parse::<u8>().ok(),
+            ValueFormat::Signed => text
+                .parse::<i8>()
+                .map(|value| value as u8)
+                .or_else(|_| text.parse::<u8>())
+                .ok(),
+            ValueFormat::Binary => u8::from_str_radix(text.trim_start_matches("0b"), 2).ok(),
+        }
+    }
+}
+
+// The machine state shown by the inspectors, captured so the debugger lock is held briefly.
+#[derive(Debug, Clone, Default)]
+pub struct MachineSnapshot {
+    pub registers: [u8; 16],
+    pub flags: Flags,
+    pub pc: u8,
+    pub stack: Vec<(u8, Flags)>,
+    pub stack_depth: usize,
+    pub cycles: u64,
+    pub code: Vec<u16>,
+    pub data: Vec<u8>,
+}
+
+impl MachineSnapshot {
+    pub fn capture(debugger: &Debugger) -> Self {
+        let interp = &debugger.interpreter;
+        Self {
+            registers: interp.registers,
+            flags: interp.flags,
+            pc: interp.pc,
+            stack: interp.stack.clone(),
+            stack_depth: interp.stack_depth,
+            cycles: interp.cycles,
+            code: interp.code.clone(),
+            data: interp.bus.dump(),
+        }
+    }
+}
+
+// In-place edits of the paused machine.
+#[derive(Debug, Clone, Copy)]
+pub enum Poke {
+    Register(u8, u8),
+    Pc(u8),
+    Flags(Flags),
+    Code(usize, u16),
+    Data(u8, u8),
+}
+
+pub fn flash(entry: &Entry) {
+    let style = entry.get_style_context();
+    style.add_class("changed");
+    glib::timeout_add_local(FLASH_MILLIS, move || {
+        style.remove_class("changed");
+        glib::Continue(false)
+    });
+}
+
+struct RegisterState {
+    format: Cell<ValueFormat>,
+    snapshot: RefCell<MachineSnapshot>,
+    registers: Vec<Entry>,
+    flags: Vec<CheckButton>,
+    pc: Entry,
+    cycles: Label,
+    stack: Label,
+    updating: Cell<bool>,
+}
+
+pub struct RegisterPane {
+    pub widget: GtkBox,
+    state: Rc<RegisterState>,
+}
+
+fn value_entry() -> Entry {
+    let entry = Entry::new();
+    entry.set_width_chars(10);
+    entry.get_style_context().add_class("mono");
+    entry
+}
+
+impl RegisterState {
+    fn render(&self, changed: &[bool; 16]) {
+        self.updating.set(true);
+        let snapshot = self.snapshot.borrow();
+        let format = self.format.get();
+        for (ii, entry) in self.registers.iter().enumerate() {
+            entry.set_text(&format.format(snapshot.registers[ii]));
+            if changed[ii] {
+                flash(entry);
+            }
+        }
+
+        let flags = snapshot.flags;
+        let values = [flags.z, flags.c, flags.n, flags.v, flags.ie, flags.if_];
+        for (check, value) in self.flags.iter().zip(values.iter()) {
+            check.set_active(*value);
+        }
+        self.pc.set_text(&format!("0x{:02X}", snapshot.pc));
+        self.cycles.set_text(&snapshot.cycles.to_string());
+
+        let mut stack = format!("{}/{}", snapshot.stack.len(), snapshot.stack_depth);
+        for (address, flags) in snapshot.stack.iter().rev() {
+            stack.push_str(&format!(
+                "\n0x{:02X}  flags 0x{:02X}",
+                address,
+                flags.to_byte()
+            ));
+        }
+        self.stack.set_text(&stack);
+        self.updating.set(false);
+    }
+
+    fn edited_flags(&self) -> Flags {
+        let active: Vec<bool> = self.flags.iter().map(|check| check.get_active()).collect();
+        Flags {
+            z: active[0],
+            c: active[1],
+            n: active[2],
+            v: active[3],
+            ie: active[4],
+            if_: active[5],
+        }
+    }
+}
+
+impl RegisterPane {
+    pub fn new() -> Self {
+        let format = ComboBoxText::new();
+        for value_format in ValueFormat::ALL.iter() {
+            format.append_text(&format!("{:?}", value_format));
+        }
+        format.set_active(Some(0));
+
+        let grid = Grid::new();
+        grid.set_row_spacing(2);
+        grid.set_column_spacing(6);
+        let registers: Vec<Entry> = (0..16).map(|_| value_entry()).collect();
+        for (ii, entry) in registers.iter().enumerate() {
+            let (row, column) = ((ii % 8) as i32, (ii / 8) as i32 * 2);
+            grid.attach(&Label::new(Some(&format!("r{}", ii))), column, row, 1, 1);
+            grid.attach(entry, column + 1, row, 1, 1);
+        }
+
+        let flag_row = GtkBox::new(Orientation::Horizontal, 4);
+        let flags: Vec<CheckButton> = ["Z", "C", "N", "V", "IE", "IF"]
+            .iter()
+            .map(|name| {
+                let check = CheckButton::with_label(name);
+                flag_row.pack_start(&check, false, false, 0);
+                check
+            })
+            .collect();
+
+        let pc = value_entry();
+        let cycles = Label::new(None);
+        let pc_row = GtkBox::new(Orientation::Horizontal, 6);
+        pc_row.pack_start(&Label::new(Some("PC")), false, false, 0);
+        pc_row.pack_start(&pc, false, false, 0);
+        pc_row.pack_start(&Label::new(Some("Cycles")), false, false, 0);
+        pc_row.pack_start(&cycles, false, false, 0);
+
+        let stack = Label::new(None);
+        stack.set_xalign(0.0);
+        stack.set_yalign(0.0);
+        stack.get_style_context().add_class("mono");
+        let stack_frame = Frame::new(Some("Call stack"));
+        stack_frame.add(&stack);
+
+        let widget = GtkBox::new(Orientation::Vertical, 6);
+        widget.set_border_width(6);
+        widget.pack_start(&format, false, false, 0);
+        widget.pack_start(&grid, false, false, 0);
+        widget.pack_start(&flag_row, false, false, 0);
+        widget.pack_start(&pc_row, false, false, 0);
+        widget.pack_start(&stack_frame, false, false, 0);
+
+        let state = Rc::new(RegisterState {
+            format: Cell::new(ValueFormat::Hex),
+            snapshot: RefCell::new(MachineSnapshot::default()),
+            registers,
+            flags,
+            pc,
+            cycles,
+            stack,
+            updating: Cell::new(false),
+        });
+
+        let weak = Rc::downgrade(&state);
+        format.connect_changed(move |combo| {
+            if let (Some(state), Some(index)) = (weak.upgrade(), combo.get_active()) {
+                state.format.set(ValueFormat::ALL[index as usize]);
+                state.render(&[false; 16]);
+            }
+        });
+
+        Self { widget, state }
+    }
+
+    pub fn refresh(&self, snapshot: &MachineSnapshot) {
+        let mut changed = [false; 16];
+        {
+            let previous = self.state.snapshot.borrow();
+            for (ii, flag) in changed.iter_mut().enumerate() {
+                *flag = previous.registers[ii] != snapshot.registers[ii];
+            }
+        }
+        *self.state.snapshot.borrow_mut() = snapshot.clone();
+        self.state.render(&changed);
+    }
+
+    pub fn connect_poke<F: Fn(Poke) + Clone + 'static>(&self, callback: F) {
+        for (ii, entry) in self.state.registers.iter().enumerate() {
+            let weak = Rc::downgrade(&self.state);
+            let callback = callback.clone();
+            entry.connect_activate(move |entry| {
+                let state = match weak.upgrade() {
+                    Some(state) => state,
+                    None => return,
+                };
+                match state.format.get().parse(&entry.get_text()) {
+                    Some(value) => callback(Poke::Register(ii as u8, value)),
+                    None => state.render(&[false; 16]),
+                }
+            });
+        }
+
+        let weak = Rc::downgrade(&self.state);
+        let pc_callback = callback.clone();
+        self.state.pc.connect_activate(move |entry| {
+            let state = match weak.upgrade() {
+                Some(state) => state,
+                None => return,
+            };
+            match ValueFormat::Hex.parse(&entry.get_text()) {
+                Some(pc) => pc_callback(Poke::Pc(pc)),
+                None => state.render(&[false; 16]),
+            }
+        });
+
+        for check in self.state.flags.iter() {
+            let weak = Rc::downgrade(&self.state);
+            let callback = callback.clone();
+            check.connect_toggled(move |_| {
+                if let Some(state) = weak.upgrade() {
+                    if !state.updating.get() {
+                        callback(Poke::Flags(state.edited_flags()));
+                    }
+                }
+            });
+        }
+    }
+}
@@ -1,28 +1,46 @@
-extern crate gtk;
+extern crate cairo;
+extern crate gdk;
 extern crate gio;
-use gtk::prelude::*;
+extern crate glib;
+extern crate gtk;
+extern crate pango;
 use gio::prelude::*;
-use gtk::{Application, ApplicationWindow, Button};
+use gtk::Application;
+
+mod board;
+mod diagnostics;
+mod editor;
+mod highlight;
+mod inspector;
+mod memory;
+mod runner;
+mod style;
+mod terminal;
+mod window;
+
+use window::IdeWindow;
 
 fn main() {
     let application = Application::new(
-        Some("com.github.gtk-rs.examples.basic"),
-        Default::default(),
-    ).expect("failed to initialize GTK application");
+        Some("com.github.dewyer.neorisc"),
+        gio::ApplicationFlags::HANDLES_OPEN,
+    )
+    .expect("failed to initialize GTK application");
 
+    application.connect_startup(|_| style::install());
     application.connect_activate(|app| {
-        let window = ApplicationWindow::new(app);
-        window.set_title("First GTK+ Program");
-        window.set_default_size(350, 70);
-
-        let button = Button::with_label("Click me!");
-        button.connect_clicked(|_| {
-            println!("Clicked!");
-        });
-        window.add(&button);
-
-        window.show_all();
+        IdeWindow::new(app).show();
+    });
+    application.connect_open(|app, files, _| {
+        for file in files {
+            let window = IdeWindow::new(app);
+            if let Some(path) = file.get_path() {
+                window.open(&path);
+            }
+            window.show();
+        }
     });
 
-    application.run(&[]);
+    let args: Vec<String> = std::env::args().collect();
+    application.run(&args);
 }
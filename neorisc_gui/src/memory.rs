@@ -0,0 +1,165 @@
+use crate::style::{CHANGED_BACKGROUND, CURRENT_BACKGROUND, FLASH_MILLIS};
+use gtk::prelude::*;
+use gtk::{CellRendererText, ListStore, ScrolledWindow, TreeView, TreeViewColumn};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+const ADDRESS_COLUMN: u32 = 0;
+
+// A hex grid over code words or data bytes; cells are edited in place.
+pub struct MemoryView {
+    pub widget: ScrolledWindow,
+    view: TreeView,
+    store: ListStore,
+    per_row: usize,
+    digits: usize,
+    values: RefCell<Vec<u16>>,
+    current: Cell<Option<usize>>,
+    flash: Cell<u64>,
+}
+
+fn value_column(cell: usize) -> u32 {
+    1 + cell as u32 * 2
+}
+
+fn background_column(cell: usize) -> u32 {
+    value_column(cell) + 1
+}
+
+impl MemoryView {
+    pub fn new(per_row: usize, digits: usize) -> Rc<Self> {
+        let types = vec![glib::Type::String; 1 + per_row * 2];
+        let store = ListStore::new(&types);
+        let view = TreeView::with_model(&store);
+        view.set_enable_search(false);
+        view.get_style_context().add_class("mono");
+
+        let renderer = CellRendererText::new();
+        renderer.set_property_family(Some("monospace"));
+        let column = TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.add_attribute(&renderer, "text", ADDRESS_COLUMN as i32);
+        view.append_column(&column);
+
+        let widget = ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        widget.set_size_request(-1, 160);
+        widget.add(&view);
+
+        let memory = Rc::new(Self {
+            widget,
+            view,
+            store,
+            per_row,
+            digits,
+            values: RefCell::new(Vec::new()),
+            current: Cell::new(None),
+            flash: Cell::new(0),
+        });
+
+        for cell in 0..per_row {
+            let renderer = CellRendererText::new();
+            renderer.set_property_family(Some("monospace"));
+            renderer.set_property_editable(true);
+            let column = TreeViewColumn::new();
+            column.set_title(&format!("{:X}", cell));
+            column.pack_start(&renderer, true);
+            column.add_attribute(&renderer, "text", value_column(cell) as i32);
+            column.add_attribute(&renderer, "cell-background", background_column(cell) as i32);
+            memory.view.append_column(&column);
+        }
+        memory
+    }
+
+    pub fn connect_edited<F: Fn(usize, u16) + Clone + 'static>(&self, callback: F) {
+        for cell in 0..self.per_row {
+            let column = match self.view.get_column(cell as i32 + 1) {
+                Some(column) => column,
+                None => continue,
+            };
+            for renderer in column.get_cells() {
+                if let Ok(renderer) = renderer.downcast::<CellRendererText>() {
+                    let callback = callback.clone();
+                    let per_row = self.per_row;
+                    renderer.connect_edited(move |_, path, text| {
+                        let text = text.trim().trim_start_matches("0x");
+                        if let (Some(row), Ok(value)) =
+                            (path.get_indices().first(), u16::from_str_radix(text, 16))
+                        {
+                            callback(*row as usize * per_row + cell, value);
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    // Shows new contents; cells that differ from the previous contents flash briefly.
+    pub fn refresh(self: &Rc<Self>, values: &[u16], current: Option<usize>) {
+        let changed: Vec<bool> = {
+            let previous = self.values.borrow();
+            values
+                .iter()
+                .enumerate()
+                .map(|(ii, value)| previous.len() == values.len() && previous[ii] != *value)
+                .collect()
+        };
+        *self.values.borrow_mut() = values.to_vec();
+        self.current.set(current);
+        self.render(&changed);
+
+        if changed.iter().any(|changed| *changed) {
+            let generation = self.flash.get() + 1;
+            self.flash.set(generation);
+            let memory = Rc::downgrade(self);
+            glib::timeout_add_local(FLASH_MILLIS, move || {
+                if let Some(memory) = memory.upgrade() {
+                    if memory.flash.get() == generation {
+                        memory.render(&[]);
+                    }
+                }
+                glib::Continue(false)
+            });
+        }
+    }
+
+    fn render(&self, changed: &[bool]) {
+        let values = self.values.borrow();
+        let rows = (values.len() + self.per_row - 1) / self.per_row;
+        if self.store.iter_n_children(None) as usize != rows {
+            self.store.clear();
+            for _ in 0..rows {
+                self.store.append();
+            }
+        }
+
+        let current = self.current.get();
+        let mut iter = self.store.get_iter_first();
+        for (row, chunk) in values.chunks(self.per_row).enumerate() {
+            let iter = match iter.as_mut() {
+                Some(iter) => iter,
+                None => break,
+            };
+            let start = row * self.per_row;
+            self.store
+                .set_value(iter, ADDRESS_COLUMN, &format!("{:02X}", start).to_value());
+            for (cell, value) in chunk.iter().enumerate() {
+                let address = start + cell;
+                let background = if current == Some(address) {
+                    Some(CURRENT_BACKGROUND)
+                } else if changed.get(address).cloned().unwrap_or(false) {
+                    Some(CHANGED_BACKGROUND)
+                } else {
+                    None
+                };
+                let text = format!("{:0width$X}", value, width = self.digits);
+                self.store
+                    .set_value(iter, value_column(cell), &text.to_value());
+                self.store
+                    .set_value(iter, background_column(cell), &background.to_value());
+            }
+            if !self.store.iter_next(iter) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use neorisc_lib::debugger::{Debugger, StopReason};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Emulation runs in slices so the inspectors can follow a running program.
+const SLICE: Duration = Duration::from_millis(20);
+pub const DEFAULT_SPEED: u32 = 1000;
+
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Run,
+    Pause,
+    Step,
+    StepOver,
+    StepBack,
+    ReverseContinue,
+    Reset,
+    Speed(u32),
+}
+
+#[derive(Debug, Clone)]
+pub enum RunnerEvent {
+    Started,
+    Progress,
+    Stopped(StopReason),
+}
+
+// Owns the emulation thread; dropping the runner stops it.
+pub struct Runner {
+    pub debugger: Arc<Mutex<Debugger>>,
+    commands: mpsc::Sender<Command>,
+    running: Arc<AtomicBool>,
+}
+
+struct Worker {
+    debugger: Arc<Mutex<Debugger>>,
+    commands: Receiver<Command>,
+    events: glib::Sender<RunnerEvent>,
+    running: Arc<AtomicBool>,
+    speed: u32,
+    // The instruction a run starts on does not trigger its breakpoint again.
+    leaving_breakpoint: bool,
+}
+
+impl Worker {
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    fn stop(&self, reason: StopReason) {
+        self.running.store(false, Ordering::SeqCst);
+        let _ = self.events.send(RunnerEvent::Stopped(reason));
+    }
+
+    fn execute(&mut self, command: Command) {
+        let running = self.is_running();
+        match command {
+            Command::Run if !running => {
+                self.leaving_breakpoint = true;
+                self.running.store(true, Ordering::SeqCst);
+                let _ = self.events.send(RunnerEvent::Started);
+            }
+            Command::Pause if running => self.stop(StopReason::Step),
+            Command::Step if !running => {
+                let reason = self.debugger.lock().expect("Debugger lock poisoned").step();
+                self.stop(reason);
+            }
+            Command::StepOver if !running => {
+                let reason = self
+                    .debugger
+                    .lock()
+                    .expect("Debugger lock poisoned")
+                    .step_over();
+                self.stop(reason);
+            }
+            Command::StepBack if !running => {
+                let reason = self
+                    .debugger
+                    .lock()
+                    .expect("Debugger lock poisoned")
+                    .step_back();
+                self.stop(reason);
+            }
+            Command::ReverseContinue if !running => {
+                let reason = self
+                    .debugger
+                    .lock()
+                    .expect("Debugger lock poisoned")
+                    .reverse_continue();
+                self.stop(reason);
+            }
+            Command::Reset => {
+                self.debugger
+                    .lock()
+                    .expect("Debugger lock poisoned")
+                    .reset();
+                self.stop(StopReason::Step);
+            }
+            Command::Speed(speed) => self.speed = speed.max(1),
+            _ => {}
+        }
+    }
+
+    // Instructions per slice and the slice period; slow speeds run one instruction per period.
+    fn pace(&self) -> (u64, Duration) {
+        let per_slice = self.speed as u64 * SLICE.as_millis() as u64 / 1000;
+        if per_slice == 0 {
+            (1, Duration::from_secs(1) / self.speed)
+        } else {
+            (per_slice, SLICE)
+        }
+    }
+
+    fn run_slice(&mut self, budget: u64) {
+        let mut debugger = self.debugger.lock().expect("Debugger lock poisoned");
+        for _ in 0..budget {
+            let pc = debugger.interpreter.pc;
+            if !self.leaving_breakpoint && debugger.breakpoints.contains(&pc) {
+                drop(debugger);
+                self.stop(StopReason::Breakpoint(pc));
+                return;
+            }
+            self.leaving_breakpoint = false;
+            match debugger.step() {
+                StopReason::Step => {}
+                reason => {
+                    drop(debugger);
+                    self.stop(reason);
+                    return;
+                }
+            }
+        }
+        let _ = self.events.send(RunnerEvent::Progress);
+    }
+
+    fn work(mut self) {
+        let mut next_slice = Instant::now();
+        loop {
+            let command = if self.is_running() {
+                let wait = next_slice.saturating_duration_since(Instant::now());
+                match self.commands.recv_timeout(wait) {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match self.commands.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                }
+            };
+
+            match command {
+                Some(command) => {
+                    if let Command::Run = command {
+                        next_slice = Instant::now();
+                    }
+                    self.execute(command);
+                }
+                None => {
+                    let (budget, period) = self.pace();
+                    next_slice += period;
+                    self.run_slice(budget);
+                }
+            }
+        }
+    }
+}
+
+impl Runner {
+    pub fn new(debugger: Debugger, speed: u32, events: glib::Sender<RunnerEvent>) -> Self {
+        let debugger = Arc::new(Mutex::new(debugger));
+        let running = Arc::new(AtomicBool::new(false));
+        let (commands, receiver) = mpsc::channel();
+        let worker = Worker {
+            debugger: debugger.clone(),
+            commands: receiver,
+            events,
+            running: running.clone(),
+            speed: speed.max(1),
+            leaving_breakpoint: false,
+        };
+        thread::spawn(move || worker.work());
+
+        Self {
+            debugger,
+            commands,
+            running,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    fn send(&self, command: Command) {
+        let _ = self.commands.send(command);
+    }
+
+    pub fn run(&self) {
+        self.send(Command::Run);
+    }
+
+    pub fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    pub fn step(&self) {
+        self.send(Command::Step);
+    }
+
+    pub fn step_over(&self) {
+        self.send(Command::StepOver);
+    }
+
+    pub fn step_back(&self) {
+        self.send(Command::StepBack);
+    }
+
+    pub fn reverse_continue(&self) {
+        self.send(Command::ReverseContinue);
+    }
+
+    pub fn reset(&self) {
+        self.send(Command::Reset);
+    }
+
+    pub fn set_speed(&self, speed: u32) {
+        self.send(Command::Speed(speed));
+    }
+}
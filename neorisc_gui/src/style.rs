@@ -0,0 +1,25 @@
+use gtk::prelude::*;
+use gtk::{CssProvider, StyleContext, STYLE_PROVIDER_PRIORITY_APPLICATION};
+
+pub const CURRENT_BACKGROUND: &str = "#b5e3a1";
+pub const CHANGED_BACKGROUND: &str = "#fce94f";
+pub const FLASH_MILLIS: u32 = 600;
+
+const CSS: &[u8] = b"
+entry.changed { background-color: #fce94f; background-image: none; }
+label.mono, entry.mono { font-family: monospace; }
+";
+
+pub fn install() {
+    let provider = CssProvider::new();
+    provider
+        .load_from_data(CSS)
+        .expect("Invalid application stylesheet");
+    if let Some(screen) = gdk::Screen::get_default() {
+        StyleContext::add_provider_for_screen(
+            &screen,
+            &provider,
+            STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+    }
+}
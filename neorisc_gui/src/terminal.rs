@@ -0,0 +1,177 @@
+use gtk::prelude::*;
+use gtk::{
+    Box as GtkBox, Button, CheckButton, Inhibit, Orientation, ScrolledWindow, TextBuffer,
+    TextTagTable, TextView, WrapMode,
+};
+use neorisc_lib::interpreter::uart::SerialBuffer;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+const HEX_BYTES_PER_LINE: usize = 16;
+
+struct TerminalState {
+    view: TextView,
+    buffer: TextBuffer,
+    serial: SerialBuffer,
+    // Everything shown in the terminal: transmitted bytes and echoed keystrokes.
+    log: RefCell<Vec<u8>>,
+    echo: CheckButton,
+    hex: CheckButton,
+}
+
+// Console of the emulated UART, typed keys are queued into its receive FIFO.
+pub struct TerminalPanel {
+    pub widget: GtkBox,
+    pub save: Button,
+    state: Rc<TerminalState>,
+}
+
+fn format_text(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .filter(|byte| **byte != b'\r')
+        .map(|byte| *byte as char)
+        .collect()
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    bytes
+        .chunks(HEX_BYTES_PER_LINE)
+        .map(|line| {
+            line.iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl TerminalState {
+    fn scroll_to_end(&self) {
+        let mut end = self.buffer.get_end_iter();
+        self.view.scroll_to_iter(&mut end, 0.0, false, 0.0, 0.0);
+    }
+
+    fn render(&self) {
+        let log = self.log.borrow();
+        if self.hex.get_active() {
+            self.buffer.set_text(&format_hex(&log));
+        } else {
+            self.buffer.set_text(&format_text(&log));
+        }
+        self.scroll_to_end();
+    }
+
+    fn append(&self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.log.borrow_mut().extend_from_slice(bytes);
+        if self.hex.get_active() {
+            // Hex lines are laid out over the whole log.
+            self.render();
+        } else {
+            self.buffer
+                .insert(&mut self.buffer.get_end_iter(), &format_text(bytes));
+            self.scroll_to_end();
+        }
+    }
+
+    fn type_key(&self, key: char) -> bool {
+        let key = if key == '\r' { '\n' } else { key };
+        if !key.is_ascii() {
+            return false;
+        }
+        let bytes = [key as u8];
+        self.serial.push_input(&bytes);
+        if self.echo.get_active() {
+            self.append(&bytes);
+        }
+        true
+    }
+}
+
+impl TerminalPanel {
+    pub fn new(serial: SerialBuffer) -> Self {
+        let buffer = TextBuffer::new(None::<&TextTagTable>);
+        let view = TextView::with_buffer(&buffer);
+        view.set_monospace(true);
+        view.set_editable(false);
+        view.set_wrap_mode(WrapMode::Char);
+        view.set_can_focus(true);
+        view.set_tooltip_text(Some("Keys typed here are sent to the UART"));
+        let scrolled = ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+        scrolled.add(&view);
+
+        let echo = CheckButton::with_label("Local echo");
+        let hex = CheckButton::with_label("Hex");
+        let clear = Button::with_label("Clear");
+        let save = Button::with_label("Save log…");
+        let options = GtkBox::new(Orientation::Horizontal, 6);
+        options.pack_start(&echo, false, false, 0);
+        options.pack_start(&hex, false, false, 0);
+        options.pack_end(&save, false, false, 0);
+        options.pack_end(&clear, false, false, 0);
+
+        let widget = GtkBox::new(Orientation::Vertical, 2);
+        widget.pack_start(&scrolled, true, true, 0);
+        widget.pack_start(&options, false, false, 0);
+
+        let state = Rc::new(TerminalState {
+            view,
+            buffer,
+            serial,
+            log: RefCell::new(Vec::new()),
+            echo,
+            hex,
+        });
+
+        let weak = Rc::downgrade(&state);
+        state.view.connect_key_press_event(move |_, event| {
+            let typed = weak.upgrade().and_then(|state| {
+                event
+                    .get_keyval()
+                    .to_unicode()
+                    .map(|key| state.type_key(key))
+            });
+            Inhibit(typed.unwrap_or(false))
+        });
+        let weak = Rc::downgrade(&state);
+        state.hex.connect_toggled(move |_| {
+            if let Some(state) = weak.upgrade() {
+                state.render();
+            }
+        });
+        let weak = Rc::downgrade(&state);
+        clear.connect_clicked(move |_| {
+            if let Some(state) = weak.upgrade() {
+                state.log.borrow_mut().clear();
+                state.render();
+            }
+        });
+
+        Self {
+            widget,
+            save,
+            state,
+        }
+    }
+
+    pub fn serial(&self) -> SerialBuffer {
+        self.state.serial.clone()
+    }
+
+    // Moves the bytes the program transmitted since the last poll into the console.
+    pub fn poll(&self) {
+        let output = self.state.serial.take_output();
+        self.state.append(&output);
+    }
+
+    pub fn save_log(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, &*self.state.log.borrow())
+    }
+}
@@ -0,0 +1,614 @@
+use crate::board::{BoardInput, BoardPanel, BoardState};
+use crate::diagnostics::DiagnosticsPanel;
+use crate::editor::Editor;
+use crate::inspector::{MachineSnapshot, Poke, RegisterPane};
+use crate::memory::MemoryView;
+use crate::runner::{Runner, RunnerEvent, DEFAULT_SPEED};
+use crate::terminal::TerminalPanel;
+use gio::prelude::*;
+use glib::clone;
+use gtk::prelude::*;
+use gtk::{
+    Application, ApplicationWindow, Box as GtkBox, Button, ButtonsType, DialogFlags,
+    FileChooserAction, FileChooserDialog, FileFilter, HeaderBar, Label, MessageDialog, MessageType,
+    Notebook, Orientation, Paned, ResponseType, Scale,
+};
+use neorisc_lib::compiler::{RiscCompiler, RiscCompilerConfig};
+use neorisc_lib::debugger::repl::format_stop;
+use neorisc_lib::debugger::Debugger;
+use neorisc_lib::interpreter::board::attach_lab_board;
+use neorisc_lib::interpreter::bus::Bus;
+use neorisc_lib::interpreter::uart::{SerialBuffer, Uart, UART_ADDRESS, UART_REGISTER_COUNT};
+use neorisc_lib::program::RiscProgram;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+type ActionHandler = fn(&IdeState);
+
+const CONTROL_ACTIONS: [&str; 7] = [
+    "run",
+    "pause",
+    "step",
+    "step-over",
+    "step-back",
+    "reverse-continue",
+    "reset",
+];
+
+pub struct IdeWindow {
+    state: Rc<IdeState>,
+}
+
+struct IdeState {
+    window: ApplicationWindow,
+    header: HeaderBar,
+    editor: Editor,
+    diagnostics: DiagnosticsPanel,
+    terminal: TerminalPanel,
+    status: Label,
+    path: RefCell<Option<PathBuf>>,
+    program: RefCell<Option<RiscProgram>>,
+    registers: RegisterPane,
+    board: BoardPanel,
+    code_view: Rc<MemoryView>,
+    data_view: Rc<MemoryView>,
+    speed: Scale,
+    runner: RefCell<Option<Runner>>,
+    events: glib::Sender<RunnerEvent>,
+}
+
+// The speed slider is logarithmic, from 1 to a million instructions per second.
+fn slider_speed(value: f64) -> u32 {
+    10f64.powf(value).round() as u32
+}
+
+fn action_button(label: &str, action: &str, tooltip: &str) -> Button {
+    let button = Button::with_label(label);
+    button.set_action_name(Some(action));
+    button.set_tooltip_text(Some(tooltip));
+    button
+}
+
+impl IdeState {
+    fn update_title(&self) {
+        let name = self
+            .path
+            .borrow()
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "untitled.s".to_string());
+        let modified = if self.editor.buffer.get_modified() {
+            "*"
+        } else {
+            ""
+        };
+        self.header
+            .set_title(Some(&format!("{}{}", modified, name)));
+        self.header.set_subtitle(
+            self.path
+                .borrow()
+                .as_ref()
+                .and_then(|path| path.parent())
+                .and_then(Path::to_str),
+        );
+    }
+
+    fn show_error(&self, message: &str) {
+        let dialog = MessageDialog::new(
+            Some(&self.window),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            MessageType::Error,
+            ButtonsType::Close,
+            message,
+        );
+        dialog.run();
+        dialog.close();
+    }
+
+    fn choose_file(&self, action: FileChooserAction) -> Option<PathBuf> {
+        let (title, accept) = match action {
+            FileChooserAction::Save => ("Save assembly source", "_Save"),
+            _ => ("Open assembly source", "_Open"),
+        };
+        self.file_dialog(
+            action,
+            title,
+            accept,
+            ("Assembly sources", &["*.s", "*.asm"]),
+        )
+    }
+
+    fn file_dialog(
+        &self,
+        action: FileChooserAction,
+        title: &str,
+        accept: &str,
+        (filter_name, patterns): (&str, &[&str]),
+    ) -> Option<PathBuf> {
+        let dialog = FileChooserDialog::with_buttons(
+            Some(title),
+            Some(&self.window),
+            action,
+            &[
+                ("_Cancel", ResponseType::Cancel),
+                (accept, ResponseType::Accept),
+            ],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+        let filter = FileFilter::new();
+        filter.set_name(Some(filter_name));
+        for pattern in patterns.iter() {
+            filter.add_pattern(pattern);
+        }
+        dialog.add_filter(&filter);
+
+        let path = match dialog.run() {
+            ResponseType::Accept => dialog.get_filename(),
+            _ => None,
+        };
+        dialog.close();
+        path
+    }
+
+    fn open_path(&self, path: &Path) {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                self.editor.set_text(&source);
+                *self.path.borrow_mut() = Some(path.to_path_buf());
+                self.update_title();
+                self.assemble();
+            }
+            Err(err) => self.show_error(&format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    fn open(&self) {
+        if let Some(path) = self.choose_file(FileChooserAction::Open) {
+            self.open_path(&path);
+        }
+    }
+
+    fn save_to(&self, path: PathBuf) {
+        match fs::write(&path, self.editor.text()) {
+            Ok(()) => {
+                *self.path.borrow_mut() = Some(path);
+                self.editor.buffer.set_modified(false);
+                self.update_title();
+            }
+            Err(err) => self.show_error(&format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    fn save(&self) {
+        let path = self.path.borrow().clone();
+        match path {
+            Some(path) => self.save_to(path),
+            None => self.save_as(),
+        }
+    }
+
+    fn save_as(&self) {
+        if let Some(path) = self.choose_file(FileChooserAction::Save) {
+            self.save_to(path);
+        }
+    }
+
+    fn save_terminal_log(&self) {
+        let path = self.file_dialog(
+            FileChooserAction::Save,
+            "Save terminal log",
+            "_Save",
+            ("Logs", &["*.log", "*.txt"]),
+        );
+        if let Some(path) = path {
+            if let Err(err) = self.terminal.save_log(&path) {
+                self.show_error(&format!("{}: {}", path.display(), err));
+            }
+        }
+    }
+
+    fn assemble(&self) {
+        let mut compiler = RiscCompiler::new(self.editor.text(), RiscCompilerConfig::default());
+        compiler.compile();
+        let diagnostics = compiler.diagnostics();
+        self.diagnostics.set_diagnostics(&diagnostics);
+        let lines: Vec<usize> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.line.saturating_sub(1))
+            .collect();
+        self.editor.set_diagnostic_lines(&lines);
+
+        let program = compiler
+            .program()
+            .filter(|_| diagnostics.is_empty())
+            .cloned();
+        let status = match &program {
+            Some(program) => format!(
+                "Assembled: {} code words, {} data bytes",
+                program.used_code_size(),
+                program.data.len()
+            ),
+            None => format!("{} error(s)", diagnostics.len()),
+        };
+        self.status.set_text(&status);
+        *self.runner.borrow_mut() = program.clone().map(|program| {
+            let mut debugger = Debugger::new(program, Some(&self.editor.text())).with_history();
+            let bus = &mut debugger.interpreter.bus;
+            attach_lab_board(bus).expect("Lab board address overlap");
+            bus.attach(
+                UART_ADDRESS,
+                UART_REGISTER_COUNT,
+                Uart::new(self.terminal.serial()),
+            )
+            .expect("UART address overlap");
+            for input in self.board.switches() {
+                input.apply(&mut debugger);
+            }
+            let speed = slider_speed(self.speed.get_value());
+            Runner::new(debugger, speed, self.events.clone())
+        });
+        *self.program.borrow_mut() = program;
+        self.sync_breakpoints();
+        self.refresh_inspectors(true);
+        self.update_controls();
+    }
+
+    fn sync_breakpoints(&self) {
+        if let Some(runner) = self.runner.borrow().as_ref() {
+            let mut debugger = runner.debugger.lock().expect("Debugger lock poisoned");
+            let addresses = self
+                .editor
+                .breakpoints()
+                .into_iter()
+                .flat_map(|line| debugger.addresses_for_line(line))
+                .collect();
+            debugger.breakpoints = addresses;
+        }
+    }
+
+    fn toggle_breakpoint(&self, line: usize) {
+        self.editor.toggle_breakpoint(line);
+        self.sync_breakpoints();
+    }
+
+    fn update_controls(&self) {
+        let runner = self.runner.borrow();
+        let running = runner.as_ref().map(Runner::is_running).unwrap_or(false);
+        for name in CONTROL_ACTIONS.iter() {
+            let enabled = match *name {
+                "pause" => running,
+                "reset" => runner.is_some(),
+                _ => !running,
+            };
+            if let Some(action) = self
+                .window
+                .lookup_action(name)
+                .and_then(|action| action.downcast::<gio::SimpleAction>().ok())
+            {
+                action.set_enabled(enabled);
+            }
+        }
+    }
+
+    // Assembles first when there is nothing to run yet.
+    fn control(&self, command: fn(&Runner)) {
+        if self.runner.borrow().is_none() {
+            self.assemble();
+        }
+        if let Some(runner) = self.runner.borrow().as_ref() {
+            command(runner);
+        }
+    }
+
+    fn run(&self) {
+        self.control(Runner::run);
+    }
+
+    fn pause(&self) {
+        self.control(Runner::pause);
+    }
+
+    fn step(&self) {
+        self.control(Runner::step);
+    }
+
+    fn step_over(&self) {
+        self.control(Runner::step_over);
+    }
+
+    fn step_back(&self) {
+        self.control(Runner::step_back);
+    }
+
+    fn reverse_continue(&self) {
+        self.control(Runner::reverse_continue);
+    }
+
+    fn reset(&self) {
+        self.control(Runner::reset);
+    }
+
+    fn set_speed(&self) {
+        if let Some(runner) = self.runner.borrow().as_ref() {
+            runner.set_speed(slider_speed(self.speed.get_value()));
+        }
+    }
+
+    fn handle_event(&self, event: RunnerEvent) {
+        match event {
+            RunnerEvent::Started => self.status.set_text("Running"),
+            RunnerEvent::Progress => self.refresh_inspectors(false),
+            RunnerEvent::Stopped(reason) => {
+                let status = self.runner.borrow().as_ref().map(|runner| {
+                    let debugger = runner.debugger.lock().expect("Debugger lock poisoned");
+                    format_stop(&debugger, &reason)
+                });
+                if let Some(status) = status {
+                    self.status
+                        .set_text(status.lines().next().unwrap_or_default());
+                }
+                self.refresh_inspectors(true);
+            }
+        }
+        self.update_controls();
+    }
+
+    fn refresh_inspectors(&self, scroll: bool) {
+        let (snapshot, board, line) = match self.runner.borrow().as_ref() {
+            Some(runner) => {
+                let debugger = runner.debugger.lock().expect("Debugger lock poisoned");
+                (
+                    MachineSnapshot::capture(&debugger),
+                    BoardState::capture(&debugger),
+                    debugger.current_line(),
+                )
+            }
+            None => (MachineSnapshot::default(), BoardState::default(), None),
+        };
+        self.editor.set_current_line(line, scroll);
+        self.terminal.poll();
+        self.registers.refresh(&snapshot);
+        self.board.refresh(board);
+        let code: Vec<u16> = snapshot.code.clone();
+        let data: Vec<u16> = snapshot.data.iter().map(|byte| *byte as u16).collect();
+        self.code_view.refresh(&code, Some(snapshot.pc as usize));
+        self.data_view.refresh(&data, None);
+    }
+
+    // Board inputs reach the machine while it runs, like on the real board.
+    fn board_input(&self, input: BoardInput) {
+        if let Some(runner) = self.runner.borrow().as_ref() {
+            input.apply(&mut runner.debugger.lock().expect("Debugger lock poisoned"));
+        }
+        if !self
+            .runner
+            .borrow()
+            .as_ref()
+            .map(Runner::is_running)
+            .unwrap_or(false)
+        {
+            self.refresh_inspectors(false);
+        }
+    }
+
+    // Edits are only applied while the program is paused.
+    fn poke(&self, poke: Poke) {
+        if let Some(runner) = self
+            .runner
+            .borrow()
+            .as_ref()
+            .filter(|runner| !runner.is_running())
+        {
+            let mut debugger = runner.debugger.lock().expect("Debugger lock poisoned");
+            let interp = &mut debugger.interpreter;
+            match poke {
+                Poke::Register(register, value) => interp.registers[register as usize] = value,
+                Poke::Pc(pc) => interp.pc = pc,
+                Poke::Flags(flags) => interp.flags = flags,
+                Poke::Code(address, word) => {
+                    if let Some(slot) = interp.code.get_mut(address) {
+                        *slot = word;
+                    }
+                }
+                Poke::Data(address, value) => interp.bus.write(address, value),
+            }
+        }
+        self.refresh_inspectors(false);
+    }
+}
+
+impl IdeWindow {
+    pub fn new(app: &Application) -> Self {
+        let window = ApplicationWindow::new(app);
+        window.set_default_size(1100, 750);
+
+        let header = HeaderBar::new();
+        header.set_show_close_button(true);
+        header.pack_start(&action_button(
+            "Open",
+            "win.open",
+            "Open a source file (Ctrl+O)",
+        ));
+        header.pack_start(&action_button(
+            "Save",
+            "win.save",
+            "Save the source file (Ctrl+S)",
+        ));
+        header.pack_end(&action_button(
+            "Assemble",
+            "win.assemble",
+            "Assemble and list diagnostics (F7)",
+        ));
+        let speed = Scale::with_range(Orientation::Horizontal, 0.0, 6.0, 0.1);
+        speed.set_value((DEFAULT_SPEED as f64).log10());
+        speed.set_size_request(160, -1);
+        speed.set_tooltip_text(Some("Emulation speed"));
+        speed.connect_format_value(|_, value| format!("{} instr/s", slider_speed(value)));
+        let controls = GtkBox::new(Orientation::Horizontal, 2);
+        for (label, action, tooltip) in [
+            ("Run", "win.run", "Run until a breakpoint (F5)"),
+            ("Pause", "win.pause", "Pause the program (F6)"),
+            ("Step", "win.step", "Execute one instruction (F11)"),
+            (
+                "Step Over",
+                "win.step-over",
+                "Step over subroutine calls (F10)",
+            ),
+            ("Back", "win.step-back", "Undo one instruction (Shift+F11)"),
+            (
+                "Reverse",
+                "win.reverse-continue",
+                "Run backwards to a breakpoint (Shift+F6)",
+            ),
+            ("Reset", "win.reset", "Reset the machine (Shift+F5)"),
+        ]
+        .iter()
+        {
+            controls.pack_start(&action_button(label, action, tooltip), false, false, 0);
+        }
+        controls.pack_start(&speed, false, false, 0);
+        header.pack_end(&controls);
+        window.set_titlebar(Some(&header));
+
+        let editor = Editor::new();
+        let diagnostics = DiagnosticsPanel::new();
+        let terminal = TerminalPanel::new(SerialBuffer::new());
+        let console = Notebook::new();
+        console.append_page(&diagnostics.widget, Some(&Label::new(Some("Diagnostics"))));
+        console.append_page(&terminal.widget, Some(&Label::new(Some("Terminal"))));
+        let status = Label::new(None);
+        status.set_xalign(0.0);
+        status.set_margin_start(6);
+        status.set_margin_end(6);
+
+        let registers = RegisterPane::new();
+        let code_view = MemoryView::new(8, 4);
+        let data_view = MemoryView::new(16, 2);
+        let memory = Notebook::new();
+        memory.append_page(&code_view.widget, Some(&Label::new(Some("Code"))));
+        memory.append_page(&data_view.widget, Some(&Label::new(Some("Data"))));
+        let inspectors = Paned::new(Orientation::Vertical);
+        let board = BoardPanel::new();
+        let machine = GtkBox::new(Orientation::Vertical, 4);
+        machine.pack_start(&registers.widget, false, false, 0);
+        machine.pack_start(&board.widget, false, false, 0);
+        inspectors.pack1(&machine, false, false);
+        inspectors.pack2(&memory, true, true);
+
+        let paned = Paned::new(Orientation::Vertical);
+        paned.pack1(&editor.widget, true, false);
+        paned.pack2(&console, false, true);
+        let workspace = Paned::new(Orientation::Horizontal);
+        workspace.pack1(&paned, true, false);
+        workspace.pack2(&inspectors, false, true);
+        let layout = GtkBox::new(Orientation::Vertical, 2);
+        layout.pack_start(&workspace, true, true, 0);
+        layout.pack_start(&status, false, false, 2);
+        window.add(&layout);
+
+        let (events, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let state = Rc::new(IdeState {
+            window,
+            header,
+            editor,
+            diagnostics,
+            terminal,
+            status,
+            path: RefCell::new(None),
+            program: RefCell::new(None),
+            registers,
+            board,
+            code_view,
+            data_view,
+            speed,
+            runner: RefCell::new(None),
+            events,
+        });
+
+        let actions: [(&str, ActionHandler, &[&str]); 11] = [
+            ("open", IdeState::open, &["<Primary>o"]),
+            ("save", IdeState::save, &["<Primary>s"]),
+            ("save-as", IdeState::save_as, &["<Primary><Shift>s"]),
+            ("assemble", IdeState::assemble, &["F7"]),
+            ("run", IdeState::run, &["F5"]),
+            ("pause", IdeState::pause, &["F6"]),
+            ("step", IdeState::step, &["F11"]),
+            ("step-over", IdeState::step_over, &["F10"]),
+            ("step-back", IdeState::step_back, &["<Shift>F11"]),
+            (
+                "reverse-continue",
+                IdeState::reverse_continue,
+                &["<Shift>F6"],
+            ),
+            ("reset", IdeState::reset, &["<Shift>F5"]),
+        ];
+        for (name, handler, accels) in actions.iter() {
+            let action = gio::SimpleAction::new(name, None);
+            let handler = *handler;
+            action.connect_activate(clone!(@weak state => move |_, _| handler(&state)));
+            state.window.add_action(&action);
+            app.set_accels_for_action(&format!("win.{}", name), accels);
+        }
+
+        state
+            .terminal
+            .save
+            .connect_clicked(clone!(@weak state => move |_| state.save_terminal_log()));
+        state.diagnostics.connect_line_activated(
+            clone!(@weak state => move |line| state.editor.goto_line(line)),
+        );
+        state
+            .registers
+            .connect_poke(clone!(@weak state => move |poke| state.poke(poke)));
+        state
+            .board
+            .connect_input(clone!(@weak state => move |input| state.board_input(input)));
+        state.code_view.connect_edited(
+            clone!(@weak state => move |address, word| state.poke(Poke::Code(address, word))),
+        );
+        state
+            .data_view
+            .connect_edited(clone!(@weak state => move |address, value| {
+                state.poke(Poke::Data(address as u8, value as u8))
+            }));
+        state.editor.connect_gutter_clicked(
+            clone!(@weak state => move |line| state.toggle_breakpoint(line)),
+        );
+        state
+            .speed
+            .connect_value_changed(clone!(@weak state => move |_| state.set_speed()));
+        receiver.attach(
+            None,
+            clone!(@weak state => @default-return glib::Continue(false), move |event| {
+                state.handle_event(event);
+                glib::Continue(true)
+            }),
+        );
+        state.refresh_inspectors(false);
+        state.update_controls();
+        state
+            .editor
+            .buffer
+            .connect_modified_changed(clone!(@weak state => move |_| state.update_title()));
+        state.update_title();
+
+        // Handlers only hold weak references, the window owns the state until it is destroyed.
+        let owner = RefCell::new(Some(state.clone()));
+        state.window.connect_destroy(move |_| {
+            owner.borrow_mut().take();
+        });
+
+        Self { state }
+    }
+
+    pub fn open(&self, path: &Path) {
+        self.state.open_path(path);
+    }
+
+    pub fn show(&self) {
+        self.state.window.show_all();
+    }
+}
@@ -0,0 +1,147 @@
+use crate::compiler::layout::{
+    resolve_numeric, string_literal_bytes, LayoutArtifact, Placement, Section, SymbolTable,
+    SymbolValue,
+};
+use crate::compiler::syntax_analyzer::{instruction_form, InstructionForm, OperandValue};
+use crate::compiler::{CompileError, RiscCompilerConfig};
+use crate::error::LangCompileError;
+use crate::isa::{Instruction, Operand, NOP_WORD};
+use crate::lang::{LangCommand, LangLiteral};
+use crate::parser::command_parser::SourceLine;
+use crate::program::{ProgramSection, ProgramSymbol, RiscProgram};
+
+pub struct CodegenArtifact {
+    pub program: RiscProgram,
+    pub errors: Vec<CompileError>,
+}
+
+fn to_byte(value: usize) -> Result<u8, LangCompileError> {
+    if value > u8::MAX as usize {
+        Err(LangCompileError::ValueOutOfBounds)
+    } else {
+        Ok(value as u8)
+    }
+}
+
+fn resolve_operand(value: &OperandValue, symbols: &SymbolTable) -> Result<u8, LangCompileError> {
+    match value {
+        OperandValue::Literal(value) => Ok(*value),
+        OperandValue::Symbol(name) => to_byte(resolve_numeric(
+            &LangLiteral::Symbol(name.clone()),
+            symbols,
+        )?),
+    }
+}
+
+fn lower_instruction(
+    form: InstructionForm,
+    symbols: &SymbolTable,
+) -> Result<Instruction, LangCompileError> {
+    Ok(match form {
+        InstructionForm::Load { rx, address } => Instruction::Load {
+            rx,
+            address: resolve_operand(&address, symbols)?,
+        },
+        InstructionForm::Store { rx, address } => Instruction::Store {
+            rx,
+            address: resolve_operand(&address, symbols)?,
+        },
+        InstructionForm::AluImmediate { op, rx, value } => Instruction::Alu {
+            op,
+            rx,
+            operand: Operand::Immediate(resolve_operand(&value, symbols)?),
+        },
+        InstructionForm::JumpAbsolute { kind, target } => Instruction::Jump {
+            kind,
+            target: Operand::Immediate(resolve_operand(&target, symbols)?),
+        },
+        InstructionForm::Resolved(instruction) => instruction,
+    })
+}
+
+fn data_bytes(line: &SourceLine, symbols: &SymbolTable) -> Result<Vec<u8>, LangCompileError> {
+    let args = line.arguments.as_slice();
+    match (&line.mnemonic, args) {
+        (Some(LangCommand::DB), _) => {
+            let mut bytes = vec![];
+            for arg in args.iter() {
+                match arg {
+                    LangLiteral::String(string) => bytes.extend(string_literal_bytes(string)),
+                    _ => bytes.push(to_byte(resolve_numeric(arg, symbols)?)?),
+                }
+            }
+            Ok(bytes)
+        }
+        (Some(LangCommand::FILL), [count, value]) => {
+            Ok(vec![
+                to_byte(resolve_numeric(value, symbols)?)?;
+                resolve_numeric(count, symbols)?
+            ])
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+fn emit_line(
+    program: &mut RiscProgram,
+    line: &SourceLine,
+    placement: &Placement,
+    symbols: &SymbolTable,
+) -> Result<(), LangCompileError> {
+    let range = placement.address..placement.address + placement.size;
+    match placement.section {
+        Section::Code => {
+            let words = match instruction_form(line) {
+                Ok(Some(form)) => vec![lower_instruction(form, symbols)?.encode()],
+                // Syntax errors are reported by the analyzer, only ALIGN pads code.
+                _ => vec![NOP_WORD; placement.size],
+            };
+            for (address, word) in range.zip(words) {
+                program.code[address] = word;
+                program
+                    .source_map
+                    .insert(address, line.origin.source_line());
+            }
+        }
+        Section::Data => {
+            for (address, byte) in range.zip(data_bytes(line, symbols)?) {
+                program.data[address] = byte;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn generate_program(
+    lines: &[SourceLine],
+    layout: &LayoutArtifact,
+    config: &RiscCompilerConfig,
+) -> CodegenArtifact {
+    let mut program = RiscProgram::new(config.max_instruction_count, config.max_data_size);
+    let mut errors = vec![];
+
+    for (line, placement) in lines.iter().zip(layout.placements.iter()) {
+        if line.error.is_some() || placement.size == 0 {
+            continue;
+        }
+        if let Err(error) = emit_line(&mut program, line, placement, &layout.symbols) {
+            errors.push(CompileError {
+                origin: line.origin.clone(),
+                error,
+            });
+        }
+    }
+
+    for (name, symbol) in layout.symbols.symbols.iter() {
+        let (section, value) = match symbol.value {
+            SymbolValue::Label(section, address) => (section.into(), address),
+            SymbolValue::Definition(value) => (ProgramSection::Constant, value as usize),
+        };
+        program
+            .symbols
+            .insert(name.clone(), ProgramSymbol { section, value });
+    }
+
+    CodegenArtifact { program, errors }
+}
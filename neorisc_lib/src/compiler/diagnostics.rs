@@ -0,0 +1,36 @@
+use crate::preprocessor::LineOrigin;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticStage {
+    Preprocess,
+    Parse,
+    Compile,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub stage: DiagnosticStage,
+    pub line: usize,
+    pub location: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(stage: DiagnosticStage, origin: &LineOrigin, message: String) -> Self {
+        Self {
+            stage,
+            line: origin.source_line() + 1,
+            location: origin.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
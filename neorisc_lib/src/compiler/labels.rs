@@ -0,0 +1,192 @@
+use crate::compiler::CompileError;
+use crate::error::LangCompileError;
+use crate::lang::LangLiteral;
+use crate::parser::command_parser::SourceLine;
+
+struct AnonymousLabel {
+    line: usize,
+    forward: bool,
+    name: String,
+}
+
+struct LabelScopeContext {
+    scope: String,
+    anonymous: Vec<AnonymousLabel>,
+    errors: Vec<CompileError>,
+}
+
+pub fn qualify_local_label(scope: &str, label: &str) -> String {
+    format!("{}.{}", scope, &label[1..])
+}
+
+fn anonymous_label_name(index: usize) -> String {
+    format!("__anon_{}", index)
+}
+
+fn collect_anonymous_labels(mut ctx: LabelScopeContext, lines: &[SourceLine]) -> LabelScopeContext {
+    for (ii, line) in lines.iter().enumerate() {
+        let label = match &line.label {
+            Some(label) if LangLiteral::is_anonymous_label(label) => label,
+            _ => continue,
+        };
+
+        if label.len() != 1 {
+            ctx.errors.push(CompileError {
+                origin: line.origin.clone(),
+                error: LangCompileError::InvalidAnonymousLabel,
+            });
+            continue;
+        }
+        let name = anonymous_label_name(ctx.anonymous.len());
+        ctx.anonymous.push(AnonymousLabel {
+            line: ii,
+            forward: label == "+",
+            name,
+        });
+    }
+
+    ctx
+}
+
+fn resolve_anonymous_reference(
+    ctx: &LabelScopeContext,
+    reference: &str,
+    at_line: usize,
+) -> Result<String, LangCompileError> {
+    let forward = reference.starts_with('+');
+    let distance = reference.len();
+    let found = if forward {
+        ctx.anonymous
+            .iter()
+            .filter(|label| label.forward && label.line > at_line)
+            .nth(distance - 1)
+    } else {
+        ctx.anonymous
+            .iter()
+            .rev()
+            .filter(|label| !label.forward && label.line <= at_line)
+            .nth(distance - 1)
+    };
+
+    found
+        .map(|label| label.name.clone())
+        .ok_or(LangCompileError::UndefinedAnonymousLabel)
+}
+
+fn qualify_reference(
+    ctx: &LabelScopeContext,
+    name: &str,
+    at_line: usize,
+) -> Result<Option<String>, LangCompileError> {
+    if LangLiteral::is_local_label(name) {
+        Ok(Some(qualify_local_label(&ctx.scope, name)))
+    } else if LangLiteral::is_anonymous_label(name) {
+        resolve_anonymous_reference(ctx, name, at_line).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn qualify_line(ctx: &mut LabelScopeContext, line: &mut SourceLine, at_line: usize) {
+    if let Some(label) = line.label.as_mut() {
+        if LangLiteral::is_local_label(label) {
+            *label = qualify_local_label(&ctx.scope, label);
+        } else if LangLiteral::is_anonymous_label(label) {
+            if let Some(anonymous) = ctx.anonymous.iter().find(|anon| anon.line == at_line) {
+                *label = anonymous.name.clone();
+            }
+        } else {
+            ctx.scope = label.clone();
+        }
+    }
+
+    for argument in line.arguments.iter_mut() {
+        let name = match argument {
+            LangLiteral::Symbol(name) | LangLiteral::ConstantSymbol(name) => name,
+            _ => continue,
+        };
+        match qualify_reference(ctx, name, at_line) {
+            Ok(Some(qualified)) => *name = qualified,
+            Ok(None) => {}
+            Err(error) => ctx.errors.push(CompileError {
+                origin: line.origin.clone(),
+                error,
+            }),
+        }
+    }
+}
+
+pub fn qualify_labels(lines: &mut [SourceLine]) -> Vec<CompileError> {
+    let mut ctx = LabelScopeContext {
+        scope: String::new(),
+        anonymous: vec![],
+        errors: vec![],
+    };
+    ctx = collect_anonymous_labels(ctx, lines);
+
+    for (ii, line) in lines.iter_mut().enumerate() {
+        qualify_line(&mut ctx, line, ii);
+    }
+
+    ctx.errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::labels::qualify_labels;
+    use crate::compiler::layout::{layout_lines, Section, SymbolValue};
+    use crate::compiler::RiscCompilerConfig;
+    use crate::error::LangCompileError;
+    use crate::lang::LangLiteral;
+    use crate::parser::command_parser::SourceLine;
+    use crate::parser::source_parser::parse_source;
+
+    fn qualified(source: &str) -> Vec<SourceLine> {
+        let mut lines = parse_source(source).lines;
+        assert!(qualify_labels(&mut lines).is_empty());
+        lines
+    }
+
+    #[test]
+    fn local_labels_scoped() {
+        let lines = qualified("first: MOV r0, #1\n.loop: JMP .loop\nsecond:\n@loop: JMP @loop");
+        assert_eq!(lines[1].label.as_deref(), Some("first.loop"));
+        assert_eq!(
+            lines[1].arguments,
+            vec![LangLiteral::Symbol("first.loop".to_string())]
+        );
+        assert_eq!(lines[3].label.as_deref(), Some("second.loop"));
+
+        let layout = layout_lines(&lines, &RiscCompilerConfig::default());
+        assert!(layout.errors.is_empty());
+        assert_eq!(
+            layout.symbols.get("second.loop"),
+            Some(&SymbolValue::Label(Section::Code, 2))
+        );
+    }
+
+    #[test]
+    fn anonymous_labels_resolved() {
+        let lines = qualified("-: MOV r0, #1\nJMP ++\n-: JMP -\nJMP --\n+: JMP -\nJMP +\n+:");
+        let target = |ii: usize| lines[ii].arguments[0].clone();
+        assert_eq!(target(1), LangLiteral::Symbol("__anon_3".to_string()));
+        assert_eq!(target(2), LangLiteral::Symbol("__anon_1".to_string()));
+        assert_eq!(target(3), LangLiteral::Symbol("__anon_0".to_string()));
+        assert_eq!(target(4), LangLiteral::Symbol("__anon_1".to_string()));
+        assert_eq!(target(5), LangLiteral::Symbol("__anon_3".to_string()));
+        assert_eq!(lines[6].label.as_deref(), Some("__anon_3"));
+    }
+
+    #[test]
+    fn missing_anonymous_label_reported() {
+        let mut lines = parse_source("JMP -\n++: JMP +").lines;
+        let errors = qualify_labels(&mut lines);
+        let errors: Vec<&LangCompileError> = errors.iter().map(|e| &e.error).collect();
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(errors[0], LangCompileError::InvalidAnonymousLabel));
+        assert!(matches!(
+            errors[1],
+            LangCompileError::UndefinedAnonymousLabel
+        ));
+    }
+}
@@ -0,0 +1,403 @@
+use crate::compiler::{CompileError, RiscCompilerConfig};
+use crate::error::LangCompileError;
+use crate::lang::{LangCommand, LangLiteral};
+use crate::parser::command_parser::SourceLine;
+use crate::preprocessor::LineOrigin;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Section {
+    Code,
+    Data,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SymbolValue {
+    Label(Section, usize),
+    Definition(u8),
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub value: SymbolValue,
+    pub origin: Option<LineOrigin>,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    pub symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn get(&self, name: &str) -> Option<&SymbolValue> {
+        self.symbols.get(name).map(|symbol| &symbol.value)
+    }
+
+    pub fn define(
+        &mut self,
+        name: &str,
+        value: SymbolValue,
+        origin: Option<LineOrigin>,
+    ) -> Result<(), LangCompileError> {
+        if self.symbols.contains_key(name) {
+            return Err(LangCompileError::DuplicateSymbol);
+        }
+        self.symbols
+            .insert(name.to_string(), Symbol { value, origin });
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Placement {
+    pub section: Section,
+    pub address: usize,
+    pub size: usize,
+}
+
+pub struct LayoutArtifact {
+    pub symbols: SymbolTable,
+    pub placements: Vec<Placement>,
+    pub errors: Vec<CompileError>,
+}
+
+struct LayoutContext<'cfg> {
+    config: &'cfg RiscCompilerConfig,
+    section: Section,
+    code_counter: usize,
+    data_counter: usize,
+    symbols: SymbolTable,
+    placements: Vec<Placement>,
+    errors: Vec<CompileError>,
+}
+
+impl<'cfg> LayoutContext<'cfg> {
+    fn counter(&self) -> usize {
+        match self.section {
+            Section::Code => self.code_counter,
+            Section::Data => self.data_counter,
+        }
+    }
+
+    fn set_counter(&mut self, value: usize) {
+        match self.section {
+            Section::Code => self.code_counter = value,
+            Section::Data => self.data_counter = value,
+        }
+    }
+
+    fn section_size(&self) -> usize {
+        match self.section {
+            Section::Code => self.config.max_instruction_count,
+            Section::Data => self.config.max_data_size,
+        }
+    }
+}
+
+pub fn resolve_numeric(
+    literal: &LangLiteral,
+    symbols: &SymbolTable,
+) -> Result<usize, LangCompileError> {
+    match literal {
+        LangLiteral::Address(value) | LangLiteral::Constant(value) => Ok(*value as usize),
+        LangLiteral::Char(chr) => Ok(*chr as usize),
+        LangLiteral::Symbol(name) => match symbols.get(name) {
+            Some(SymbolValue::Definition(value)) => Ok(*value as usize),
+            Some(SymbolValue::Label(_, address)) => Ok(*address),
+            None => Err(LangCompileError::UndefinedSymbol),
+        },
+        _ => Err(LangCompileError::InvalidDirectiveArgument),
+    }
+}
+
+pub fn string_literal_bytes(literal: &str) -> Vec<u8> {
+    let inner = literal.strip_prefix('\"').unwrap_or(literal);
+    let inner = inner.strip_suffix('\"').unwrap_or(inner);
+
+    inner.bytes().collect()
+}
+
+fn data_size(args: &[LangLiteral]) -> Result<usize, LangCompileError> {
+    if args.is_empty() {
+        return Err(LangCompileError::InvalidDirectiveArgument);
+    }
+
+    Ok(args
+        .iter()
+        .map(|arg| match arg {
+            LangLiteral::String(string) => string_literal_bytes(string).len(),
+            _ => 1,
+        })
+        .sum())
+}
+
+fn expect_section(ctx: &LayoutContext, section: Section) -> Result<(), LangCompileError> {
+    if ctx.section == section {
+        Ok(())
+    } else if section == Section::Code {
+        Err(LangCompileError::InstructionInDataSection)
+    } else {
+        Err(LangCompileError::DataInCodeSection)
+    }
+}
+
+fn line_size(ctx: &mut LayoutContext, line: &SourceLine) -> Result<usize, LangCompileError> {
+    let args = &line.arguments;
+    let mnemonic = match &line.mnemonic {
+        Some(mnemonic) => mnemonic,
+        None => return Ok(0),
+    };
+
+    match mnemonic {
+        LangCommand::CODE => {
+            ctx.section = Section::Code;
+            Ok(0)
+        }
+        LangCommand::DATA => {
+            ctx.section = Section::Data;
+            Ok(0)
+        }
+        LangCommand::ORG => match args.as_slice() {
+            [address] => {
+                let address = resolve_numeric(address, &ctx.symbols)?;
+                ctx.set_counter(address);
+                Ok(0)
+            }
+            _ => Err(LangCompileError::InvalidDirectiveArgument),
+        },
+        LangCommand::DEF => match args.as_slice() {
+            [LangLiteral::Symbol(name), value] => {
+                let value = resolve_numeric(value, &ctx.symbols)?;
+                let value = u8::try_from(value).map_err(|_| LangCompileError::ValueOutOfBounds)?;
+                let value = SymbolValue::Definition(value);
+                ctx.symbols.define(name, value, Some(line.origin.clone()))?;
+                Ok(0)
+            }
+            _ => Err(LangCompileError::InvalidDirectiveArgument),
+        },
+        LangCommand::DB => {
+            expect_section(ctx, Section::Data)?;
+            data_size(args)
+        }
+        LangCommand::DS => {
+            expect_section(ctx, Section::Data)?;
+            match args.as_slice() {
+                [count] => resolve_numeric(count, &ctx.symbols),
+                _ => Err(LangCompileError::InvalidDirectiveArgument),
+            }
+        }
+        LangCommand::FILL => {
+            expect_section(ctx, Section::Data)?;
+            match args.as_slice() {
+                [count, value] => {
+                    resolve_numeric(value, &ctx.symbols)?;
+                    resolve_numeric(count, &ctx.symbols)
+                }
+                _ => Err(LangCompileError::InvalidDirectiveArgument),
+            }
+        }
+        LangCommand::ALIGN => match args.as_slice() {
+            [alignment] => match resolve_numeric(alignment, &ctx.symbols)? {
+                0 => Err(LangCompileError::InvalidAlignment),
+                alignment => Ok((alignment - ctx.counter() % alignment) % alignment),
+            },
+            _ => Err(LangCompileError::InvalidDirectiveArgument),
+        },
+        _ => {
+            expect_section(ctx, Section::Code)?;
+            Ok(1)
+        }
+    }
+}
+
+fn layout_line(ctx: &mut LayoutContext, line: &SourceLine) -> Result<(), LangCompileError> {
+    // Section switches, ORG and ALIGN take effect before a label on the same line is bound.
+    let size = line_size(ctx, line);
+    let address = ctx.counter();
+    // A bad label still lets the line take its space, so later addresses don't shift.
+    let defined = match &line.label {
+        Some(label) => {
+            let padding = match (&line.mnemonic, &size) {
+                (Some(LangCommand::ALIGN), Ok(padding)) => *padding,
+                _ => 0,
+            };
+            let value = SymbolValue::Label(ctx.section, address + padding);
+            ctx.symbols.define(label, value, Some(line.origin.clone()))
+        }
+        None => Ok(()),
+    };
+
+    let size = size?;
+    if address + size > ctx.section_size() {
+        return Err(match ctx.section {
+            Section::Code => LangCompileError::CodeMemoryOverflow,
+            Section::Data => LangCompileError::DataMemoryOverflow,
+        });
+    }
+    ctx.set_counter(address + size);
+    ctx.placements.push(Placement {
+        section: ctx.section,
+        address,
+        size,
+    });
+
+    defined
+}
+
+pub fn layout_lines(lines: &[SourceLine], config: &RiscCompilerConfig) -> LayoutArtifact {
+    let mut ctx = LayoutContext {
+        config,
+        section: Section::Code,
+        code_counter: 0,
+        data_counter: 0,
+        symbols: SymbolTable::default(),
+        placements: vec![],
+        errors: vec![],
+    };
+
+    for (name, value) in config.preprocessor.defines.iter() {
+        ctx.symbols.symbols.insert(
+            name.clone(),
+            Symbol {
+                value: SymbolValue::Definition(*value),
+                origin: None,
+            },
+        );
+    }
+
+    for line in lines.iter() {
+        let placed = ctx.placements.len();
+        if let Err(error) = layout_line(&mut ctx, line) {
+            if ctx.placements.len() == placed {
+                ctx.placements.push(Placement {
+                    section: ctx.section,
+                    address: ctx.counter(),
+                    size: 0,
+                });
+            }
+            ctx.errors.push(CompileError {
+                origin: line.origin.clone(),
+                error,
+            });
+        }
+    }
+
+    LayoutArtifact {
+        symbols: ctx.symbols,
+        placements: ctx.placements,
+        errors: ctx.errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::layout::{layout_lines, LayoutArtifact, Section, SymbolValue};
+    use crate::compiler::RiscCompilerConfig;
+    use crate::error::LangCompileError;
+    use crate::parser::source_parser::parse_source;
+
+    fn layout(source: &str) -> LayoutArtifact {
+        let parsed = parse_source(source);
+        assert!(parsed.errors.is_empty());
+        layout_lines(&parsed.lines, &RiscCompilerConfig::default())
+    }
+
+    #[test]
+    fn labels_follow_location_counter() {
+        let art = layout(
+            "start: MOV r0, #1\nADD r0, #1\nALIGN 4\nloop: JMP loop\nDATA\nDB 1, \"abc\"\nbuf: DS 3\nDEF N 2\nFILL N, 0xFF\nALIGN 8\nend:",
+        );
+        assert!(art.errors.is_empty());
+        assert_eq!(
+            art.symbols.get("start"),
+            Some(&SymbolValue::Label(Section::Code, 0))
+        );
+        assert_eq!(
+            art.symbols.get("loop"),
+            Some(&SymbolValue::Label(Section::Code, 4))
+        );
+        assert_eq!(
+            art.symbols.get("buf"),
+            Some(&SymbolValue::Label(Section::Data, 4))
+        );
+        assert_eq!(
+            art.symbols.get("end"),
+            Some(&SymbolValue::Label(Section::Data, 16))
+        );
+        assert_eq!(art.placements[2].size, 2);
+    }
+
+    #[test]
+    fn org_moves_counter() {
+        let art = layout("ORG 0x10\nfirst: MOV r0, #1\nDATA\nORG 0x20\nvar: DB 0");
+        assert_eq!(
+            art.symbols.get("first"),
+            Some(&SymbolValue::Label(Section::Code, 0x10))
+        );
+        assert_eq!(
+            art.symbols.get("var"),
+            Some(&SymbolValue::Label(Section::Data, 0x20))
+        );
+    }
+
+    #[test]
+    fn layout_errors_reported() {
+        let art = layout(
+            "DB 1\nDS 2\nDATA\nMOV r0, #1\nDS COUNT\nALIGN 0\nx: DB 1\nx: DB 2\nFILL 200, 0",
+        );
+        let errors: Vec<&LangCompileError> = art.errors.iter().map(|e| &e.error).collect();
+        assert!(matches!(errors[0], LangCompileError::DataInCodeSection));
+        assert!(matches!(errors[1], LangCompileError::DataInCodeSection));
+        assert!(matches!(
+            errors[2],
+            LangCompileError::InstructionInDataSection
+        ));
+        assert!(matches!(errors[3], LangCompileError::UndefinedSymbol));
+        assert!(matches!(errors[4], LangCompileError::InvalidAlignment));
+        assert!(matches!(errors[5], LangCompileError::DuplicateSymbol));
+        assert!(matches!(errors[6], LangCompileError::DataMemoryOverflow));
+        assert_eq!(art.errors[3].origin.line, 4);
+        assert_eq!(art.placements.len(), 9);
+    }
+
+    #[test]
+    fn duplicate_label_keeps_counter() {
+        let art = layout("x: MOV r0, #1\nx: MOV r0, #2\nafter: JMP after");
+        assert_eq!(art.errors.len(), 1);
+        assert!(matches!(
+            art.errors[0].error,
+            LangCompileError::DuplicateSymbol
+        ));
+        assert_eq!(art.placements.len(), 3);
+        assert_eq!((art.placements[1].address, art.placements[1].size), (1, 1));
+        assert_eq!(
+            art.symbols.get("after"),
+            Some(&SymbolValue::Label(Section::Code, 2))
+        );
+    }
+
+    #[test]
+    fn labels_bound_after_directives() {
+        let art = layout("MOV r0, #1\ntable: DATA\nDB 1\npadded: ALIGN 4\nDB 2");
+        assert_eq!(
+            art.symbols.get("table"),
+            Some(&SymbolValue::Label(Section::Data, 0))
+        );
+        assert_eq!(
+            art.symbols.get("padded"),
+            Some(&SymbolValue::Label(Section::Data, 4))
+        );
+        assert_eq!(art.placements[3].address, 1);
+        assert_eq!(art.placements[3].size, 3);
+        assert!(art.errors.is_empty());
+
+        let art = layout("ORG 0xFF\nMOV r0, #1\nend:\nDEF LAST end");
+        assert_eq!(art.errors.len(), 1);
+        assert!(matches!(
+            art.errors[0].error,
+            LangCompileError::ValueOutOfBounds
+        ));
+    }
+}
@@ -1,23 +1,52 @@
+pub mod codegen;
+pub mod diagnostics;
+pub mod labels;
+pub mod layout;
 pub mod syntax_analyzer;
-use crate::parser::source_parser::{parse_source, LangParsingArtifact};
-use std::borrow::Borrow;
+use crate::compiler::codegen::{generate_program, CodegenArtifact};
+use crate::compiler::diagnostics::{Diagnostic, DiagnosticStage};
+use crate::compiler::labels::qualify_labels;
+use crate::compiler::layout::{layout_lines, LayoutArtifact};
+use crate::compiler::syntax_analyzer::analyze_syntax;
+use crate::error::LangCompileError;
+use crate::parser::source_parser::{parse_lines, LangParsingArtifact};
+use crate::preprocessor::{
+    preprocess_source, LangPreprocessingArtifact, LineOrigin, PreprocessorConfig,
+};
+use crate::program::RiscProgram;
 use std::time::Instant;
 
+#[derive(Clone)]
 pub struct RiscCompilerConfig {
-    max_instruction_count: usize,
+    pub max_instruction_count: usize,
+    pub max_data_size: usize,
+    pub preprocessor: PreprocessorConfig,
+    pub verbose: bool,
 }
 
 impl Default for RiscCompilerConfig {
     fn default() -> Self {
         Self {
             max_instruction_count: 256,
+            max_data_size: 128,
+            preprocessor: PreprocessorConfig::default(),
+            verbose: false,
         }
     }
 }
 
+#[derive(Debug)]
+pub struct CompileError {
+    pub origin: LineOrigin,
+    pub error: LangCompileError,
+}
+
 pub struct RiscCompiler {
     code: String,
+    preprocessed: Option<LangPreprocessingArtifact>,
     parsed: Option<LangParsingArtifact>,
+    layout: Option<LayoutArtifact>,
+    generated: Option<CodegenArtifact>,
     config: RiscCompilerConfig,
 }
 
@@ -25,36 +54,143 @@ impl RiscCompiler {
     pub fn new(code: String, config: RiscCompilerConfig) -> Self {
         Self {
             code,
+            preprocessed: None,
             parsed: None,
+            layout: None,
+            generated: None,
             config,
         }
     }
 
+    fn report<E: std::fmt::Display>(&self, title: &str, errors: &[(E, &LineOrigin)]) {
+        if self.config.verbose && !errors.is_empty() {
+            eprintln!("{}", title);
+            for (ii, (error, origin)) in errors.iter().enumerate() {
+                eprintln!("{} | {} on {}", ii, error, origin);
+            }
+        }
+    }
+
+    pub fn preprocess(&mut self) {
+        let artifact = preprocess_source(&self.code, &self.config.preprocessor);
+        let errors: Vec<_> = artifact
+            .errors
+            .iter()
+            .map(|err| (&err.error, &err.origin))
+            .collect();
+        self.report("Preprocessor errors!!", &errors);
+
+        self.preprocessed = Some(artifact);
+    }
+
     pub fn parse(&mut self) {
         let now = Instant::now();
-        let artifact = parse_source(&self.code);
+        self.preprocess();
+        let lines = &self.preprocessed.as_ref().expect("Didn't preprocess").lines;
+        let artifact = parse_lines(lines);
         let took = now.elapsed().as_millis();
-        println!("Parsing took: {}", took as f64 / 1000.0);
-        if artifact.errors.is_some() {
-            println!("Errors!!");
-            for (ii, err) in artifact.errors.as_ref().unwrap().iter().enumerate() {
-                println!("{} | {} on {}", ii, err.error, err.line);
-            }
+        if self.config.verbose {
+            eprintln!("Parsing took: {}", took as f64 / 1000.0);
         }
+        let errors: Vec<_> = artifact
+            .errors
+            .iter()
+            .map(|err| (&err.error, &err.origin))
+            .collect();
+        self.report("Errors!!", &errors);
 
         self.parsed = Some(artifact);
     }
 
+    pub fn layout(&mut self) {
+        let lines = match self.parsed.as_mut() {
+            Some(parsed) => &mut parsed.lines,
+            None => return,
+        };
+        let label_errors = qualify_labels(lines);
+        let mut artifact = layout_lines(lines, &self.config);
+        artifact.errors.splice(0..0, label_errors);
+        artifact.errors.extend(analyze_syntax(lines));
+        let errors: Vec<_> = artifact
+            .errors
+            .iter()
+            .map(|err| (&err.error, &err.origin))
+            .collect();
+        self.report("Layout errors!!", &errors);
+
+        self.layout = Some(artifact);
+    }
+
+    pub fn generate(&mut self) {
+        let (parsed, layout) = match (self.parsed.as_ref(), self.layout.as_ref()) {
+            (Some(parsed), Some(layout)) => (parsed, layout),
+            _ => return,
+        };
+        let artifact = generate_program(&parsed.lines, layout, &self.config);
+        let errors: Vec<_> = artifact
+            .errors
+            .iter()
+            .map(|err| (&err.error, &err.origin))
+            .collect();
+        self.report("Codegen errors!!", &errors);
+
+        self.generated = Some(artifact);
+    }
+
     pub fn compile(&mut self) {
         self.parse();
+        self.layout();
+        self.generate();
+    }
+
+    pub fn preprocessed(&self) -> Option<&LangPreprocessingArtifact> {
+        self.preprocessed.as_ref()
+    }
+
+    pub fn parsed(&self) -> Option<&LangParsingArtifact> {
+        self.parsed.as_ref()
+    }
+
+    pub fn layout_artifact(&self) -> Option<&LayoutArtifact> {
+        self.layout.as_ref()
+    }
+
+    pub fn program(&self) -> Option<&RiscProgram> {
+        self.generated.as_ref().map(|generated| &generated.program)
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        if let Some(preprocessed) = &self.preprocessed {
+            diagnostics.extend(preprocessed.errors.iter().map(|err| {
+                Diagnostic::new(
+                    DiagnosticStage::Preprocess,
+                    &err.origin,
+                    err.error.to_string(),
+                )
+            }));
+        }
         if let Some(parsed) = &self.parsed {
-            if let Some(lines) = &parsed.lines {}
+            diagnostics.extend(parsed.errors.iter().map(|err| {
+                Diagnostic::new(DiagnosticStage::Parse, &err.origin, err.error.to_string())
+            }));
         }
+        let compile_errors = self
+            .layout
+            .iter()
+            .flat_map(|layout| layout.errors.iter())
+            .chain(self.generated.iter().flat_map(|gen| gen.errors.iter()));
+        diagnostics.extend(compile_errors.map(|err| {
+            Diagnostic::new(DiagnosticStage::Compile, &err.origin, err.error.to_string())
+        }));
+
+        diagnostics
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::compiler::layout::{Section, SymbolValue};
     use crate::compiler::{RiscCompiler, RiscCompilerConfig};
 
     #[test]
@@ -65,6 +201,122 @@ mod tests {
         );
         interpreter.compile();
         let art = &interpreter.parsed.expect("Didn't parse");
-        assert_eq!(art.errors.is_none(), true);
+        assert!(art.errors.is_empty());
+    }
+
+    #[test]
+    fn macros_expanded_before_parsing() {
+        let mut compiler = RiscCompiler::new(
+            "MACRO inc reg\nADD reg, #1\nENDM\ninc r0\ninc r1".to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let preprocessed = compiler.preprocessed.expect("Didn't preprocess");
+        assert!(preprocessed.errors.is_empty());
+        let art = compiler.parsed.expect("Didn't parse");
+        assert_eq!(art.lines.len(), 2);
+    }
+
+    #[test]
+    fn config_defines_select_code() {
+        let mut config = RiscCompilerConfig::default();
+        config.preprocessor.defines.insert("SIM".to_string(), 1);
+        let mut compiler = RiscCompiler::new(
+            "IFDEF SIM\nMOV r0, #1\nELSE\nMOV r0, #2\nMOV r1, #2\nENDIF".to_string(),
+            config,
+        );
+        compiler.compile();
+        let art = compiler.parsed.expect("Didn't parse");
+        assert_eq!(art.lines.len(), 1);
+    }
+
+    #[test]
+    fn repeated_data_laid_out() {
+        let mut compiler = RiscCompiler::new(
+            "DATA\nREPT 4\nDB 0\nENDR\ntable: TIMES 2 DB 1, 2\nALIGN 8\nend:".to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let layout = compiler.layout.expect("Didn't lay out");
+        assert!(layout.errors.is_empty());
+        assert_eq!(
+            layout.symbols.get("table"),
+            Some(&SymbolValue::Label(Section::Data, 4))
+        );
+        assert_eq!(
+            layout.symbols.get("end"),
+            Some(&SymbolValue::Label(Section::Data, 8))
+        );
+    }
+
+    #[test]
+    fn macro_local_labels_keep_scope() {
+        let mut compiler = RiscCompiler::new(
+            "MACRO wait\n%%loop: JMP %%loop\nENDM\nmain: wait\n.done: JMP .done".to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let layout = compiler.layout.expect("Didn't lay out");
+        assert!(layout.errors.is_empty());
+        assert_eq!(
+            layout.symbols.get("main.done"),
+            Some(&SymbolValue::Label(Section::Code, 1))
+        );
+        assert!(layout.symbols.get("main.__wait_1_loop").is_some());
+    }
+
+    #[test]
+    fn errors_in_expansion_point_at_invocation() {
+        let mut compiler = RiscCompiler::new(
+            "MACRO bad reg\nADD reg, #1000\nENDM\nMOV r0, #1\nbad r2".to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let art = compiler.parsed.expect("Didn't parse");
+        assert_eq!(art.errors[0].origin.line, 1);
+        assert_eq!(art.errors[0].origin.source_line(), 4);
+    }
+
+    #[test]
+    fn program_generated() {
+        let mut compiler = RiscCompiler::new(
+            "DEF LIMIT 3\nstart: MOV r0, #LIMIT\nMOV r1, value\nALIGN 4\nJSR start\nDATA\nDB 7\nvalue: DB \"hi\"".to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        assert!(compiler.diagnostics().is_empty());
+        let program = compiler.program().expect("Didn't generate");
+        assert_eq!(
+            &program.code[0..5],
+            &[0x2003, 0xE101, 0xFF00, 0xFF00, 0xC900]
+        );
+        assert_eq!(&program.data[0..3], &[7, b'h', b'i']);
+        assert_eq!(program.source_map.get(&4), Some(&4));
+    }
+
+    #[test]
+    fn diagnostics_collected_from_all_stages() {
+        let mut compiler = RiscCompiler::new(
+            "IF\nENDIF\nMOV r0 r1\nADD r0, 0x10\nJMP nowhere".to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let lines: Vec<usize> = compiler.diagnostics().iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn later_stages_run_despite_parse_errors() {
+        let mut compiler = RiscCompiler::new(
+            "DATA\nMOV r0 r1\nbuf: DS COUNT\nbuf: DB 1".to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let art = compiler.parsed.expect("Didn't parse");
+        assert_eq!(art.errors.len(), 1);
+        assert_eq!(art.errors[0].origin.line, 1);
+        let layout = compiler.layout.expect("Didn't lay out");
+        let lines: Vec<usize> = layout.errors.iter().map(|e| e.origin.line).collect();
+        assert_eq!(lines, vec![2, 3]);
     }
 }
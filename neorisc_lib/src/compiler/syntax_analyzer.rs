@@ -1,41 +1,223 @@
+use crate::compiler::CompileError;
 use crate::error::LangSyntaxError;
-use crate::lang::LangCommand;
+use crate::isa::{AluOp, Instruction, JumpKind, Operand, ShiftOp};
+use crate::lang::{LangCommand, LangLiteral};
 use crate::parser::command_parser::SourceLine;
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OperandValue {
+    Literal(u8),
+    Symbol(String),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum InstructionForm {
+    Load {
+        rx: u8,
+        address: OperandValue,
+    },
+    Store {
+        rx: u8,
+        address: OperandValue,
+    },
+    AluImmediate {
+        op: AluOp,
+        rx: u8,
+        value: OperandValue,
+    },
+    JumpAbsolute {
+        kind: JumpKind,
+        target: OperandValue,
+    },
+    Resolved(Instruction),
+}
+
 trait ValidateCommand {
-    fn no_args(&self) -> Option<LangSyntaxError>;
+    fn no_args(&self) -> Result<(), LangSyntaxError>;
 }
 
 impl ValidateCommand for SourceLine {
-    fn no_args(&self) -> Option<LangSyntaxError> {
-        if self.arguments.len() != 0 {
-            Some(LangSyntaxError::InstructionTakesZeroArguments)
+    fn no_args(&self) -> Result<(), LangSyntaxError> {
+        if !self.arguments.is_empty() {
+            Err(LangSyntaxError::InstructionTakesZeroArguments)
         } else {
-            None
+            Ok(())
+        }
+    }
+}
+
+fn address_value(literal: &LangLiteral) -> Option<OperandValue> {
+    match literal {
+        LangLiteral::Address(address) => Some(OperandValue::Literal(*address)),
+        LangLiteral::Symbol(name) => Some(OperandValue::Symbol(name.clone())),
+        _ => None,
+    }
+}
+
+fn immediate_value(literal: &LangLiteral) -> Option<OperandValue> {
+    match literal {
+        LangLiteral::Constant(value) => Some(OperandValue::Literal(*value)),
+        LangLiteral::ConstantSymbol(name) => Some(OperandValue::Symbol(name.clone())),
+        _ => None,
+    }
+}
+
+fn mov_form(args: &[LangLiteral]) -> Option<InstructionForm> {
+    match args {
+        [LangLiteral::Register(rx), LangLiteral::Indirect(ry)] => {
+            Some(InstructionForm::Resolved(Instruction::LoadIndirect {
+                rx: *rx,
+                ry: *ry,
+            }))
+        }
+        [LangLiteral::Indirect(ry), LangLiteral::Register(rx)] => {
+            Some(InstructionForm::Resolved(Instruction::StoreIndirect {
+                rx: *rx,
+                ry: *ry,
+            }))
         }
+        [LangLiteral::Register(rx), source] => {
+            address_value(source).map(|address| InstructionForm::Load { rx: *rx, address })
+        }
+        [destination, LangLiteral::Register(rx)] => {
+            address_value(destination).map(|address| InstructionForm::Store { rx: *rx, address })
+        }
+        _ => None,
     }
 }
 
-fn validate_source_line(line: &SourceLine) -> Option<LangSyntaxError> {
-    if let Some(mnemonic) = line.mnemonic.as_ref() {
-        match mnemonic {
-            LangCommand::CODE => line.no_args(),
-            _ => Some(LangSyntaxError::Other),
+fn alu_form(op: AluOp, args: &[LangLiteral]) -> Option<InstructionForm> {
+    match args {
+        [LangLiteral::Register(rx), LangLiteral::Register(ry)] => {
+            Some(InstructionForm::Resolved(Instruction::Alu {
+                op,
+                rx: *rx,
+                operand: Operand::Register(*ry),
+            }))
+        }
+        [LangLiteral::Register(rx), value] => {
+            immediate_value(value).map(|value| InstructionForm::AluImmediate { op, rx: *rx, value })
         }
-    } else if line.arguments.len() > 0 {
-        Some(LangSyntaxError::UnexpectedArguments)
+        _ => None,
+    }
+}
+
+fn jump_form(kind: JumpKind, args: &[LangLiteral]) -> Option<InstructionForm> {
+    match args {
+        [LangLiteral::Indirect(ry)] => Some(InstructionForm::Resolved(Instruction::Jump {
+            kind,
+            target: Operand::Register(*ry),
+        })),
+        [target] => {
+            address_value(target).map(|target| InstructionForm::JumpAbsolute { kind, target })
+        }
+        _ => None,
+    }
+}
+
+pub fn instruction_form(line: &SourceLine) -> Result<Option<InstructionForm>, LangSyntaxError> {
+    let mnemonic = match line.mnemonic.as_ref() {
+        Some(mnemonic) if mnemonic.is_instruction() => mnemonic,
+        Some(_) => return Ok(None),
+        None if line.arguments.is_empty() => return Ok(None),
+        None => return Err(LangSyntaxError::UnexpectedArguments),
+    };
+    let args = line.arguments.as_slice();
+
+    let fixed = match mnemonic {
+        LangCommand::RTS => Some(Instruction::Rts),
+        LangCommand::RTI => Some(Instruction::Rti),
+        LangCommand::CLI => Some(Instruction::Cli),
+        LangCommand::STI => Some(Instruction::Sti),
+        LangCommand::NOP => Some(Instruction::Nop),
+        _ => None,
+    };
+    if let Some(instruction) = fixed {
+        line.no_args()?;
+        return Ok(Some(InstructionForm::Resolved(instruction)));
+    }
+
+    let form = if let Some(op) = ShiftOp::from_command(mnemonic) {
+        match args {
+            [LangLiteral::Register(rx)] => Some(InstructionForm::Resolved(Instruction::Shift {
+                op,
+                rx: *rx,
+            })),
+            _ => None,
+        }
+    } else if let Some(kind) = JumpKind::from_command(mnemonic) {
+        jump_form(kind, args)
+    } else if mnemonic == &LangCommand::MOV {
+        mov_form(args).or_else(|| alu_form(AluOp::Mov, args))
+    } else if let Some(op) = AluOp::from_command(mnemonic) {
+        alu_form(op, args)
     } else {
         None
-    }
+    };
+
+    form.map(Some).ok_or(LangSyntaxError::InvalidOperands)
 }
 
-pub fn analyze_syntax(lines: &Vec<SourceLine>) -> Vec<LangSyntaxError> {
+pub fn analyze_syntax(lines: &[SourceLine]) -> Vec<CompileError> {
     let mut errors = Vec::new();
-    for line in lines.iter() {
-        if let Some(err) = validate_source_line(line) {
-            errors.push(err);
+    for line in lines.iter().filter(|line| line.error.is_none()) {
+        if let Err(err) = instruction_form(line) {
+            errors.push(CompileError {
+                origin: line.origin.clone(),
+                error: err.into(),
+            });
         }
     }
 
     errors
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::syntax_analyzer::{instruction_form, InstructionForm, OperandValue};
+    use crate::error::LangSyntaxError;
+    use crate::isa::{AluOp, Instruction};
+    use crate::parser::command_parser::parse_command_line;
+
+    fn form(line: &str) -> Result<Option<InstructionForm>, LangSyntaxError> {
+        instruction_form(&parse_command_line(line).expect("Line parsing failed"))
+    }
+
+    #[test]
+    fn operand_shapes_recognized() {
+        assert_eq!(
+            form("MOV r1, (r2)"),
+            Ok(Some(InstructionForm::Resolved(Instruction::LoadIndirect {
+                rx: 1,
+                ry: 2
+            })))
+        );
+        assert_eq!(
+            form("MOV buffer, r3"),
+            Ok(Some(InstructionForm::Store {
+                rx: 3,
+                address: OperandValue::Symbol("buffer".to_string())
+            }))
+        );
+        assert_eq!(
+            form("SUB r0, #LIMIT"),
+            Ok(Some(InstructionForm::AluImmediate {
+                op: AluOp::Sub,
+                rx: 0,
+                value: OperandValue::Symbol("LIMIT".to_string())
+            }))
+        );
+        assert_eq!(form("DB 1, 2"), Ok(None));
+    }
+
+    #[test]
+    fn invalid_operands_rejected() {
+        assert_eq!(form("ADD r0, 0x10"), Err(LangSyntaxError::InvalidOperands));
+        assert_eq!(form("SL0 #1"), Err(LangSyntaxError::InvalidOperands));
+        assert_eq!(form("JMP r1"), Err(LangSyntaxError::InvalidOperands));
+        assert_eq!(
+            form("RTS r1"),
+            Err(LangSyntaxError::InstructionTakesZeroArguments)
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+use crate::debugger::{Debugger, StopReason};
+use crate::framing::{read_message, write_message};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+pub const THREAD_ID: i64 = 1;
+const REGISTERS_REFERENCE: i64 = 1;
+const FLAGS_REFERENCE: i64 = 2;
+const MEMORY_REFERENCE: i64 = 3;
+
+#[derive(Default)]
+pub struct DapServer {
+    pub debugger: Option<Debugger>,
+    source_path: String,
+    stop_on_entry: bool,
+    // In request order, the response answers each entry in place.
+    breakpoint_lines: Vec<usize>,
+    seq: i64,
+    finished: bool,
+}
+
+impl DapServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn response(&mut self, request: &Value, body: Value) -> Value {
+        json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        })
+    }
+
+    fn error_response(&mut self, request: &Value, message: String) -> Value {
+        json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        })
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Value {
+        json!({
+            "seq": self.next_seq(),
+            "type": "event",
+            "event": event,
+            "body": body,
+        })
+    }
+
+    fn apply_breakpoints(&mut self) -> Vec<Value> {
+        let lines = self.breakpoint_lines.clone();
+        let debugger = match self.debugger.as_mut() {
+            Some(debugger) => debugger,
+            None => {
+                return lines
+                    .iter()
+                    .map(|line| json!({ "verified": false, "line": line }))
+                    .collect()
+            }
+        };
+
+        debugger.breakpoints.clear();
+        lines
+            .iter()
+            .map(|line| {
+                let address = line
+                    .checked_sub(1)
+                    .and_then(|line| debugger.addresses_for_line(line).first().copied());
+                if let Some(address) = address {
+                    debugger.breakpoints.insert(address);
+                }
+                json!({ "verified": address.is_some(), "line": line })
+            })
+            .collect()
+    }
+
+    fn stop_events(&mut self, reason: StopReason) -> Vec<Value> {
+        let (reason, description) = match reason {
+            StopReason::Step => ("step", None),
+            StopReason::Breakpoint(_) => ("breakpoint", None),
+            StopReason::Watchpoint { .. } => ("data breakpoint", None),
+            StopReason::CycleLimit => ("pause", Some("Cycle limit reached".to_string())),
+            StopReason::HistoryStart => (
+                "step",
+                Some("Reached the start of the recorded history".to_string()),
+            ),
+            StopReason::Fault(err) => ("exception", Some(err.to_string())),
+            StopReason::Halted => {
+                return vec![
+                    self.event("exited", json!({ "exitCode": 0 })),
+                    self.event("terminated", json!({})),
+                ]
+            }
+        };
+
+        vec![self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "text": description,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        )]
+    }
+
+    fn launch(&mut self, request: &Value) -> Result<(), String> {
+        let args = &request["arguments"];
+        let path = args["program"]
+            .as_str()
+            .ok_or_else(|| "Missing program path".to_string())?;
+        let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        let mut compiler = RiscCompiler::new(source.clone(), RiscCompilerConfig::default());
+        compiler.compile();
+        let diagnostics = compiler.diagnostics();
+        if let Some(first) = diagnostics.first() {
+            return Err(format!("{}: {}", path, first));
+        }
+
+        let program = compiler.program().expect("Didn't generate").clone();
+        self.debugger = Some(Debugger::new(program, Some(&source)).with_history());
+        self.source_path = path.to_string();
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+
+        Ok(())
+    }
+
+    fn frame(&self, id: usize, address: u8) -> Value {
+        let debugger = self.debugger.as_ref().expect("Not launched");
+        let name = debugger
+            .enclosing_label(address)
+            .map_or_else(|| format!("0x{:02X}", address), |label| label.to_string());
+        let line = debugger
+            .program
+            .source_map
+            .get(&(address as usize))
+            .map_or(0, |line| line + 1);
+
+        json!({
+            "id": id,
+            "name": name,
+            "line": line,
+            "column": 1,
+            "instructionPointerReference": format!("0x{:02X}", address),
+            "source": { "path": self.source_path },
+        })
+    }
+
+    fn stack_trace(&self) -> Value {
+        let debugger = self.debugger.as_ref().expect("Not launched");
+        let interp = &debugger.interpreter;
+        // Each return address points after the JSR that made the call.
+        let frames: Vec<Value> = std::iter::once(interp.pc)
+            .chain(
+                interp
+                    .stack
+                    .iter()
+                    .rev()
+                    .map(|(return_pc, _)| return_pc.wrapping_sub(1)),
+            )
+            .enumerate()
+            .map(|(id, address)| self.frame(id, address))
+            .collect();
+
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    fn variables(&self, reference: i64) -> Vec<Value> {
+        let debugger = self.debugger.as_ref().expect("Not launched");
+        let interp = &debugger.interpreter;
+        let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+
+        match reference {
+            REGISTERS_REFERENCE => interp
+                .registers
+                .iter()
+                .enumerate()
+                .map(|(ii, value)| variable(format!("r{}", ii), format!("0x{:02X}", value)))
+                .chain(std::iter::once(variable(
+                    "pc".to_string(),
+                    format!("0x{:02X}", interp.pc),
+                )))
+                .collect(),
+            FLAGS_REFERENCE => {
+                let flags = interp.flags;
+                [
+                    ("Z", flags.z),
+                    ("C", flags.c),
+                    ("N", flags.n),
+                    ("V", flags.v),
+                    ("IE", flags.ie),
+                    ("IF", flags.if_),
+                ]
+                .iter()
+                .map(|(name, set)| variable(name.to_string(), (*set as u8).to_string()))
+                .collect()
+            }
+            MEMORY_REFERENCE => interp
+                .bus
+                .dump()
+                .iter()
+                .enumerate()
+                .map(|(address, value)| {
+                    variable(format!("0x{:02X}", address), format!("0x{:02X}", value))
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn execution<F: FnOnce(&mut Debugger) -> StopReason>(
+        &mut self,
+        request: &Value,
+        run: F,
+    ) -> Vec<Value> {
+        let reason = match self.debugger.as_mut() {
+            Some(debugger) => run(debugger),
+            None => return vec![self.error_response(request, "Not launched".to_string())],
+        };
+        let mut messages = vec![self.response(request, json!({ "allThreadsContinued": true }))];
+        messages.extend(self.stop_events(reason));
+
+        messages
+    }
+
+    pub fn handle(&mut self, request: &Value) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or_default();
+        if command != "initialize"
+            && command != "launch"
+            && command != "setBreakpoints"
+            && command != "disconnect"
+            && command != "terminate"
+            && self.debugger.is_none()
+        {
+            return vec![self.error_response(request, "Not launched".to_string())];
+        }
+
+        match command {
+            "initialize" => vec![self.response(
+                request,
+                json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsDataBreakpoints": false,
+                    "supportsTerminateRequest": true,
+                    "supportsStepBack": true,
+                }),
+            )],
+            "launch" => match self.launch(request) {
+                Ok(()) => {
+                    self.apply_breakpoints();
+                    vec![
+                        self.response(request, json!({})),
+                        self.event("initialized", json!({})),
+                    ]
+                }
+                Err(message) => vec![self.error_response(request, message)],
+            },
+            "setBreakpoints" => {
+                self.breakpoint_lines = request["arguments"]["breakpoints"]
+                    .as_array()
+                    .map(|breakpoints| {
+                        breakpoints
+                            .iter()
+                            .map(|bp| bp["line"].as_u64().unwrap_or_default() as usize)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let breakpoints = self.apply_breakpoints();
+                vec![self.response(request, json!({ "breakpoints": breakpoints }))]
+            }
+            "configurationDone" => {
+                let mut messages = vec![self.response(request, json!({}))];
+                if self.stop_on_entry {
+                    messages.push(self.event(
+                        "stopped",
+                        json!({ "reason": "entry", "threadId": THREAD_ID, "allThreadsStopped": true }),
+                    ));
+                } else {
+                    let reason = self.debugger.as_mut().expect("Not launched").resume();
+                    messages.extend(self.stop_events(reason));
+                }
+                messages
+            }
+            "threads" => vec![self.response(
+                request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            )],
+            "stackTrace" => {
+                let body = self.stack_trace();
+                vec![self.response(request, body)]
+            }
+            "scopes" => vec![self.response(
+                request,
+                json!({ "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                    { "name": "Flags", "variablesReference": FLAGS_REFERENCE, "expensive": false },
+                    { "name": "Data memory", "variablesReference": MEMORY_REFERENCE, "expensive": true },
+                ] }),
+            )],
+            "variables" => {
+                let reference = request["arguments"]["variablesReference"]
+                    .as_i64()
+                    .unwrap_or_default();
+                let variables = self.variables(reference);
+                vec![self.response(request, json!({ "variables": variables }))]
+            }
+            "continue" => self.execution(request, |debugger| debugger.resume()),
+            "next" => self.execution(request, |debugger| debugger.step_over()),
+            "stepIn" => self.execution(request, |debugger| debugger.step()),
+            "stepOut" => self.execution(request, |debugger| debugger.step_out()),
+            "stepBack" => self.execution(request, |debugger| debugger.step_back()),
+            "reverseContinue" => self.execution(request, |debugger| debugger.reverse_continue()),
+            "pause" => {
+                let mut messages = vec![self.response(request, json!({}))];
+                messages.extend(self.stop_events(StopReason::Step));
+                messages
+            }
+            "disconnect" | "terminate" => {
+                self.finished = true;
+                vec![self.response(request, json!({}))]
+            }
+            _ => vec![self.error_response(request, format!("Unsupported request: {}", command))],
+        }
+    }
+
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        while !self.finished {
+            let request = match read_message(&mut input)? {
+                Some(request) => request,
+                None => break,
+            };
+            for message in self.handle(&request) {
+                write_message(&mut output, &message)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dap::DapServer;
+    use serde_json::{json, Value};
+    use std::fs;
+
+    fn request(seq: i64, command: &str, arguments: Value) -> Value {
+        json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+    }
+
+    #[test]
+    fn launch_break_and_inspect() {
+        let path = std::env::temp_dir().join("neorisc_dap_test.s");
+        fs::write(
+            &path,
+            "MOV r0, #2\nloop: JSR bump\nSUB r0, #1\nJNZ loop\nend: JMP end\nbump: ADD r1, #5\nRTS",
+        )
+        .expect("Write failed");
+
+        let mut server = DapServer::new();
+        server.handle(&request(1, "initialize", json!({})));
+        let launched = server.handle(&request(
+            2,
+            "launch",
+            json!({ "program": path.to_str().expect("Invalid path") }),
+        ));
+        assert_eq!(launched[0]["success"], true);
+        assert_eq!(launched[1]["event"], "initialized");
+
+        let set = server.handle(&request(
+            3,
+            "setBreakpoints",
+            json!({ "source": { "path": "x" }, "breakpoints": [{ "line": 6 }, { "line": 100 }, { "line": 0 }] }),
+        ));
+        let breakpoints = &set[0]["body"]["breakpoints"];
+        assert_eq!(breakpoints[0], json!({ "verified": true, "line": 6 }));
+        assert_eq!(breakpoints[1], json!({ "verified": false, "line": 100 }));
+        assert_eq!(breakpoints[2], json!({ "verified": false, "line": 0 }));
+
+        let done = server.handle(&request(4, "configurationDone", json!({})));
+        assert_eq!(done[1]["body"]["reason"], "breakpoint");
+
+        let trace = server.handle(&request(5, "stackTrace", json!({ "threadId": 1 })));
+        let frames = &trace[0]["body"]["stackFrames"];
+        assert_eq!(frames[0]["name"], "bump");
+        assert_eq!(frames[0]["line"], 6);
+        assert_eq!(frames[1]["name"], "loop");
+        assert_eq!(frames[1]["line"], 2);
+
+        let stepped = server.handle(&request(6, "next", json!({ "threadId": 1 })));
+        assert_eq!(stepped[1]["body"]["reason"], "step");
+        let vars = server.handle(&request(7, "variables", json!({ "variablesReference": 1 })));
+        assert_eq!(vars[0]["body"]["variables"][1]["value"], "0x05");
+
+        server.handle(&request(8, "setBreakpoints", json!({ "breakpoints": [] })));
+        let finished = server.handle(&request(9, "continue", json!({ "threadId": 1 })));
+        assert_eq!(finished[1]["event"], "exited");
+        assert_eq!(finished[2]["event"], "terminated");
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,395 @@
+pub mod repl;
+
+use crate::error::InterpreterError;
+use crate::interpreter::bus::Bus;
+use crate::interpreter::history::{Change, History, StepDelta};
+use crate::interpreter::{RiscInterpreter, StepOutcome};
+use crate::isa::{Instruction, JumpKind};
+use crate::lang::LangLiteral;
+use crate::program::{ProgramSection, RiscProgram};
+use std::collections::BTreeSet;
+use std::mem;
+
+pub const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Watchpoint {
+    Register(u8),
+    Data(u8),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StopReason {
+    Step,
+    Breakpoint(u8),
+    Watchpoint { watch: Watchpoint, old: u8, new: u8 },
+    Halted,
+    Fault(InterpreterError),
+    CycleLimit,
+    // Stepping backwards reached the oldest recorded state.
+    HistoryStart,
+}
+
+pub struct Debugger {
+    pub interpreter: RiscInterpreter,
+    pub program: RiscProgram,
+    pub breakpoints: BTreeSet<u8>,
+    pub watchpoints: BTreeSet<Watchpoint>,
+    pub source: Vec<String>,
+    pub max_cycles: u64,
+}
+
+impl Debugger {
+    pub fn new(program: RiscProgram, source: Option<&str>) -> Self {
+        Self {
+            interpreter: RiscInterpreter::new(&program),
+            program,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            source: source
+                .map(|source| source.lines().map(|line| line.to_string()).collect())
+                .unwrap_or_default(),
+            max_cycles: DEFAULT_MAX_CYCLES,
+        }
+    }
+
+    // Records an undo log for stepping backwards, which costs time and memory on every step.
+    pub fn with_history(mut self) -> Self {
+        self.interpreter.history = Some(History::default());
+        self
+    }
+
+    pub fn reset(&mut self) {
+        let mut bus = mem::take(&mut self.interpreter.bus);
+        bus.load(&self.program.data);
+        let depth = self.interpreter.stack_depth;
+        let history = self
+            .interpreter
+            .history
+            .as_ref()
+            .map(|history| History::new(history.capacity, history.checkpoint_interval));
+        self.interpreter = RiscInterpreter::with_bus(&self.program, bus).with_stack_depth(depth);
+        self.interpreter.history = history;
+    }
+
+    pub fn resolve_address(&self, location: &str) -> Option<u8> {
+        if let Ok(LangLiteral::Address(address)) = LangLiteral::from_string(location) {
+            return Some(address);
+        }
+
+        let symbol = self.program.symbols.get(location)?;
+        match symbol.section {
+            ProgramSection::Constant => None,
+            _ if symbol.value > u8::MAX as usize => None,
+            _ => Some(symbol.value as u8),
+        }
+    }
+
+    pub fn resolve_watchpoint(&self, location: &str) -> Option<Watchpoint> {
+        match LangLiteral::from_string(location) {
+            Ok(LangLiteral::Register(reg)) => Some(Watchpoint::Register(reg)),
+            _ => self.resolve_address(location).map(Watchpoint::Data),
+        }
+    }
+
+    pub fn watch_value(&self, watch: Watchpoint) -> u8 {
+        match watch {
+            Watchpoint::Register(reg) => self.interpreter.registers[reg as usize],
+            Watchpoint::Data(address) => self.interpreter.bus.peek(address),
+        }
+    }
+
+    pub fn current_line(&self) -> Option<usize> {
+        self.program
+            .source_map
+            .get(&(self.interpreter.pc as usize))
+            .copied()
+    }
+
+    pub fn source_text(&self, line: usize) -> Option<&str> {
+        self.source.get(line).map(|text| text.as_str())
+    }
+
+    fn single_step(&mut self) -> Option<StopReason> {
+        let watched: Vec<(Watchpoint, u8)> = self
+            .watchpoints
+            .iter()
+            .map(|watch| (*watch, self.watch_value(*watch)))
+            .collect();
+
+        match self.interpreter.step() {
+            Ok(StepOutcome::Halted) => return Some(StopReason::Halted),
+            Err(err) => return Some(StopReason::Fault(err)),
+            Ok(StepOutcome::Executed) => {}
+        }
+
+        watched.into_iter().find_map(|(watch, old)| {
+            let new = self.watch_value(watch);
+            if new != old {
+                Some(StopReason::Watchpoint { watch, old, new })
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn step(&mut self) -> StopReason {
+        self.single_step().unwrap_or(StopReason::Step)
+    }
+
+    // Runs until `done` holds, stopping early on breakpoints, watchpoints and faults.
+    fn run_until<F: Fn(&RiscInterpreter) -> bool>(&mut self, done: F) -> StopReason {
+        let limit = self.interpreter.cycles.saturating_add(self.max_cycles);
+        let mut first = true;
+        loop {
+            if !first && self.breakpoints.contains(&self.interpreter.pc) {
+                return StopReason::Breakpoint(self.interpreter.pc);
+            }
+            first = false;
+            if self.interpreter.cycles >= limit {
+                return StopReason::CycleLimit;
+            }
+            if let Some(reason) = self.single_step() {
+                return reason;
+            }
+            if done(&self.interpreter) {
+                return StopReason::Step;
+            }
+        }
+    }
+
+    pub fn resume(&mut self) -> StopReason {
+        self.run_until(|_| false)
+    }
+
+    pub fn step_back(&mut self) -> StopReason {
+        if self.interpreter.step_back() {
+            StopReason::Step
+        } else {
+            StopReason::HistoryStart
+        }
+    }
+
+    // Rewinds to the latest earlier state at a breakpoint or just before a watched value changed.
+    pub fn reverse_continue(&mut self) -> StopReason {
+        let history = match &self.interpreter.history {
+            Some(history) => history,
+            None => return StopReason::HistoryStart,
+        };
+        let watched = |delta: &StepDelta| {
+            self.watchpoints.iter().find_map(|watch| {
+                match watch {
+                    Watchpoint::Register(reg) => delta
+                        .registers
+                        .iter()
+                        .find(|(changed, _)| changed == reg)
+                        .map(|(_, old)| *old),
+                    Watchpoint::Data(address) => delta
+                        .memory
+                        .filter(|(changed, _)| changed == address)
+                        .map(|(_, old)| old),
+                }
+                .map(|old| (*watch, old))
+            })
+        };
+        let found = history.find_back(history.steps(), |delta| {
+            watched(delta).is_some() || self.breakpoints.contains(&delta.pc)
+        });
+        let step = match found {
+            Some(step) => step,
+            None => {
+                let oldest = history.oldest_step();
+                self.interpreter.rewind_to(oldest);
+                return StopReason::HistoryStart;
+            }
+        };
+        let pc = history.delta(step).expect("Delta missing").pc;
+        let watch = history.delta(step).and_then(watched);
+
+        self.interpreter.rewind_to(step + 1);
+        let reason = match watch {
+            Some((watch, old)) => StopReason::Watchpoint {
+                watch,
+                old,
+                new: self.watch_value(watch),
+            },
+            None => StopReason::Breakpoint(pc),
+        };
+        self.interpreter.rewind_to(step);
+        reason
+    }
+
+    // When the register or data address last changed within the recorded history.
+    pub fn last_change(&self, watch: Watchpoint) -> Option<Change> {
+        let history = self.interpreter.history.as_ref()?;
+        let current = self.watch_value(watch);
+        match watch {
+            Watchpoint::Register(reg) => history.last_register_change(reg, current),
+            Watchpoint::Data(address) => history.last_memory_change(address, current),
+        }
+    }
+
+    pub fn is_call(&self) -> bool {
+        matches!(
+            self.interpreter.fetch(),
+            Ok(Instruction::Jump {
+                kind: JumpKind::Jsr,
+                ..
+            })
+        )
+    }
+
+    pub fn step_over(&mut self) -> StopReason {
+        if !self.is_call() {
+            return self.step();
+        }
+
+        let return_pc = self.interpreter.pc.wrapping_add(1);
+        let depth = self.interpreter.stack.len();
+        self.run_until(move |interp| interp.pc == return_pc && interp.stack.len() == depth)
+    }
+
+    pub fn step_out(&mut self) -> StopReason {
+        match self.interpreter.stack.len() {
+            0 => self.step(),
+            depth => self.run_until(move |interp| interp.stack.len() < depth),
+        }
+    }
+
+    pub fn addresses_for_line(&self, line: usize) -> Vec<u8> {
+        self.program
+            .source_map
+            .iter()
+            .filter(|(_, source_line)| **source_line == line)
+            .map(|(address, _)| *address as u8)
+            .collect()
+    }
+
+    // Nearest code label at or before the address.
+    pub fn enclosing_label(&self, address: u8) -> Option<&str> {
+        self.program
+            .symbols
+            .iter()
+            .filter(|(name, symbol)| {
+                symbol.section == ProgramSection::Code
+                    && symbol.value <= address as usize
+                    && !name.starts_with("__anon_")
+            })
+            .max_by_key(|(name, symbol)| (symbol.value, !name.contains('.')))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::debugger::{Debugger, StopReason, Watchpoint};
+
+    fn debugger(source: &str) -> Debugger {
+        let mut compiler = RiscCompiler::new(source.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        assert!(compiler.diagnostics().is_empty());
+        Debugger::new(
+            compiler.program().expect("Didn't generate").clone(),
+            Some(source),
+        )
+    }
+
+    const PROGRAM: &str = "MOV r0, #3\nloop: JSR bump\nSUB r0, #1\nJNZ loop\nend: JMP end\nbump: ADD r1, #2\nMOV total, r1\nRTS\nDATA\ntotal: DB 0";
+
+    #[test]
+    fn breakpoints_and_step_over() {
+        let mut dbg = debugger(PROGRAM);
+        let bump = dbg.resolve_address("bump").expect("Label missing");
+        dbg.breakpoints.insert(bump);
+        assert_eq!(dbg.resume(), StopReason::Breakpoint(bump));
+        assert_eq!(dbg.current_line(), Some(5));
+        assert_eq!(dbg.source_text(5), Some("bump: ADD r1, #2"));
+
+        dbg.breakpoints.clear();
+        dbg.reset();
+        dbg.step();
+        assert!(dbg.is_call());
+        assert_eq!(dbg.step_over(), StopReason::Step);
+        assert_eq!(dbg.interpreter.pc, 2);
+        assert_eq!(dbg.interpreter.registers[1], 2);
+        assert_eq!(dbg.resume(), StopReason::Halted);
+    }
+
+    #[test]
+    fn step_out_returns_to_caller() {
+        let mut dbg = debugger(PROGRAM);
+        dbg.step();
+        dbg.step();
+        assert_eq!(dbg.enclosing_label(dbg.interpreter.pc), Some("bump"));
+        assert_eq!(dbg.step_out(), StopReason::Step);
+        assert_eq!(dbg.interpreter.pc, 2);
+        assert_eq!(dbg.enclosing_label(2), Some("loop"));
+        assert_eq!(dbg.addresses_for_line(3), vec![3]);
+    }
+
+    #[test]
+    fn watchpoints_stop_on_change() {
+        let mut dbg = debugger(PROGRAM);
+        let total = dbg.resolve_watchpoint("total").expect("Label missing");
+        assert_eq!(total, Watchpoint::Data(0));
+        dbg.watchpoints.insert(total);
+        assert_eq!(
+            dbg.resume(),
+            StopReason::Watchpoint {
+                watch: total,
+                old: 0,
+                new: 2
+            }
+        );
+        dbg.watchpoints.clear();
+        dbg.watchpoints.insert(Watchpoint::Register(0));
+        assert_eq!(
+            dbg.resume(),
+            StopReason::Watchpoint {
+                watch: Watchpoint::Register(0),
+                old: 3,
+                new: 2
+            }
+        );
+    }
+
+    #[test]
+    fn reverse_execution() {
+        let mut dbg = debugger(PROGRAM);
+        assert!(dbg.interpreter.history.is_none());
+        dbg = dbg.with_history();
+        assert_eq!(dbg.step_back(), StopReason::HistoryStart);
+        assert_eq!(dbg.resume(), StopReason::Halted);
+        let total = dbg.resolve_watchpoint("total").expect("Label missing");
+        let change = dbg.last_change(total).expect("No change");
+        assert_eq!((change.pc, change.old, change.new), (6, 4, 6));
+
+        dbg.watchpoints.insert(total);
+        assert_eq!(
+            dbg.reverse_continue(),
+            StopReason::Watchpoint {
+                watch: total,
+                old: 4,
+                new: 6
+            }
+        );
+        assert_eq!(dbg.interpreter.pc, 6);
+        assert_eq!(dbg.watch_value(total), 4);
+        dbg.watchpoints.clear();
+
+        let bump = dbg.resolve_address("bump").expect("Label missing");
+        dbg.breakpoints.insert(bump);
+        assert_eq!(dbg.reverse_continue(), StopReason::Breakpoint(bump));
+        assert_eq!(dbg.interpreter.registers[1], 4);
+        assert_eq!(dbg.step_back(), StopReason::Step);
+        assert_eq!(dbg.interpreter.pc, 1);
+        assert_eq!(dbg.reverse_continue(), StopReason::Breakpoint(bump));
+        assert_eq!(dbg.interpreter.registers[1], 2);
+        assert_eq!(dbg.reverse_continue(), StopReason::Breakpoint(bump));
+        assert_eq!(dbg.interpreter.registers[1], 0);
+        assert_eq!(dbg.reverse_continue(), StopReason::HistoryStart);
+        assert_eq!((dbg.interpreter.pc, dbg.interpreter.cycles), (0, 0));
+        assert_eq!(dbg.resume(), StopReason::Breakpoint(bump));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::debugger::{Debugger, StopReason, Watchpoint};
+use crate::interpreter::snapshot::Snapshot;
+use crate::isa::Instruction;
+use std::fmt::Write;
+use std::path::Path;
+
+pub const HELP: &str = "\
+step|s [n]          execute n instructions (default 1)
+next|n              step over JSR
+continue|c          run until a breakpoint, watchpoint, halt or fault
+back|bs [n]         undo n instructions (default 1)
+rcontinue|rc        run backwards to a breakpoint or watched change
+last <loc>          show when a register or data address last changed
+break|b <loc>       set a breakpoint at an address or label
+delete|d <loc>      remove a breakpoint
+watch|w <loc>       stop when a register (r0-r15) or data address changes
+unwatch <loc>       remove a watchpoint
+info                list breakpoints and watchpoints
+stack|bt            print the hardware call stack
+regs|r              print registers and flags
+mem|x <loc> [len]   dump data memory
+list|l              show the current source line
+reset               restart the program
+save <file>         save the machine state as a snapshot
+load <file>         restore a saved snapshot
+quit|q              exit the debugger";
+
+pub enum ReplAction {
+    Output(String),
+    Quit,
+}
+
+pub fn format_registers(dbg: &Debugger) -> String {
+    let interp = &dbg.interpreter;
+    let mut out = String::new();
+    for (ii, value) in interp.registers.iter().enumerate() {
+        let separator = if ii % 4 == 3 { "\n" } else { "  " };
+        write!(out, "r{:<2} = 0x{:02X}{}", ii, value, separator).expect("Write to string failed");
+    }
+    let flags = interp.flags;
+    write!(
+        out,
+        "Z={} C={} N={} V={} IE={} IF={}  pc=0x{:02X}  cycles={}",
+        flags.z as u8,
+        flags.c as u8,
+        flags.n as u8,
+        flags.v as u8,
+        flags.ie as u8,
+        flags.if_ as u8,
+        interp.pc,
+        interp.cycles
+    )
+    .expect("Write to string failed");
+
+    out
+}
+
+pub fn format_stack(dbg: &Debugger) -> String {
+    let interp = &dbg.interpreter;
+    let mut out = format!("depth {}/{}", interp.stack.len(), interp.stack_depth);
+    for (ii, (address, flags)) in interp.stack.iter().enumerate().rev() {
+        let label = dbg.enclosing_label(*address).unwrap_or("?");
+        write!(
+            out,
+            "\n#{} return 0x{:02X} in {}  flags=0x{:02X}",
+            ii,
+            address,
+            label,
+            flags.to_byte()
+        )
+        .expect("Write to string failed");
+    }
+
+    out
+}
+
+pub fn format_memory(dbg: &Debugger, start: u8, length: usize) -> String {
+    let data = dbg.interpreter.bus.dump();
+    let end = (start as usize + length).min(data.len());
+    let mut out = String::new();
+    for row in (start as usize..end).step_by(8) {
+        let bytes: Vec<String> = data[row..(row + 8).min(end)]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        writeln!(out, "{:02X}: {}", row, bytes.join(" ")).expect("Write to string failed");
+    }
+
+    out.trim_end().to_string()
+}
+
+pub fn format_location(dbg: &Debugger) -> String {
+    let pc = dbg.interpreter.pc;
+    let instruction = dbg.interpreter.fetch().map_or_else(
+        |err| err.to_string(),
+        |instr: Instruction| instr.to_string(),
+    );
+    let mut out = format!("0x{:02X}: {}", pc, instruction);
+    if let Some(line) = dbg.current_line() {
+        write!(
+            out,
+            "\n{:>4} | {}",
+            line + 1,
+            dbg.source_text(line).unwrap_or("")
+        )
+        .expect("Write to string failed");
+    }
+
+    out
+}
+
+fn format_watch(watch: &Watchpoint) -> String {
+    match watch {
+        Watchpoint::Register(reg) => format!("r{}", reg),
+        Watchpoint::Data(address) => format!("[0x{:02X}]", address),
+    }
+}
+
+pub fn format_stop(dbg: &Debugger, reason: &StopReason) -> String {
+    let header = match reason {
+        StopReason::Step => None,
+        StopReason::Breakpoint(address) => Some(format!("Breakpoint at 0x{:02X}", address)),
+        StopReason::Watchpoint { watch, old, new } => Some(format!(
+            "Watchpoint {}: 0x{:02X} -> 0x{:02X}",
+            format_watch(watch),
+            old,
+            new
+        )),
+        StopReason::Halted => Some("Program halted".to_string()),
+        StopReason::Fault(err) => Some(format!("Fault: {}", err)),
+        StopReason::CycleLimit => Some("Cycle limit reached".to_string()),
+        StopReason::HistoryStart => Some("Reached the start of the recorded history".to_string()),
+    };
+
+    match header {
+        Some(header) => format!("{}\n{}", header, format_location(dbg)),
+        None => format_location(dbg),
+    }
+}
+
+fn parse_count(argument: Option<&str>) -> Result<usize, String> {
+    match argument {
+        None => Ok(1),
+        Some(count) => count
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid count: {}", count)),
+    }
+}
+
+fn expect_address(dbg: &Debugger, argument: Option<&str>) -> Result<u8, String> {
+    let location = argument.ok_or_else(|| "Expected an address or label".to_string())?;
+    dbg.resolve_address(location)
+        .ok_or_else(|| format!("Unknown location: {}", location))
+}
+
+fn expect_watch(dbg: &Debugger, argument: Option<&str>) -> Result<Watchpoint, String> {
+    let location = argument.ok_or_else(|| "Expected a register, address or label".to_string())?;
+    dbg.resolve_watchpoint(location)
+        .ok_or_else(|| format!("Unknown location: {}", location))
+}
+
+fn expect_history(dbg: &Debugger) -> Result<(), String> {
+    match dbg.interpreter.history {
+        Some(_) => Ok(()),
+        None => Err("history is disabled (start with reverse debugging enabled)".to_string()),
+    }
+}
+
+fn run_command(dbg: &mut Debugger, command: &str, args: &[&str]) -> Result<String, String> {
+    match command {
+        "step" | "s" => {
+            let mut reason = StopReason::Step;
+            for _ in 0..parse_count(args.first().copied())? {
+                reason = dbg.step();
+                if reason != StopReason::Step {
+                    break;
+                }
+            }
+            Ok(format_stop(dbg, &reason))
+        }
+        "next" | "n" => {
+            let reason = dbg.step_over();
+            Ok(format_stop(dbg, &reason))
+        }
+        "continue" | "c" => {
+            let reason = dbg.resume();
+            Ok(format_stop(dbg, &reason))
+        }
+        "back" | "bs" => {
+            expect_history(dbg)?;
+            let mut reason = StopReason::Step;
+            for _ in 0..parse_count(args.first().copied())? {
+                reason = dbg.step_back();
+                if reason != StopReason::Step {
+                    break;
+                }
+            }
+            Ok(format_stop(dbg, &reason))
+        }
+        "rcontinue" | "rc" => {
+            expect_history(dbg)?;
+            let reason = dbg.reverse_continue();
+            Ok(format_stop(dbg, &reason))
+        }
+        "last" => {
+            expect_history(dbg)?;
+            let watch = expect_watch(dbg, args.first().copied())?;
+            match dbg.last_change(watch) {
+                Some(change) => Ok(format!(
+                    "{} changed at cycle {} by 0x{:02X}: 0x{:02X} -> 0x{:02X}",
+                    format_watch(&watch),
+                    change.cycle,
+                    change.pc,
+                    change.old,
+                    change.new
+                )),
+                None => Ok(format!(
+                    "{} unchanged in the recorded history",
+                    format_watch(&watch)
+                )),
+            }
+        }
+        "break" | "b" => {
+            let address = expect_address(dbg, args.first().copied())?;
+            dbg.breakpoints.insert(address);
+            Ok(format!("Breakpoint set at 0x{:02X}", address))
+        }
+        "delete" | "d" => {
+            let address = expect_address(dbg, args.first().copied())?;
+            if dbg.breakpoints.remove(&address) {
+                Ok(format!("Breakpoint at 0x{:02X} removed", address))
+            } else {
+                Err(format!("No breakpoint at 0x{:02X}", address))
+            }
+        }
+        "watch" | "w" => {
+            let watch = expect_watch(dbg, args.first().copied())?;
+            dbg.watchpoints.insert(watch);
+            Ok(format!("Watching {}", format_watch(&watch)))
+        }
+        "unwatch" => {
+            let watch = expect_watch(dbg, args.first().copied())?;
+            if dbg.watchpoints.remove(&watch) {
+                Ok(format!("Stopped watching {}", format_watch(&watch)))
+            } else {
+                Err(format!("Not watching {}", format_watch(&watch)))
+            }
+        }
+        "info" => {
+            let breakpoints: Vec<String> = dbg
+                .breakpoints
+                .iter()
+                .map(|address| format!("0x{:02X}", address))
+                .collect();
+            let watchpoints: Vec<String> = dbg.watchpoints.iter().map(format_watch).collect();
+            Ok(format!(
+                "Breakpoints: {}\nWatchpoints: {}",
+                breakpoints.join(" "),
+                watchpoints.join(" ")
+            ))
+        }
+        "regs" | "r" => Ok(format_registers(dbg)),
+        "stack" | "bt" => Ok(format_stack(dbg)),
+        "mem" | "x" => {
+            let start = expect_address(dbg, args.first().copied())?;
+            let length = match args.get(1) {
+                Some(_) => parse_count(args.get(1).copied())?,
+                None => 16,
+            };
+            Ok(format_memory(dbg, start, length))
+        }
+        "list" | "l" => Ok(format_location(dbg)),
+        "reset" => {
+            dbg.reset();
+            Ok(format_location(dbg))
+        }
+        "save" => {
+            let path = args.first().ok_or_else(|| "Expected a file".to_string())?;
+            Snapshot::capture(&dbg.interpreter)
+                .save(Path::new(path))
+                .map_err(|err| err.to_string())?;
+            Ok(format!("State saved to {}", path))
+        }
+        "load" => {
+            let path = args.first().ok_or_else(|| "Expected a file".to_string())?;
+            Snapshot::load(Path::new(path))
+                .and_then(|snapshot| snapshot.restore(&mut dbg.interpreter))
+                .map_err(|err| err.to_string())?;
+            Ok(format_location(dbg))
+        }
+        "help" | "h" => Ok(HELP.to_string()),
+        _ => Err(format!("Unknown command: {} (try help)", command)),
+    }
+}
+
+pub fn execute(dbg: &mut Debugger, line: &str) -> ReplAction {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.split_first() {
+        None => ReplAction::Output(String::new()),
+        Some((&"quit", _)) | Some((&"q", _)) => ReplAction::Quit,
+        Some((command, args)) => match run_command(dbg, command, args) {
+            Ok(output) => ReplAction::Output(output),
+            Err(error) => ReplAction::Output(format!("error: {}", error)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::debugger::repl::{execute, ReplAction};
+    use crate::debugger::Debugger;
+
+    fn output(dbg: &mut Debugger, line: &str) -> String {
+        match execute(dbg, line) {
+            ReplAction::Output(output) => output,
+            ReplAction::Quit => panic!("Unexpected quit"),
+        }
+    }
+
+    #[test]
+    fn commands_drive_debugger() {
+        let source = "MOV r0, #1\nloop: ADD r0, #1\nMOV 0x10, r0\nJMP loop";
+        let mut compiler = RiscCompiler::new(source.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        let program = compiler.program().expect("Didn't generate").clone();
+        let mut dbg = Debugger::new(program, Some(source)).with_history();
+
+        assert_eq!(output(&mut dbg, "b loop"), "Breakpoint set at 0x01");
+        assert!(output(&mut dbg, "c")
+            .starts_with("Breakpoint at 0x01\n0x01: ADD r0, #0x01\n   2 | loop:"));
+        assert_eq!(output(&mut dbg, "w 0x10"), "Watching [0x10]");
+        assert!(output(&mut dbg, "c").starts_with("Watchpoint [0x10]: 0x00 -> 0x02"));
+        assert_eq!(output(&mut dbg, "x 0x10 4"), "10: 02 00 00 00");
+        assert!(output(&mut dbg, "regs").starts_with("r0  = 0x02"));
+        assert_eq!(output(&mut dbg, "bt"), "depth 0/16");
+        assert_eq!(
+            output(&mut dbg, "d nowhere"),
+            "error: Unknown location: nowhere"
+        );
+        assert_eq!(
+            output(&mut dbg, "last 0x10"),
+            "[0x10] changed at cycle 2 by 0x02: 0x00 -> 0x02"
+        );
+        assert!(output(&mut dbg, "rc").starts_with("Watchpoint [0x10]: 0x00 -> 0x02\n0x02:"));
+        assert!(output(&mut dbg, "bs").starts_with("0x01: ADD r0, #0x01"));
+        assert!(output(&mut dbg, "rc").starts_with("Reached the start of the recorded history"));
+        assert!(matches!(execute(&mut dbg, "q"), ReplAction::Quit));
+    }
+
+    #[test]
+    fn reverse_commands_need_history() {
+        let source = "MOV r0, #1\nMOV 0x10, r0\nend: JMP end";
+        let mut compiler = RiscCompiler::new(source.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        let program = compiler.program().expect("Didn't generate").clone();
+        let mut dbg = Debugger::new(program, Some(source));
+
+        assert!(output(&mut dbg, "s").starts_with("0x01:"));
+        for command in ["bs", "rc", "last r0"].iter() {
+            assert_eq!(
+                output(&mut dbg, command),
+                "error: history is disabled (start with reverse debugging enabled)"
+            );
+        }
+        assert_eq!(dbg.interpreter.pc, 1);
+    }
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum LangParseError {
     #[error("Duplicate label on line")]
     DuplicateLabel,
@@ -36,12 +36,150 @@ pub enum LangParseError {
     Other,
 }
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum LangSyntaxError {
     #[error("InstructionTakesZeroArguments")]
     InstructionTakesZeroArguments,
     #[error("UnexpectedArguments")]
     UnexpectedArguments,
+    #[error("InvalidOperands")]
+    InvalidOperands,
     #[error("Other syntax error")]
     Other,
 }
+
+#[derive(Error, Debug)]
+pub enum LangPreprocessError {
+    #[error("MacroNameExpected")]
+    MacroNameExpected,
+    #[error("InvalidMacroParameter")]
+    InvalidMacroParameter,
+    #[error("DuplicateMacro")]
+    DuplicateMacro,
+    #[error("NestedMacroDefinition")]
+    NestedMacroDefinition,
+    #[error("UnterminatedMacro")]
+    UnterminatedMacro,
+    #[error("UnexpectedEndm")]
+    UnexpectedEndm,
+    #[error("MacroArgumentExpected")]
+    MacroArgumentExpected,
+    #[error("MacroArgumentCountMismatch: expected {expected}, found {found}")]
+    MacroArgumentCountMismatch { expected: usize, found: usize },
+    #[error("MacroNestingTooDeep")]
+    MacroNestingTooDeep,
+    #[error("InvalidCondition")]
+    InvalidCondition,
+    #[error("UndefinedConditionSymbol")]
+    UndefinedConditionSymbol,
+    #[error("UnexpectedElse")]
+    UnexpectedElse,
+    #[error("DuplicateElse")]
+    DuplicateElse,
+    #[error("UnexpectedEndif")]
+    UnexpectedEndif,
+    #[error("UnterminatedConditional")]
+    UnterminatedConditional,
+    #[error("InvalidRepeatCount")]
+    InvalidRepeatCount,
+    #[error("UnexpectedEndr")]
+    UnexpectedEndr,
+    #[error("UnterminatedRepeat")]
+    UnterminatedRepeat,
+}
+
+#[derive(Error, Debug)]
+pub enum LangCompileError {
+    #[error("DuplicateSymbol")]
+    DuplicateSymbol,
+    #[error("UndefinedSymbol")]
+    UndefinedSymbol,
+    #[error("InvalidDirectiveArgument")]
+    InvalidDirectiveArgument,
+    #[error("InvalidAlignment")]
+    InvalidAlignment,
+    #[error("InstructionInDataSection")]
+    InstructionInDataSection,
+    #[error("DataInCodeSection")]
+    DataInCodeSection,
+    #[error("CodeMemoryOverflow")]
+    CodeMemoryOverflow,
+    #[error("DataMemoryOverflow")]
+    DataMemoryOverflow,
+    #[error("InvalidAnonymousLabel")]
+    InvalidAnonymousLabel,
+    #[error("UndefinedAnonymousLabel")]
+    UndefinedAnonymousLabel,
+    #[error("ValueOutOfBounds")]
+    ValueOutOfBounds,
+    #[error(transparent)]
+    Syntax(#[from] LangSyntaxError),
+}
+
+fn line_suffix(line: &Option<usize>) -> String {
+    line.map_or_else(String::new, |line| format!(" (line {})", line))
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum InterpreterError {
+    #[error("InvalidInstruction 0x{word:04X} at 0x{pc:02X}")]
+    InvalidInstruction { pc: u8, word: u16 },
+    #[error("ProgramCounterOutOfBounds at 0x{pc:02X}")]
+    ProgramCounterOutOfBounds { pc: u8 },
+    #[error("StackOverflow at 0x{pc:02X}{}", line_suffix(.line))]
+    StackOverflow { pc: u8, line: Option<usize> },
+    #[error("StackUnderflow at 0x{pc:02X}{}", line_suffix(.line))]
+    StackUnderflow { pc: u8, line: Option<usize> },
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum BusError {
+    #[error("InvalidAddressRange 0x{base:02X} size {size}")]
+    InvalidAddressRange { base: u8, size: u8 },
+    #[error("AddressRangeOverlap at 0x{base:02X} with {name}")]
+    AddressRangeOverlap { base: u8, name: String },
+    #[error("DeviceCountMismatch expected {expected}, found {found}")]
+    DeviceCountMismatch { expected: usize, found: usize },
+    #[error("InvalidDeviceState {0}")]
+    InvalidDeviceState(String),
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum TestSpecError {
+    #[error("{path}: {message}")]
+    Io { path: String, message: String },
+    #[error("InvalidTestFile {0}")]
+    InvalidTestFile(String),
+    #[error("UnknownRegister {0}")]
+    UnknownRegister(String),
+    #[error("UnknownAddress {0}")]
+    UnknownAddress(String),
+    #[error("UnknownFlag {0}")]
+    UnknownFlag(String),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum TraceError {
+    #[error("InvalidTraceHeader")]
+    InvalidTraceHeader,
+    #[error("TruncatedTrace")]
+    TruncatedTrace,
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum SnapshotError {
+    #[error("{path}: {message}")]
+    Io { path: String, message: String },
+    #[error("InvalidSnapshot {0}")]
+    InvalidSnapshot(String),
+    #[error("UnsupportedSnapshotVersion {0}")]
+    UnsupportedVersion(u64),
+    #[error("MissingDevice {name} at 0x{base:02X}")]
+    MissingDevice { name: String, base: u8 },
+    #[error("UnexpectedDevice {name} at 0x{base:02X}")]
+    UnexpectedDevice { name: String, base: u8 },
+    #[error(transparent)]
+    Bus(#[from] BusError),
+}
@@ -0,0 +1,155 @@
+const INSTRUCTION_COLUMN: usize = 8;
+const COMMENT_COLUMN: usize = 32;
+
+struct FormattedLine<'src> {
+    label: Option<&'src str>,
+    mnemonic: Option<&'src str>,
+    arguments: Vec<String>,
+    comment: Option<&'src str>,
+    indented: bool,
+}
+
+// Splits on `separator` outside of string and char literals.
+fn split_outside_literals(text: &str, separator: char, limit: usize) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (ii, chr) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (chr, quote) {
+            ('\\', Some(_)) => escaped = true,
+            ('\"', None) | ('\'', None) => quote = Some(chr),
+            (chr, Some(open)) if chr == open => quote = None,
+            (chr, None) if chr == separator && parts.len() + 1 < limit => {
+                parts.push(&text[start..ii]);
+                start = ii + chr.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+
+    parts
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    split_outside_literals(text, ' ', usize::MAX)
+        .into_iter()
+        .flat_map(|part| split_outside_literals(part, '\t', usize::MAX))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn split_line(line: &str) -> FormattedLine<'_> {
+    let mut halves = split_outside_literals(line, ';', 2).into_iter();
+    let code = halves.next().unwrap_or("").trim();
+    let comment = halves.next().map(|comment| comment.trim_end());
+
+    let (label, rest) = match split_outside_literals(code, ':', 2).as_slice() {
+        [label, rest]
+            if !label.trim().is_empty() && !label.trim().contains(char::is_whitespace) =>
+        {
+            (Some(label.trim()), rest.trim())
+        }
+        _ => (None, code),
+    };
+
+    let mut words = rest.splitn(2, char::is_whitespace);
+    let mnemonic = words.next().filter(|mnemonic| !mnemonic.is_empty());
+    let arguments = words
+        .next()
+        .map(|args| {
+            split_outside_literals(args, ',', usize::MAX)
+                .into_iter()
+                .map(collapse_whitespace)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    FormattedLine {
+        label,
+        mnemonic,
+        arguments,
+        comment,
+        indented: line.starts_with(char::is_whitespace),
+    }
+}
+
+fn pad_to(out: &mut String, column: usize) {
+    let width = out.chars().count();
+    let padding = if width < column { column - width } else { 1 };
+    out.push_str(&" ".repeat(padding));
+}
+
+pub fn format_line(line: &str) -> String {
+    let parts = split_line(line);
+    let mut out = String::new();
+
+    if let Some(label) = parts.label {
+        out.push_str(label);
+        out.push(':');
+    }
+    if let Some(mnemonic) = parts.mnemonic {
+        pad_to(&mut out, INSTRUCTION_COLUMN);
+        out.push_str(mnemonic);
+        if !parts.arguments.is_empty() {
+            out.push(' ');
+            out.push_str(&parts.arguments.join(", "));
+        }
+    }
+    if let Some(comment) = parts.comment {
+        if !out.is_empty() {
+            pad_to(&mut out, COMMENT_COLUMN);
+        } else if parts.indented {
+            pad_to(&mut out, INSTRUCTION_COLUMN);
+        }
+        out.push(';');
+        out.push_str(comment);
+    }
+
+    out
+}
+
+pub fn format_source(source: &str) -> String {
+    let mut out = source
+        .lines()
+        .map(format_line)
+        .collect::<Vec<String>>()
+        .join("\n");
+    out.push('\n');
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatter::{format_line, format_source};
+
+    #[test]
+    fn lines_aligned() {
+        assert_eq!(format_line("loop:ADD   r1,#3"), "loop:   ADD r1, #3");
+        assert_eq!(
+            format_line("  JNZ loop;again"),
+            "        JNZ loop                ;again"
+        );
+        assert_eq!(format_line("    ; note"), "        ; note");
+        assert_eq!(
+            format_line("msg: DB \"a,  b;\" , ';'"),
+            "msg:    DB \"a,  b;\", ';'"
+        );
+        assert_eq!(format_line("DEF   LD    0x80"), "        DEF LD 0x80");
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let source = "start: MOV r0,#1 ; init\n\nfar_away_label: JMP start\n; end\n";
+        let formatted = format_source(source);
+        assert_eq!(format_source(&formatted), formatted);
+    }
+}
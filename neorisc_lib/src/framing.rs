@@ -0,0 +1,53 @@
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+// `Content-Length` framed JSON, shared by the debug adapter and language servers.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::framing::{read_message, write_message};
+    use serde_json::json;
+
+    #[test]
+    fn messages_framed_with_content_length() {
+        let mut out = vec![];
+        write_message(&mut out, &json!({ "a": 1 })).expect("Write failed");
+        assert_eq!(out, b"Content-Length: 7\r\n\r\n{\"a\":1}");
+        let mut input: &[u8] = &out;
+        assert_eq!(
+            read_message(&mut input).expect("Read failed"),
+            Some(json!({ "a": 1 }))
+        );
+        assert_eq!(read_message(&mut input).expect("Read failed"), None);
+    }
+}
@@ -0,0 +1,462 @@
+pub mod packet;
+
+use crate::debugger::{Debugger, StopReason, Watchpoint};
+use crate::error::InterpreterError;
+use crate::gdb::packet::{escape, frame, from_hex, read_incoming, to_hex, Incoming, INTERRUPT};
+use crate::interpreter::bus::Bus;
+use crate::interpreter::{Flags, DATA_MEMORY_SIZE};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Range;
+
+pub const REGISTER_COUNT: usize = 18;
+pub const FLAGS_REGISTER: usize = 16;
+pub const PC_REGISTER: usize = 17;
+// Code memory is visible to memory reads as big-endian words from this offset,
+// lower addresses are data memory.
+pub const CODE_MEMORY_OFFSET: usize = 0x10000;
+
+const RESUME_CHUNK_CYCLES: u64 = 100_000;
+
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.neorisc.core">
+    <flags id="neorisc_flags" size="1">
+      <field name="Z" start="0" end="0"/>
+      <field name="C" start="1" end="1"/>
+      <field name="N" start="2" end="2"/>
+      <field name="V" start="3" end="3"/>
+      <field name="IE" start="4" end="4"/>
+      <field name="IF" start="5" end="5"/>
+    </flags>
+    <reg name="r0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="r1" bitsize="8" type="uint8"/>
+    <reg name="r2" bitsize="8" type="uint8"/>
+    <reg name="r3" bitsize="8" type="uint8"/>
+    <reg name="r4" bitsize="8" type="uint8"/>
+    <reg name="r5" bitsize="8" type="uint8"/>
+    <reg name="r6" bitsize="8" type="uint8"/>
+    <reg name="r7" bitsize="8" type="uint8"/>
+    <reg name="r8" bitsize="8" type="uint8"/>
+    <reg name="r9" bitsize="8" type="uint8"/>
+    <reg name="r10" bitsize="8" type="uint8"/>
+    <reg name="r11" bitsize="8" type="uint8"/>
+    <reg name="r12" bitsize="8" type="uint8"/>
+    <reg name="r13" bitsize="8" type="uint8"/>
+    <reg name="r14" bitsize="8" type="uint8"/>
+    <reg name="r15" bitsize="8" type="uint8"/>
+    <reg name="flags" bitsize="8" type="neorisc_flags"/>
+    <reg name="pc" bitsize="8" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+pub enum Reply {
+    Packet(String),
+    Close(Option<String>),
+}
+
+pub struct GdbStub {
+    pub debugger: Debugger,
+    no_ack: bool,
+}
+
+fn parse_hex(hex: &str) -> Option<usize> {
+    usize::from_str_radix(hex, 16).ok()
+}
+
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let mut parts = args.splitn(2, ',');
+    Some((parse_hex(parts.next()?)?, parse_hex(parts.next()?)?))
+}
+
+fn stop_reply(reason: &StopReason) -> String {
+    match reason {
+        StopReason::Step | StopReason::CycleLimit => "S05".to_string(),
+        StopReason::Breakpoint(_) => "T05swbreak:;".to_string(),
+        StopReason::Watchpoint {
+            watch: Watchpoint::Data(address),
+            ..
+        } => format!("T05watch:{:x};", address),
+        StopReason::Watchpoint { .. } => "S05".to_string(),
+        StopReason::Halted => "W00".to_string(),
+        StopReason::HistoryStart => "T05replaylog:begin;".to_string(),
+        StopReason::Fault(InterpreterError::InvalidInstruction { .. }) => "S04".to_string(),
+        StopReason::Fault(_) => "S0b".to_string(),
+    }
+}
+
+impl GdbStub {
+    pub fn new(debugger: Debugger) -> Self {
+        Self {
+            debugger,
+            no_ack: false,
+        }
+    }
+
+    fn read_register(&self, index: usize) -> Option<u8> {
+        let interp = &self.debugger.interpreter;
+        match index {
+            0..=15 => Some(interp.registers[index]),
+            FLAGS_REGISTER => Some(interp.flags.to_byte()),
+            PC_REGISTER => Some(interp.pc),
+            _ => None,
+        }
+    }
+
+    fn write_register(&mut self, index: usize, value: u8) -> bool {
+        let interp = &mut self.debugger.interpreter;
+        match index {
+            0..=15 => interp.registers[index] = value,
+            FLAGS_REGISTER => interp.flags = Flags::from_byte(value),
+            PC_REGISTER => interp.pc = value,
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn read_memory_byte(&self, address: usize) -> Option<u8> {
+        let interp = &self.debugger.interpreter;
+        if address >= CODE_MEMORY_OFFSET {
+            let offset = address - CODE_MEMORY_OFFSET;
+            let word = interp.code.get(offset / 2)?.to_be_bytes();
+            Some(word[offset % 2])
+        } else if address < DATA_MEMORY_SIZE {
+            Some(interp.bus.peek(address as u8))
+        } else {
+            None
+        }
+    }
+
+    fn write_memory_byte(&mut self, address: usize, value: u8) -> bool {
+        let interp = &mut self.debugger.interpreter;
+        if address >= CODE_MEMORY_OFFSET {
+            let offset = address - CODE_MEMORY_OFFSET;
+            match interp.code.get_mut(offset / 2) {
+                Some(word) => {
+                    let mut bytes = word.to_be_bytes();
+                    bytes[offset % 2] = value;
+                    *word = u16::from_be_bytes(bytes);
+                    true
+                }
+                None => false,
+            }
+        } else if address < DATA_MEMORY_SIZE {
+            interp.bus.write(address as u8, value);
+            true
+        } else {
+            false
+        }
+    }
+
+    // Ranges that overflow or are longer than the larger memory are rejected up front.
+    fn memory_range(&self, args: &str) -> Option<Range<usize>> {
+        let (address, length) = parse_range(args)?;
+        let limit = DATA_MEMORY_SIZE.max(self.debugger.interpreter.code.len() * 2);
+        if length > limit {
+            return None;
+        }
+        Some(address..address.checked_add(length)?)
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let bytes: Option<Vec<u8>> = self.memory_range(args).and_then(|range| {
+            range
+                .map(|address| self.read_memory_byte(address))
+                .collect()
+        });
+
+        bytes.map_or_else(|| "E01".to_string(), |bytes| to_hex(&bytes))
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let mut parts = args.splitn(2, ':');
+        let range = parts.next().and_then(|range| self.memory_range(range));
+        let data = parts.next().and_then(from_hex);
+        match (range, data) {
+            (Some(range), Some(data)) if data.len() == range.len() => {
+                let written = range
+                    .zip(data)
+                    .all(|(address, byte)| self.write_memory_byte(address, byte));
+                if written {
+                    "OK".to_string()
+                } else {
+                    "E01".to_string()
+                }
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn read_features(&self, args: &str) -> String {
+        let range = args.strip_prefix("target.xml:").and_then(parse_range);
+        match range {
+            Some((offset, length)) if offset < TARGET_XML.len() => {
+                let end = (offset + length).min(TARGET_XML.len());
+                let prefix = if end == TARGET_XML.len() { 'l' } else { 'm' };
+                format!("{}{}", prefix, escape(&TARGET_XML[offset..end]))
+            }
+            Some(_) => "l".to_string(),
+            None => "E00".to_string(),
+        }
+    }
+
+    fn change_breakpoint(&mut self, args: &str, insert: bool) -> String {
+        let mut parts = args.splitn(3, ',');
+        let kind = parts.next();
+        let address = parts.next().and_then(parse_hex);
+        let address = match address {
+            Some(address) if address <= u8::MAX as usize => address as u8,
+            _ => return "E01".to_string(),
+        };
+
+        match kind {
+            Some("0") | Some("1") => {
+                if insert {
+                    self.debugger.breakpoints.insert(address);
+                } else {
+                    self.debugger.breakpoints.remove(&address);
+                }
+            }
+            Some("2") | Some("4") => {
+                let watch = Watchpoint::Data(address);
+                if insert {
+                    self.debugger.watchpoints.insert(watch);
+                } else {
+                    self.debugger.watchpoints.remove(&watch);
+                }
+            }
+            _ => return String::new(),
+        }
+
+        "OK".to_string()
+    }
+
+    fn resume(&mut self, interrupted: &mut dyn FnMut() -> bool) -> String {
+        self.debugger.max_cycles = RESUME_CHUNK_CYCLES;
+        loop {
+            let reason = self.debugger.resume();
+            if reason != StopReason::CycleLimit || interrupted() {
+                return stop_reply(&reason);
+            }
+            // Keep breakpoints at the current pc from firing again on the next chunk.
+            if self
+                .debugger
+                .breakpoints
+                .contains(&self.debugger.interpreter.pc)
+            {
+                return stop_reply(&StopReason::Breakpoint(self.debugger.interpreter.pc));
+            }
+        }
+    }
+
+    pub fn handle_packet(&mut self, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Reply {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            "?" => "S05".to_string(),
+            "g" => (0..REGISTER_COUNT)
+                .filter_map(|index| self.read_register(index))
+                .map(|value| to_hex(&[value]))
+                .collect(),
+            "G" => match from_hex(args) {
+                Some(values) if values.len() == REGISTER_COUNT => {
+                    for (index, value) in values.into_iter().enumerate() {
+                        self.write_register(index, value);
+                    }
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "p" => parse_hex(args)
+                .and_then(|index| self.read_register(index))
+                .map_or_else(|| "E01".to_string(), |value| to_hex(&[value])),
+            "P" => {
+                let mut parts = args.splitn(2, '=');
+                let index = parts.next().and_then(parse_hex);
+                let value = parts.next().and_then(from_hex);
+                match (index, value.as_deref()) {
+                    (Some(index), Some([value])) if self.write_register(index, *value) => {
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "s" => stop_reply(&self.debugger.step()),
+            "c" => self.resume(interrupted),
+            "b" => match args {
+                "s" => stop_reply(&self.debugger.step_back()),
+                "c" => stop_reply(&self.debugger.reverse_continue()),
+                _ => String::new(),
+            },
+            "Z" => self.change_breakpoint(args, true),
+            "z" => self.change_breakpoint(args, false),
+            "H" => "OK".to_string(),
+            "T" => "OK".to_string(),
+            "k" => return Reply::Close(None),
+            "D" => return Reply::Close(Some("OK".to_string())),
+            "q" | "Q" => self.handle_query(packet),
+            _ => String::new(),
+        };
+
+        Reply::Packet(reply)
+    }
+
+    fn handle_query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            let mut features =
+                "PacketSize=1000;qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+"
+                    .to_string();
+            if self.debugger.interpreter.history.is_some() {
+                features.push_str(";ReverseStep+;ReverseContinue+");
+            }
+            features
+        } else if let Some(args) = packet.strip_prefix("qXfer:features:read:") {
+            self.read_features(args)
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            "OK".to_string()
+        } else {
+            match packet {
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    fn send(&self, stream: &mut TcpStream, data: &str) -> io::Result<()> {
+        stream.write_all(frame(data).as_bytes())?;
+        stream.flush()
+    }
+
+    pub fn serve_connection(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader = stream.try_clone()?;
+        loop {
+            let packet = match read_incoming(&mut reader) {
+                Ok(Incoming::Packet(packet)) => packet,
+                Ok(Incoming::Nack) if !self.no_ack => {
+                    stream.write_all(b"-")?;
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            if !self.no_ack {
+                stream.write_all(b"+")?;
+            }
+
+            let poll_stream = stream.try_clone()?;
+            let mut interrupted = || poll_interrupt(&poll_stream);
+            // Binary packets such as X are not supported, an empty reply makes gdb fall back.
+            let reply = match std::str::from_utf8(&packet) {
+                Ok(packet) => self.handle_packet(packet, &mut interrupted),
+                Err(_) => Reply::Packet(String::new()),
+            };
+            match reply {
+                Reply::Packet(reply) => self.send(&mut stream, &reply)?,
+                Reply::Close(reply) => {
+                    if let Some(reply) = reply {
+                        self.send(&mut stream, &reply)?;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    pub fn serve(&mut self, listener: TcpListener) -> io::Result<()> {
+        let (stream, _) = listener.accept()?;
+        self.serve_connection(stream)
+    }
+}
+
+fn poll_interrupt(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let mut byte = [0u8];
+    let mut reader = stream;
+    let interrupted = matches!(reader.read(&mut byte), Ok(1) if byte[0] == INTERRUPT);
+    let _ = stream.set_nonblocking(false);
+
+    interrupted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::debugger::Debugger;
+    use crate::gdb::{GdbStub, Reply, TARGET_XML};
+
+    fn stub(source: &str) -> GdbStub {
+        let mut compiler = RiscCompiler::new(source.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        let program = compiler.program().expect("Didn't generate").clone();
+        GdbStub::new(Debugger::new(program, Some(source)))
+    }
+
+    fn reply(stub: &mut GdbStub, packet: &str) -> String {
+        match stub.handle_packet(packet, &mut || false) {
+            Reply::Packet(reply) => reply,
+            Reply::Close(_) => panic!("Unexpected close"),
+        }
+    }
+
+    #[test]
+    fn registers_and_memory() {
+        let mut stub = stub("MOV r1, #0x42\nMOV 0x10, r1\nend: JMP end");
+        assert_eq!(reply(&mut stub, "s"), "S05");
+        assert_eq!(reply(&mut stub, "p1"), "42");
+        assert_eq!(reply(&mut stub, "p11"), "01");
+        assert_eq!(reply(&mut stub, "g").len(), 36);
+        assert_eq!(reply(&mut stub, "P2=7f"), "OK");
+        assert_eq!(stub.debugger.interpreter.registers[2], 0x7F);
+        assert_eq!(reply(&mut stub, "M20,2:abcd"), "OK");
+        assert_eq!(reply(&mut stub, "m20,2"), "abcd");
+        assert_eq!(reply(&mut stub, "m10000,2"), "2142");
+        assert_eq!(reply(&mut stub, "m200,1"), "E01");
+        assert_eq!(reply(&mut stub, "m ffffffffffffffff,2"), "E01");
+        assert_eq!(reply(&mut stub, "mffffffffffffffff,2"), "E01");
+        assert_eq!(reply(&mut stub, "m0,ffffffffffff"), "E01");
+        assert_eq!(reply(&mut stub, "Mffffffffffffffff,1:00"), "E01");
+    }
+
+    #[test]
+    fn breakpoints_watchpoints_and_exit() {
+        let mut stub = stub("MOV r1, #1\nMOV 0x10, r1\nloop: ADD r1, #1\nJMP loop");
+        assert_eq!(reply(&mut stub, "Z2,10,1"), "OK");
+        assert_eq!(reply(&mut stub, "c"), "T05watch:10;");
+        assert_eq!(reply(&mut stub, "z2,10,1"), "OK");
+        assert_eq!(reply(&mut stub, "Z0,2,2"), "OK");
+        assert_eq!(reply(&mut stub, "c"), "T05swbreak:;");
+        assert_eq!(reply(&mut stub, "c"), "T05swbreak:;");
+        assert_eq!(reply(&mut stub, "p1"), "03");
+        assert_eq!(reply(&mut stub, "vMustReplyEmpty"), "");
+        assert!(!reply(&mut stub, "qSupported").contains("ReverseStep"));
+        assert_eq!(reply(&mut stub, "bs"), "T05replaylog:begin;");
+        assert!(matches!(
+            stub.handle_packet("k", &mut || false),
+            Reply::Close(None)
+        ));
+    }
+
+    #[test]
+    fn target_description_served_in_chunks() {
+        let mut stub = stub("NOP");
+        let first = reply(&mut stub, "qXfer:features:read:target.xml:0,a");
+        assert_eq!(first, format!("m{}", &TARGET_XML[0..10]));
+        let rest = reply(
+            &mut stub,
+            &format!("qXfer:features:read:target.xml:10,{:x}", TARGET_XML.len()),
+        );
+        assert!(rest.starts_with('l'));
+        assert!(TARGET_XML.contains("<reg name=\"r15\" bitsize=\"8\""));
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::{self, Read};
+
+pub const INTERRUPT: u8 = 0x03;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Incoming {
+    Packet(Vec<u8>),
+    Interrupt,
+    Ack,
+    Nack,
+}
+
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+pub fn escape(data: &str) -> String {
+    let mut out = String::with_capacity(data.len());
+    for chr in data.chars() {
+        match chr {
+            '}' | '#' | '$' | '*' => {
+                out.push('}');
+                out.push((chr as u8 ^ 0x20) as char);
+            }
+            _ => out.push(chr),
+        }
+    }
+
+    out
+}
+
+pub fn frame(data: &str) -> String {
+    format!("${}#{:02x}", data, checksum(data.as_bytes()))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|ii| u8::from_str_radix(hex.get(ii..ii + 2)?, 16).ok())
+        .collect()
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0u8];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+pub fn read_incoming<R: Read>(reader: &mut R) -> io::Result<Incoming> {
+    loop {
+        match read_byte(reader)? {
+            b'+' => return Ok(Incoming::Ack),
+            b'-' => return Ok(Incoming::Nack),
+            INTERRUPT => return Ok(Incoming::Interrupt),
+            b'$' => break,
+            _ => continue,
+        }
+    }
+
+    // The checksum covers the bytes as sent, before `}` escapes are undone.
+    let mut raw = vec![];
+    loop {
+        match read_byte(reader)? {
+            b'#' => break,
+            byte => raw.push(byte),
+        }
+    }
+    let sum = [read_byte(reader)?, read_byte(reader)?];
+    let expected = std::str::from_utf8(&sum)
+        .ok()
+        .and_then(|sum| u8::from_str_radix(sum, 16).ok());
+    if expected != Some(checksum(&raw)) {
+        return Ok(Incoming::Nack);
+    }
+
+    let mut data = Vec::with_capacity(raw.len());
+    let mut bytes = raw.into_iter();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => data.extend(bytes.next().map(|byte| byte ^ 0x20)),
+            byte => data.push(byte),
+        }
+    }
+    Ok(Incoming::Packet(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gdb::packet::{checksum, escape, frame, from_hex, read_incoming, to_hex, Incoming};
+
+    #[test]
+    fn packets_framed_and_read() {
+        assert_eq!(frame("OK"), "$OK#9a");
+        let mut input: &[u8] = b"+$qSupported#37\x03$g#00";
+        assert_eq!(
+            read_incoming(&mut input).expect("Read failed"),
+            Incoming::Ack
+        );
+        assert_eq!(
+            read_incoming(&mut input).expect("Read failed"),
+            Incoming::Packet(b"qSupported".to_vec())
+        );
+        assert_eq!(
+            read_incoming(&mut input).expect("Read failed"),
+            Incoming::Interrupt
+        );
+        assert_eq!(
+            read_incoming(&mut input).expect("Read failed"),
+            Incoming::Nack
+        );
+    }
+
+    #[test]
+    fn hex_and_escapes() {
+        assert_eq!(to_hex(&[0x00, 0xAB]), "00ab");
+        assert_eq!(from_hex("00ab"), Some(vec![0x00, 0xAB]));
+        assert_eq!(from_hex("0"), None);
+        assert_eq!(escape("a}#"), "a}]}\x03");
+    }
+
+    #[test]
+    fn escaped_bytes_checked_before_unescaping() {
+        let escaped = escape("X0,3:}#$");
+        let packet = format!("${}#{:02x}", escaped, checksum(escaped.as_bytes()));
+        let mut input = packet.as_bytes();
+        assert_eq!(
+            read_incoming(&mut input).expect("Read failed"),
+            Incoming::Packet(b"X0,3:}#$".to_vec())
+        );
+
+        let mut input: &[u8] = b"$}]#00";
+        assert_eq!(
+            read_incoming(&mut input).expect("Read failed"),
+            Incoming::Nack
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use crate::error::BusError;
+use crate::interpreter::bus::{DataBus, Device};
+use crate::interpreter::timer::{Timer, TIMER_ADDRESS, TIMER_REGISTER_COUNT};
+use std::any::Any;
+
+pub const LEDS_ADDRESS: u8 = 0x80;
+pub const SWITCHES_ADDRESS: u8 = 0x81;
+pub const BUTTONS_ADDRESS: u8 = 0x84;
+pub const DISPLAY_ADDRESS: u8 = 0x90;
+
+pub const BUTTON_COUNT: usize = 4;
+pub const SWITCH_COUNT: usize = 8;
+pub const DIGIT_COUNT: usize = 4;
+pub const DISPLAY_REGISTER_COUNT: u8 = 2;
+
+// Segment patterns of the hex digits, bit 0 is segment a, bit 6 is g and bit 7 the decimal point.
+pub const SEGMENT_DIGITS: [u8; 16] = [
+    0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71,
+];
+
+pub fn decode_segments(segments: u8) -> Option<char> {
+    match segments & 0x7F {
+        0 => Some(' '),
+        0x40 => Some('-'),
+        pattern => SEGMENT_DIGITS
+            .iter()
+            .position(|digit| *digit == pattern)
+            .and_then(|digit| std::char::from_digit(digit as u32, 16))
+            .map(|chr| chr.to_ascii_uppercase()),
+    }
+}
+
+// LD register, the eight LEDs show the last written byte.
+#[derive(Debug, Default, Clone)]
+pub struct Leds {
+    pub value: u8,
+}
+
+impl Leds {
+    pub fn is_on(&self, index: usize) -> bool {
+        self.value & (1 << index) != 0
+    }
+}
+
+impl Device for Leds {
+    fn name(&self) -> &str {
+        "leds"
+    }
+
+    fn peek(&self, _offset: u8) -> u8 {
+        self.value
+    }
+
+    fn write(&mut self, _offset: u8, value: u8) {
+        self.value = value;
+    }
+
+    fn reset(&mut self) {
+        self.value = 0;
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        vec![self.value]
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [value] => {
+                self.value = *value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// SW register, read only, driven by the host.
+#[derive(Debug, Default, Clone)]
+pub struct Switches {
+    pub value: u8,
+}
+
+impl Switches {
+    // Returns false for indexes beyond the eight switches.
+    pub fn set_switch(&mut self, index: usize, on: bool) -> bool {
+        if index >= SWITCH_COUNT {
+            return false;
+        }
+        if on {
+            self.value |= 1 << index;
+        } else {
+            self.value &= !(1 << index);
+        }
+        true
+    }
+}
+
+impl Device for Switches {
+    fn name(&self) -> &str {
+        "switches"
+    }
+
+    fn peek(&self, _offset: u8) -> u8 {
+        self.value
+    }
+
+    fn write(&mut self, _offset: u8, _value: u8) {}
+
+    fn save_state(&self) -> Vec<u8> {
+        vec![self.value]
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [value] => {
+                self.value = *value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// BT (state), BTIE (interrupt enable mask) and BTIF (pressed edge flags, written 1s clear them).
+#[derive(Debug, Default, Clone)]
+pub struct Buttons {
+    pub pressed: u8,
+    pub interrupt_enable: u8,
+    pub interrupt_flags: u8,
+}
+
+impl Buttons {
+    pub const STATE: u8 = 0;
+    pub const INTERRUPT_ENABLE: u8 = 1;
+    pub const INTERRUPT_FLAGS: u8 = 2;
+
+    // Returns false for indexes beyond the four buttons.
+    pub fn set_pressed(&mut self, index: usize, pressed: bool) -> bool {
+        if index >= BUTTON_COUNT {
+            return false;
+        }
+        let mask = 1 << index;
+        if pressed && self.pressed & mask == 0 {
+            self.interrupt_flags |= mask;
+        }
+        if pressed {
+            self.pressed |= mask;
+        } else {
+            self.pressed &= !mask;
+        }
+        true
+    }
+
+    pub fn click(&mut self, index: usize) -> bool {
+        self.set_pressed(index, true) && self.set_pressed(index, false)
+    }
+}
+
+impl Device for Buttons {
+    fn name(&self) -> &str {
+        "buttons"
+    }
+
+    fn peek(&self, offset: u8) -> u8 {
+        match offset {
+            Self::STATE => self.pressed,
+            Self::INTERRUPT_ENABLE => self.interrupt_enable,
+            _ => self.interrupt_flags,
+        }
+    }
+
+    fn write(&mut self, offset: u8, value: u8) {
+        match offset {
+            Self::STATE => {}
+            Self::INTERRUPT_ENABLE => self.interrupt_enable = value & 0x0F,
+            _ => self.interrupt_flags &= !value,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.interrupt_enable = 0;
+        self.interrupt_flags = 0;
+    }
+
+    fn interrupt_pending(&self) -> bool {
+        self.interrupt_flags & self.interrupt_enable != 0
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        vec![self.pressed, self.interrupt_enable, self.interrupt_flags]
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [pressed, interrupt_enable, interrupt_flags] => {
+                self.pressed = *pressed;
+                self.interrupt_enable = *interrupt_enable;
+                self.interrupt_flags = *interrupt_flags;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// SEG (segment pattern) and DIG (digit select mask, bit 0 is the rightmost digit) registers.
+// The board scans the digits one at a time, every selected digit keeps the segments it was last lit with.
+#[derive(Debug, Default, Clone)]
+pub struct SevenSegmentDisplay {
+    pub segments: u8,
+    pub select: u8,
+    pub digits: [u8; DIGIT_COUNT],
+}
+
+impl SevenSegmentDisplay {
+    pub const SEGMENTS: u8 = 0;
+    pub const SELECT: u8 = 1;
+
+    fn latch(&mut self) {
+        for (index, digit) in self.digits.iter_mut().enumerate() {
+            if self.select & (1 << index) != 0 {
+                *digit = self.segments;
+            }
+        }
+    }
+
+    // Leftmost digit first, '?' marks patterns that are not a hex digit.
+    pub fn text(&self) -> String {
+        self.digits
+            .iter()
+            .rev()
+            .map(|segments| decode_segments(*segments).unwrap_or('?'))
+            .collect()
+    }
+}
+
+impl Device for SevenSegmentDisplay {
+    fn name(&self) -> &str {
+        "display"
+    }
+
+    fn peek(&self, offset: u8) -> u8 {
+        match offset {
+            Self::SEGMENTS => self.segments,
+            _ => self.select,
+        }
+    }
+
+    fn write(&mut self, offset: u8, value: u8) {
+        match offset {
+            Self::SEGMENTS => self.segments = value,
+            _ => self.select = value & ((1 << DIGIT_COUNT) - 1),
+        }
+        self.latch();
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![self.segments, self.select];
+        state.extend_from_slice(&self.digits);
+        state
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [segments, select, digits @ ..] if digits.len() == DIGIT_COUNT => {
+                self.segments = *segments;
+                self.select = *select;
+                self.digits.copy_from_slice(digits);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub fn attach_lab_board(bus: &mut DataBus) -> Result<(), BusError> {
+    bus.attach(LEDS_ADDRESS, 1, Leds::default())?;
+    bus.attach(SWITCHES_ADDRESS, 1, Switches::default())?;
+    bus.attach(TIMER_ADDRESS, TIMER_REGISTER_COUNT, Timer::default())?;
+    bus.attach(BUTTONS_ADDRESS, 3, Buttons::default())?;
+    bus.attach(
+        DISPLAY_ADDRESS,
+        DISPLAY_REGISTER_COUNT,
+        SevenSegmentDisplay::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::interpreter::board::{
+        attach_lab_board, decode_segments, Buttons, Leds, SevenSegmentDisplay, Switches,
+        DIGIT_COUNT,
+    };
+    use crate::interpreter::bus::{Bus, DataBus, Device};
+    use crate::interpreter::{RiscInterpreter, RunOutcome};
+
+    #[test]
+    fn program_drives_board() {
+        let mut compiler = RiscCompiler::new(
+            "MOV r0, 0x81\nMOV 0x80, r0\nMOV r1, #0x5B\nMOV 0x90, r1\nMOV r1, #0x01\nMOV 0x91, r1\nMOV r1, #0x00\nMOV 0x91, r1\nMOV r1, #0x06\nMOV 0x90, r1\nMOV r1, #0x02\nMOV 0x91, r1\nend: JMP end"
+                .to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let mut bus = DataBus::new(&[]);
+        attach_lab_board(&mut bus).expect("Attach failed");
+        let mut interp =
+            RiscInterpreter::with_bus(compiler.program().expect("Didn't generate"), bus);
+        interp
+            .bus
+            .device_mut::<Switches>()
+            .expect("Switches missing")
+            .set_switch(3, true);
+
+        assert_eq!(interp.run(100), Ok(RunOutcome::Halted));
+        let leds = interp.bus.device::<Leds>().expect("Leds missing");
+        assert!(leds.is_on(3) && !leds.is_on(0));
+        let display = interp
+            .bus
+            .device::<SevenSegmentDisplay>()
+            .expect("Display missing");
+        assert_eq!(display.text(), "  12");
+        assert_eq!((display.segments, display.select), (0x06, 0x02));
+        assert_eq!(decode_segments(0xFF), Some('8'));
+        assert_eq!(decode_segments(0x01), None);
+    }
+
+    #[test]
+    fn buttons_latch_pressed_edges() {
+        let mut bus = DataBus::new(&[]);
+        attach_lab_board(&mut bus).expect("Attach failed");
+        let buttons = bus.device_mut::<Buttons>().expect("Buttons missing");
+        buttons.set_pressed(1, true);
+        buttons.set_pressed(1, true);
+        assert!(!buttons.interrupt_pending());
+
+        bus.write(0x85, 0x02);
+        assert_eq!(bus.read(0x84), 0x02);
+        assert_eq!(bus.read(0x86), 0x02);
+        assert!(bus
+            .device::<Buttons>()
+            .expect("Buttons missing")
+            .interrupt_pending());
+
+        bus.write(0x86, 0x02);
+        let buttons = bus.device_mut::<Buttons>().expect("Buttons missing");
+        assert!(!buttons.interrupt_pending());
+        buttons.click(0);
+        assert_eq!(buttons.pressed, 0x02);
+        assert_eq!(buttons.interrupt_flags, 0x01);
+        assert!(!buttons.set_pressed(4, true));
+        assert!(!buttons.click(usize::MAX));
+        assert_eq!((buttons.pressed, buttons.interrupt_flags), (0x02, 0x01));
+
+        let switches = bus.device_mut::<Switches>().expect("Switches missing");
+        assert!(switches.set_switch(7, true));
+        assert!(!switches.set_switch(8, true));
+        assert!(!switches.set_switch(64, true));
+        assert_eq!(switches.value, 0x80);
+    }
+
+    #[test]
+    fn select_mask_drives_digits() {
+        let mut display = SevenSegmentDisplay::default();
+        display.write(SevenSegmentDisplay::SEGMENTS, 0x40);
+        assert_eq!(display.text(), "    ");
+        display.write(SevenSegmentDisplay::SELECT, 0xFF);
+        assert_eq!(display.select, 0x0F);
+        assert_eq!(display.text(), "----");
+        display.write(SevenSegmentDisplay::SELECT, 0x08);
+        display.write(SevenSegmentDisplay::SEGMENTS, 0x3F);
+        assert_eq!(display.text(), "0---");
+
+        let mut restored = SevenSegmentDisplay::default();
+        assert!(restored.restore_state(&display.save_state()));
+        assert_eq!(restored.text(), "0---");
+        assert!(!restored.restore_state(&[0; DIGIT_COUNT]));
+    }
+}
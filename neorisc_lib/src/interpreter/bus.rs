@@ -0,0 +1,268 @@
+use crate::error::BusError;
+use crate::interpreter::DATA_MEMORY_SIZE;
+use std::any::Any;
+use std::ops::RangeInclusive;
+
+// Where the lab board peripherals live in the data address space.
+pub const PERIPHERAL_BASE: u8 = 0x80;
+
+pub trait Bus {
+    fn read(&mut self, address: u8) -> u8;
+    fn write(&mut self, address: u8, value: u8);
+    // Reads without side effects, for debuggers and inspectors.
+    fn peek(&self, address: u8) -> u8;
+}
+
+// A peripheral mapped into the data address space, offsets are relative to its base address.
+pub trait Device: Any + Send {
+    fn name(&self) -> &str;
+    fn read(&mut self, offset: u8) -> u8 {
+        self.peek(offset)
+    }
+    fn peek(&self, offset: u8) -> u8;
+    fn write(&mut self, offset: u8, value: u8);
+    fn reset(&mut self) {}
+    // Advances time-dependent state by the cycles of the executed instruction.
+    fn tick(&mut self, _cycles: u64) {}
+    fn interrupt_pending(&self) -> bool {
+        false
+    }
+    // Register state for snapshots, host connections are not part of it.
+    fn save_state(&self) -> Vec<u8> {
+        vec![]
+    }
+    // Returns false if the state was not saved by this kind of device.
+    fn restore_state(&mut self, state: &[u8]) -> bool {
+        state.is_empty()
+    }
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+pub struct MappedDevice {
+    pub range: RangeInclusive<u8>,
+    pub device: Box<dyn Device>,
+}
+
+pub struct DataBus {
+    pub memory: Vec<u8>,
+    devices: Vec<MappedDevice>,
+}
+
+impl Default for DataBus {
+    fn default() -> Self {
+        Self {
+            memory: vec![0; DATA_MEMORY_SIZE],
+            devices: vec![],
+        }
+    }
+}
+
+impl DataBus {
+    pub fn new(data: &[u8]) -> Self {
+        let mut bus = Self::default();
+        bus.load(data);
+        bus
+    }
+
+    // Reloads the memory image and resets the attached devices.
+    pub fn load(&mut self, data: &[u8]) {
+        self.memory.iter_mut().for_each(|cell| *cell = 0);
+        for (cell, byte) in self.memory.iter_mut().zip(data.iter()) {
+            *cell = *byte;
+        }
+        for mapped in self.devices.iter_mut() {
+            mapped.device.reset();
+        }
+    }
+
+    pub fn attach<D: Device>(&mut self, base: u8, size: u8, device: D) -> Result<(), BusError> {
+        let end = base
+            .checked_add(size.saturating_sub(1))
+            .filter(|_| size > 0)
+            .ok_or(BusError::InvalidAddressRange { base, size })?;
+        if let Some(mapped) = self
+            .devices
+            .iter()
+            .find(|mapped| base <= *mapped.range.end() && *mapped.range.start() <= end)
+        {
+            return Err(BusError::AddressRangeOverlap {
+                base,
+                name: mapped.device.name().to_string(),
+            });
+        }
+
+        self.devices.push(MappedDevice {
+            range: base..=end,
+            device: Box::new(device),
+        });
+        Ok(())
+    }
+
+    pub fn devices(&self) -> &[MappedDevice] {
+        &self.devices
+    }
+
+    pub fn device<D: Device>(&self) -> Option<&D> {
+        self.devices
+            .iter()
+            .find_map(|mapped| mapped.device.as_any().downcast_ref::<D>())
+    }
+
+    pub fn device_mut<D: Device>(&mut self) -> Option<&mut D> {
+        self.devices
+            .iter_mut()
+            .find_map(|mapped| mapped.device.as_any_mut().downcast_mut::<D>())
+    }
+
+    pub fn tick(&mut self, cycles: u64) {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.tick(cycles);
+        }
+    }
+
+    pub fn interrupt_pending(&self) -> bool {
+        self.devices
+            .iter()
+            .any(|mapped| mapped.device.interrupt_pending())
+    }
+
+    pub fn is_mapped(&self, address: u8) -> bool {
+        self.mapped(address).is_some()
+    }
+
+    pub fn save_device_states(&self) -> Vec<Vec<u8>> {
+        self.devices
+            .iter()
+            .map(|mapped| mapped.device.save_state())
+            .collect()
+    }
+
+    // States in attach order, as returned by `save_device_states`.
+    pub fn restore_device_states(&mut self, states: &[Vec<u8>]) -> Result<(), BusError> {
+        if states.len() != self.devices.len() {
+            return Err(BusError::DeviceCountMismatch {
+                expected: self.devices.len(),
+                found: states.len(),
+            });
+        }
+        for (mapped, state) in self.devices.iter_mut().zip(states.iter()) {
+            if !mapped.device.restore_state(state) {
+                return Err(BusError::InvalidDeviceState(
+                    mapped.device.name().to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn mapped(&self, address: u8) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|mapped| mapped.range.contains(&address))
+    }
+
+    pub fn dump(&self) -> Vec<u8> {
+        (0..DATA_MEMORY_SIZE)
+            .map(|address| self.peek(address as u8))
+            .collect()
+    }
+}
+
+impl Bus for DataBus {
+    fn read(&mut self, address: u8) -> u8 {
+        match self.mapped(address) {
+            Some(index) => {
+                let mapped = &mut self.devices[index];
+                let offset = address - mapped.range.start();
+                mapped.device.read(offset)
+            }
+            None => self.memory[address as usize],
+        }
+    }
+
+    fn write(&mut self, address: u8, value: u8) {
+        match self.mapped(address) {
+            Some(index) => {
+                let mapped = &mut self.devices[index];
+                let offset = address - mapped.range.start();
+                mapped.device.write(offset, value)
+            }
+            None => self.memory[address as usize] = value,
+        }
+    }
+
+    fn peek(&self, address: u8) -> u8 {
+        match self.mapped(address) {
+            Some(index) => {
+                let mapped = &self.devices[index];
+                mapped.device.peek(address - mapped.range.start())
+            }
+            None => self.memory[address as usize],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::BusError;
+    use crate::interpreter::bus::{Bus, DataBus, Device, PERIPHERAL_BASE};
+    use std::any::Any;
+
+    #[derive(Default)]
+    struct Latch {
+        value: u8,
+        reads: usize,
+    }
+
+    impl Device for Latch {
+        fn name(&self) -> &str {
+            "latch"
+        }
+        fn read(&mut self, offset: u8) -> u8 {
+            self.reads += 1;
+            self.peek(offset)
+        }
+        fn peek(&self, offset: u8) -> u8 {
+            self.value.wrapping_add(offset)
+        }
+        fn write(&mut self, _offset: u8, value: u8) {
+            self.value = value;
+        }
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn devices_decoded_by_address() {
+        let mut bus = DataBus::new(&[1, 2, 3]);
+        bus.attach(PERIPHERAL_BASE, 2, Latch::default())
+            .expect("Attach failed");
+        assert_eq!(
+            bus.attach(0x81, 4, Latch::default()),
+            Err(BusError::AddressRangeOverlap {
+                base: 0x81,
+                name: "latch".to_string()
+            })
+        );
+        assert!(bus.attach(0xF0, 0, Latch::default()).is_err());
+
+        bus.write(0x80, 0x10);
+        bus.write(0x82, 0x55);
+        assert_eq!(bus.read(0x81), 0x11);
+        assert_eq!(bus.peek(0x80), 0x10);
+        assert_eq!(bus.read(0x82), 0x55);
+        assert_eq!(bus.read(0x01), 2);
+
+        let latch = bus.device::<Latch>().expect("Device missing");
+        assert_eq!(latch.reads, 1);
+        assert_eq!(bus.dump()[0x81], 0x11);
+
+        bus.load(&[9]);
+        assert_eq!(bus.memory[..3], [9, 0, 0]);
+    }
+}
@@ -0,0 +1,393 @@
+use crate::interpreter::state::MachineState;
+use crate::interpreter::{Flags, RiscInterpreter};
+use crate::isa::Instruction;
+use std::collections::VecDeque;
+
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1 << 16;
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 1024;
+
+// The values one step overwrote, enough to undo it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StepDelta {
+    pub pc: u8,
+    pub cycles: u64,
+    pub halted: bool,
+    pub flags: Flags,
+    pub registers: Vec<(u8, u8)>,
+    pub stack: Option<Vec<(u8, Flags)>>,
+    pub memory: Option<(u8, u8)>,
+    pub devices: Option<Vec<Vec<u8>>>,
+}
+
+impl StepDelta {
+    fn undo(self, interp: &mut RiscInterpreter) {
+        interp.pc = self.pc;
+        interp.cycles = self.cycles;
+        interp.halted = self.halted;
+        interp.flags = self.flags;
+        for (register, old) in self.registers {
+            interp.registers[register as usize] = old;
+        }
+        if let Some(stack) = self.stack {
+            interp.stack = stack;
+        }
+        if let Some((address, old)) = self.memory {
+            interp.bus.memory[address as usize] = old;
+        }
+        if let Some(devices) = self.devices {
+            interp
+                .bus
+                .restore_device_states(&devices)
+                .expect("Devices changed while recording history");
+        }
+    }
+}
+
+// A register or data memory change found in the history, `step` counts from the start of the run.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Change {
+    pub step: u64,
+    pub cycle: u64,
+    pub pc: u8,
+    pub old: u8,
+    pub new: u8,
+}
+
+// Machine state before a step, turned into a delta once the step completed.
+pub(crate) struct PendingDelta {
+    pc: u8,
+    cycles: u64,
+    halted: bool,
+    flags: Flags,
+    registers: [u8; 16],
+    stack: Vec<(u8, Flags)>,
+    memory: Option<(u8, u8)>,
+    devices: Vec<Vec<u8>>,
+}
+
+impl PendingDelta {
+    fn capture(interp: &RiscInterpreter) -> Self {
+        let interrupt = interp.flags.ie && interp.interrupt_requested();
+        let store = match interp.code.get(interp.pc as usize).copied() {
+            Some(word) if !interrupt => match Instruction::decode(word) {
+                Some(Instruction::Store { address, .. }) => Some(address),
+                Some(Instruction::StoreIndirect { ry, .. }) => Some(interp.registers[ry as usize]),
+                _ => None,
+            },
+            _ => None,
+        };
+        Self {
+            pc: interp.pc,
+            cycles: interp.cycles,
+            halted: interp.halted,
+            flags: interp.flags,
+            registers: interp.registers,
+            stack: interp.stack.clone(),
+            memory: store
+                .filter(|address| !interp.bus.is_mapped(*address))
+                .map(|address| (address, interp.bus.memory[address as usize])),
+            devices: interp.bus.save_device_states(),
+        }
+    }
+
+    fn finish(self, interp: &RiscInterpreter) -> StepDelta {
+        let registers = (0..16u8)
+            .map(|register| (register, self.registers[register as usize]))
+            .filter(|(register, old)| interp.registers[*register as usize] != *old)
+            .collect();
+        let devices = if self.devices.is_empty() || interp.bus.save_device_states() == self.devices
+        {
+            None
+        } else {
+            Some(self.devices)
+        };
+        StepDelta {
+            pc: self.pc,
+            cycles: self.cycles,
+            halted: self.halted,
+            flags: self.flags,
+            registers,
+            stack: if interp.stack != self.stack {
+                Some(self.stack)
+            } else {
+                None
+            },
+            memory: self
+                .memory
+                .filter(|(address, old)| interp.bus.memory[*address as usize] != *old),
+            devices,
+        }
+    }
+}
+
+// Undo log of the last `capacity` steps plus full checkpoints every `checkpoint_interval` steps,
+// so rewinding far only undoes the steps after the nearest checkpoint.
+#[derive(Debug, Clone)]
+pub struct History {
+    pub capacity: usize,
+    pub checkpoint_interval: u64,
+    first_step: u64,
+    deltas: VecDeque<StepDelta>,
+    checkpoints: VecDeque<(u64, MachineState)>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY, DEFAULT_CHECKPOINT_INTERVAL)
+    }
+}
+
+impl History {
+    pub fn new(capacity: usize, checkpoint_interval: u64) -> Self {
+        Self {
+            capacity,
+            checkpoint_interval,
+            first_step: 0,
+            deltas: VecDeque::new(),
+            checkpoints: VecDeque::new(),
+        }
+    }
+
+    // Number of steps executed since recording started.
+    pub fn steps(&self) -> u64 {
+        self.first_step + self.deltas.len() as u64
+    }
+
+    // The earliest step that can still be rewound to.
+    pub fn oldest_step(&self) -> u64 {
+        self.first_step
+    }
+
+    pub fn delta(&self, step: u64) -> Option<&StepDelta> {
+        step.checked_sub(self.first_step)
+            .and_then(|index| self.deltas.get(index as usize))
+    }
+
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub(crate) fn begin(&mut self, interp: &RiscInterpreter) -> PendingDelta {
+        let steps = self.steps();
+        let due = self.checkpoint_interval > 0 && steps % self.checkpoint_interval == 0;
+        if due
+            && self
+                .checkpoints
+                .back()
+                .map_or(true, |(step, _)| *step != steps)
+        {
+            self.checkpoints
+                .push_back((steps, MachineState::capture(interp)));
+        }
+        PendingDelta::capture(interp)
+    }
+
+    pub(crate) fn record(&mut self, pending: PendingDelta, interp: &RiscInterpreter) {
+        self.deltas.push_back(pending.finish(interp));
+        while self.deltas.len() > self.capacity {
+            self.deltas.pop_front();
+            self.first_step += 1;
+        }
+        while self
+            .checkpoints
+            .front()
+            .is_some_and(|(step, _)| *step < self.first_step)
+        {
+            self.checkpoints.pop_front();
+        }
+    }
+
+    // Restores the state after `step` steps, the steps after it are forgotten.
+    pub(crate) fn rewind(&mut self, interp: &mut RiscInterpreter, step: u64) -> bool {
+        if step < self.first_step || step > self.steps() {
+            return false;
+        }
+
+        if let Some((checkpoint, state)) = self
+            .checkpoints
+            .iter()
+            .find(|(checkpoint, _)| *checkpoint >= step)
+        {
+            if *checkpoint < self.steps() {
+                state
+                    .restore(interp)
+                    .expect("Devices changed while recording history");
+                self.deltas
+                    .truncate((*checkpoint - self.first_step) as usize);
+            }
+        }
+        while self.steps() > step {
+            let delta = self.deltas.pop_back().expect("Delta missing");
+            delta.undo(interp);
+        }
+        self.checkpoints
+            .retain(|(checkpoint, _)| *checkpoint <= step);
+        true
+    }
+
+    // Latest step at or before `before` whose delta matches.
+    pub fn find_back<F: Fn(&StepDelta) -> bool>(&self, before: u64, matches: F) -> Option<u64> {
+        let end = before
+            .saturating_sub(self.first_step)
+            .min(self.deltas.len() as u64);
+        (0..end)
+            .rev()
+            .find(|index| matches(&self.deltas[*index as usize]))
+            .map(|index| self.first_step + index)
+    }
+
+    fn last_change<F: Fn(&StepDelta) -> Option<u8>>(&self, current: u8, old: F) -> Option<Change> {
+        let step = self.find_back(self.steps(), |delta| old(delta).is_some())?;
+        let delta = self.delta(step)?;
+        Some(Change {
+            step,
+            cycle: delta.cycles,
+            pc: delta.pc,
+            old: old(delta)?,
+            new: current,
+        })
+    }
+
+    pub fn last_register_change(&self, register: u8, current: u8) -> Option<Change> {
+        self.last_change(current, |delta| {
+            delta
+                .registers
+                .iter()
+                .find(|(changed, _)| *changed == register)
+                .map(|(_, old)| *old)
+        })
+    }
+
+    pub fn last_memory_change(&self, address: u8, current: u8) -> Option<Change> {
+        self.last_change(current, |delta| {
+            delta
+                .memory
+                .filter(|(changed, _)| *changed == address)
+                .map(|(_, old)| old)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::interpreter::board::{attach_lab_board, Leds};
+    use crate::interpreter::bus::DataBus;
+    use crate::interpreter::history::History;
+    use crate::interpreter::{RiscInterpreter, RunOutcome};
+
+    const SOURCE: &str = "
+    MOV r0, #0
+loop: ADD r0, #1
+    MOV total, r0
+    MOV 0x80, r0
+    JSR bump
+    CMP r0, #20
+    JNZ loop
+end: JMP end
+bump: ADD r1, #2
+    RTS
+DATA
+total: DB 0
+";
+
+    fn interpreter(history: History) -> RiscInterpreter {
+        let mut compiler = RiscCompiler::new(SOURCE.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        assert!(compiler.diagnostics().is_empty());
+        let program = compiler.program().expect("Didn't generate");
+        let mut bus = DataBus::new(&program.data);
+        attach_lab_board(&mut bus).expect("Attach failed");
+        let mut interp = RiscInterpreter::with_bus(program, bus);
+        interp.history = Some(history);
+        interp
+    }
+
+    fn state(interp: &RiscInterpreter) -> (u8, [u8; 16], u8, u64, usize, Vec<u8>, u8) {
+        (
+            interp.pc,
+            interp.registers,
+            interp.flags.to_byte(),
+            interp.cycles,
+            interp.stack.len(),
+            interp.bus.dump(),
+            interp.bus.device::<Leds>().expect("LEDs missing").value,
+        )
+    }
+
+    #[test]
+    fn step_back_restores_every_state() {
+        let mut interp = interpreter(History::new(1000, 16));
+        let mut states = vec![state(&interp)];
+        for _ in 0..60 {
+            interp.step().expect("Step failed");
+            states.push(state(&interp));
+        }
+        assert!(
+            interp
+                .history
+                .as_ref()
+                .expect("History missing")
+                .checkpoint_count()
+                > 1
+        );
+
+        assert!(interp.rewind_to(7));
+        assert_eq!(state(&interp), states[7]);
+        for expected in (0..7).rev() {
+            assert!(interp.step_back());
+            assert_eq!(state(&interp), states[expected]);
+        }
+        assert!(!interp.step_back());
+    }
+
+    #[test]
+    fn rewind_then_rerun_matches() {
+        let mut interp = interpreter(History::new(1000, 8));
+        assert_eq!(interp.run(10_000).expect("Run failed"), RunOutcome::Halted);
+        let finished = state(&interp);
+        assert!(interp.rewind_to(25));
+        assert_eq!(interp.run(10_000).expect("Run failed"), RunOutcome::Halted);
+        assert_eq!(state(&interp), finished);
+    }
+
+    #[test]
+    fn bounded_and_queried() {
+        let mut interp = interpreter(History::new(10, 4));
+        for _ in 0..30 {
+            interp.step().expect("Step failed");
+        }
+        let history = interp.history.as_ref().expect("History missing");
+        assert_eq!((history.oldest_step(), history.steps()), (20, 30));
+        assert!(history.checkpoint_count() <= 3);
+
+        let change = history
+            .last_register_change(0, interp.registers[0])
+            .expect("No change");
+        assert_eq!((change.pc, change.old, change.new), (1, 3, 4));
+        let change = history
+            .last_memory_change(0, interp.bus.dump()[0])
+            .expect("No change");
+        assert_eq!((change.pc, change.old, change.new), (2, 3, 4));
+        assert_eq!(history.last_register_change(2, 0), None);
+        assert!(!interp.rewind_to(19));
+        assert!(interp.rewind_to(20));
+    }
+
+    #[test]
+    fn faulting_step_recorded() {
+        let mut interp = interpreter(History::new(1000, 4)).with_stack_depth(0);
+        let mut states = vec![state(&interp)];
+        while interp.step().is_ok() {
+            states.push(state(&interp));
+        }
+        let before = states.last().expect("No states").clone();
+        assert_ne!(state(&interp).3, before.3);
+        assert_eq!(
+            interp.history.as_ref().map(History::steps),
+            Some(states.len() as u64)
+        );
+        assert!(interp.step_back());
+        assert_eq!(state(&interp), before);
+    }
+}
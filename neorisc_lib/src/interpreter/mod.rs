@@ -1 +1,463 @@
+pub mod board;
+pub mod bus;
+pub mod history;
+pub mod snapshot;
+pub mod state;
+pub mod timer;
+pub mod trace;
+pub mod uart;
 
+use crate::error::InterpreterError;
+use crate::interpreter::bus::{Bus, DataBus};
+use crate::interpreter::history::History;
+use crate::interpreter::trace::{PendingEntry, Trace};
+use crate::isa::{AluOp, Instruction, JumpKind, Operand, ShiftOp, INTERRUPT_VECTOR, RESET_VECTOR};
+use crate::program::RiscProgram;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const DATA_MEMORY_SIZE: usize = 256;
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+// Saving PC and flags and vectoring takes as long as an instruction.
+pub const INTERRUPT_ENTRY_CYCLES: u64 = 1;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize)]
+pub struct Flags {
+    pub z: bool,
+    pub c: bool,
+    pub n: bool,
+    pub v: bool,
+    pub ie: bool,
+    pub if_: bool,
+}
+
+impl Flags {
+    // Bit order Z, C, N, V, IE, IF from bit 0.
+    pub fn to_byte(&self) -> u8 {
+        [self.z, self.c, self.n, self.v, self.ie, self.if_]
+            .iter()
+            .enumerate()
+            .fold(0, |byte, (bit, set)| byte | (*set as u8) << bit)
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        let bit = |index: u8| byte & (1 << index) != 0;
+        Self {
+            z: bit(0),
+            c: bit(1),
+            n: bit(2),
+            v: bit(3),
+            ie: bit(4),
+            if_: bit(5),
+        }
+    }
+
+    fn set_zn(&mut self, result: u8) {
+        self.z = result == 0;
+        self.n = result & 0x80 != 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StepOutcome {
+    Executed,
+    Halted,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RunOutcome {
+    Halted,
+    CycleLimit,
+}
+
+pub struct RiscInterpreter {
+    pub registers: [u8; 16],
+    pub flags: Flags,
+    pub pc: u8,
+    // Hardware return stack of (return address, flags), at most stack_depth entries.
+    pub stack: Vec<(u8, Flags)>,
+    pub stack_depth: usize,
+    pub code: Vec<u16>,
+    pub source_map: BTreeMap<usize, usize>,
+    pub bus: DataBus,
+    pub cycles: u64,
+    pub halted: bool,
+    // External interrupt request line, ORed with the device interrupts.
+    pub irq: bool,
+    // Records every executed instruction while set.
+    pub trace: Option<Trace>,
+    // Undo log for stepping backwards while set.
+    pub history: Option<History>,
+}
+
+impl RiscInterpreter {
+    pub fn new(program: &RiscProgram) -> Self {
+        Self::with_bus(program, DataBus::new(&program.data))
+    }
+
+    pub fn with_bus(program: &RiscProgram, bus: DataBus) -> Self {
+        Self {
+            registers: [0; 16],
+            flags: Flags::default(),
+            pc: RESET_VECTOR,
+            stack: vec![],
+            stack_depth: DEFAULT_STACK_DEPTH,
+            code: program.code.clone(),
+            source_map: program.source_map.clone(),
+            bus,
+            cycles: 0,
+            halted: false,
+            irq: false,
+            trace: None,
+            history: None,
+        }
+    }
+
+    pub fn with_stack_depth(mut self, depth: usize) -> Self {
+        self.stack_depth = depth;
+        self
+    }
+
+    // 1-based source line of the instruction at the address.
+    pub fn source_line(&self, address: u8) -> Option<usize> {
+        self.source_map
+            .get(&(address as usize))
+            .map(|line| line + 1)
+    }
+
+    pub fn fetch(&self) -> Result<Instruction, InterpreterError> {
+        let word = *self
+            .code
+            .get(self.pc as usize)
+            .ok_or(InterpreterError::ProgramCounterOutOfBounds { pc: self.pc })?;
+
+        Instruction::decode(word).ok_or(InterpreterError::InvalidInstruction { pc: self.pc, word })
+    }
+
+    fn jump_taken(&self, kind: JumpKind) -> bool {
+        match kind {
+            JumpKind::Jmp | JumpKind::Jsr => true,
+            JumpKind::Jz => self.flags.z,
+            JumpKind::Jnz => !self.flags.z,
+            JumpKind::Jc => self.flags.c,
+            JumpKind::Jnc => !self.flags.c,
+            JumpKind::Jn => self.flags.n,
+            JumpKind::Jnn => !self.flags.n,
+            JumpKind::Jv => self.flags.v,
+            JumpKind::Jnv => !self.flags.v,
+        }
+    }
+
+    fn execute_alu(&mut self, op: AluOp, rx: u8, value: u8) {
+        let current = self.registers[rx as usize];
+        let carry = self.flags.c as u8;
+        let result = match op {
+            AluOp::Mov => value,
+            AluOp::Add | AluOp::Adc => {
+                let carry_in = if op == AluOp::Adc { carry } else { 0 };
+                let wide = current as u16 + value as u16 + carry_in as u16;
+                let result = wide as u8;
+                self.flags.c = wide > 0xFF;
+                self.flags.v = (!(current ^ value) & (current ^ result) & 0x80) != 0;
+                result
+            }
+            AluOp::Sub | AluOp::Sbc | AluOp::Cmp => {
+                let borrow_in = if op == AluOp::Sbc { carry } else { 0 };
+                let wide = current as i16 - value as i16 - borrow_in as i16;
+                let result = wide as u8;
+                self.flags.c = wide < 0;
+                self.flags.v = ((current ^ value) & (current ^ result) & 0x80) != 0;
+                result
+            }
+            AluOp::And | AluOp::Tst => current & value,
+            AluOp::Or => current | value,
+            AluOp::Xor => current ^ value,
+        };
+
+        self.flags.set_zn(result);
+        if op != AluOp::Cmp && op != AluOp::Tst {
+            self.registers[rx as usize] = result;
+        }
+    }
+
+    fn execute_shift(&mut self, op: ShiftOp, rx: u8) {
+        let value = self.registers[rx as usize];
+        let carry = self.flags.c as u8;
+        let (result, carry_out) = match op {
+            ShiftOp::Sl0 => (value << 1, value & 0x80 != 0),
+            ShiftOp::Sl1 => (value << 1 | 1, value & 0x80 != 0),
+            ShiftOp::Sr0 => (value >> 1, value & 1 != 0),
+            ShiftOp::Sr1 => (value >> 1 | 0x80, value & 1 != 0),
+            ShiftOp::Asr => (((value as i8) >> 1) as u8, value & 1 != 0),
+            ShiftOp::Rol => (value.rotate_left(1), value & 0x80 != 0),
+            ShiftOp::Ror => (value.rotate_right(1), value & 1 != 0),
+            ShiftOp::Rlc => (value << 1 | carry, value & 0x80 != 0),
+            ShiftOp::Rrc => (value >> 1 | carry << 7, value & 1 != 0),
+            ShiftOp::Swp => (value.rotate_left(4), self.flags.c),
+        };
+
+        self.flags.c = carry_out;
+        self.flags.set_zn(result);
+        self.registers[rx as usize] = result;
+    }
+
+    fn push(&mut self, frame: (u8, Flags)) -> Result<(), InterpreterError> {
+        if self.stack.len() >= self.stack_depth {
+            return Err(InterpreterError::StackOverflow {
+                pc: self.pc,
+                line: self.source_line(self.pc),
+            });
+        }
+
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<(u8, Flags), InterpreterError> {
+        self.stack.pop().ok_or(InterpreterError::StackUnderflow {
+            pc: self.pc,
+            line: self.source_line(self.pc),
+        })
+    }
+
+    pub fn interrupt_requested(&self) -> bool {
+        self.irq || self.bus.interrupt_pending()
+    }
+
+    // Saves PC and flags like JSR, masks further interrupts and jumps to the vector.
+    fn enter_interrupt(&mut self) -> Result<(), InterpreterError> {
+        self.push((self.pc, self.flags))?;
+        self.flags.ie = false;
+        self.flags.if_ = true;
+        self.pc = INTERRUPT_VECTOR;
+        self.cycles += INTERRUPT_ENTRY_CYCLES;
+        self.bus.tick(INTERRUPT_ENTRY_CYCLES);
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<StepOutcome, InterpreterError> {
+        if self.halted || (self.trace.is_none() && self.history.is_none()) {
+            return self.execute_step();
+        }
+
+        let entry = self.trace.as_ref().map(|_| PendingEntry::capture(self));
+        let mut history = self.history.take();
+        let delta = history.as_mut().map(|history| history.begin(self));
+        let result = self.execute_step();
+        // A faulting step may already have counted its cycles and ticked the devices.
+        if let (Some(history), Some(delta)) = (history.as_mut(), delta) {
+            history.record(delta, self);
+        }
+        self.history = history;
+        let outcome = result?;
+        if let (StepOutcome::Executed, Some(entry)) = (outcome, entry) {
+            if let Some(mut trace) = self.trace.take() {
+                if let Some(entry) = entry.finish(self, &trace.filter) {
+                    trace.entries.push(entry);
+                }
+                self.trace = Some(trace);
+            }
+        }
+        Ok(outcome)
+    }
+
+    // Restores the state after `step` recorded steps, false if it is no longer in the history.
+    pub fn rewind_to(&mut self, step: u64) -> bool {
+        match self.history.take() {
+            Some(mut history) => {
+                let rewound = history.rewind(self, step);
+                self.history = Some(history);
+                rewound
+            }
+            None => false,
+        }
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        match self.history.as_ref().map(History::steps) {
+            Some(steps) if steps > 0 => self.rewind_to(steps - 1),
+            _ => false,
+        }
+    }
+
+    fn execute_step(&mut self) -> Result<StepOutcome, InterpreterError> {
+        if self.halted {
+            return Ok(StepOutcome::Halted);
+        }
+        if self.flags.ie && self.interrupt_requested() {
+            self.enter_interrupt()?;
+            return Ok(StepOutcome::Executed);
+        }
+
+        let instruction = self.fetch()?;
+        let next_pc = self.pc.wrapping_add(1);
+        self.cycles += instruction.cycles();
+        self.bus.tick(instruction.cycles());
+
+        match instruction {
+            Instruction::Load { rx, address } => {
+                self.registers[rx as usize] = self.bus.read(address)
+            }
+            Instruction::Store { rx, address } => {
+                self.bus.write(address, self.registers[rx as usize])
+            }
+            Instruction::LoadIndirect { rx, ry } => {
+                self.registers[rx as usize] = self.bus.read(self.registers[ry as usize])
+            }
+            Instruction::StoreIndirect { rx, ry } => self
+                .bus
+                .write(self.registers[ry as usize], self.registers[rx as usize]),
+            Instruction::Alu { op, rx, operand } => {
+                let value = match operand {
+                    Operand::Immediate(value) => value,
+                    Operand::Register(ry) => self.registers[ry as usize],
+                };
+                self.execute_alu(op, rx, value);
+            }
+            Instruction::Shift { op, rx } => self.execute_shift(op, rx),
+            Instruction::Jump { kind, target } => {
+                let target = match target {
+                    Operand::Immediate(address) => address,
+                    Operand::Register(ry) => self.registers[ry as usize],
+                };
+                if kind == JumpKind::Jmp && target == self.pc && !self.flags.ie {
+                    self.halted = true;
+                    return Ok(StepOutcome::Halted);
+                }
+                if self.jump_taken(kind) {
+                    if kind == JumpKind::Jsr {
+                        self.push((next_pc, self.flags))?;
+                    }
+                    self.pc = target;
+                    return Ok(StepOutcome::Executed);
+                }
+            }
+            Instruction::Rts => {
+                let (pc, _) = self.pop()?;
+                self.pc = pc;
+                return Ok(StepOutcome::Executed);
+            }
+            Instruction::Rti => {
+                let (pc, flags) = self.pop()?;
+                self.pc = pc;
+                self.flags = flags;
+                return Ok(StepOutcome::Executed);
+            }
+            Instruction::Cli => self.flags.ie = false,
+            Instruction::Sti => self.flags.ie = true,
+            Instruction::Nop => {}
+        }
+
+        self.pc = next_pc;
+        Ok(StepOutcome::Executed)
+    }
+
+    pub fn run(&mut self, max_cycles: u64) -> Result<RunOutcome, InterpreterError> {
+        while self.cycles < max_cycles {
+            if self.step()? == StepOutcome::Halted {
+                return Ok(RunOutcome::Halted);
+            }
+        }
+
+        Ok(RunOutcome::CycleLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::error::InterpreterError;
+    use crate::interpreter::board::attach_lab_board;
+    use crate::interpreter::bus::DataBus;
+    use crate::interpreter::{RiscInterpreter, RunOutcome};
+    use crate::isa::INTERRUPT_VECTOR;
+
+    fn interpreter(source: &str) -> RiscInterpreter {
+        let mut compiler = RiscCompiler::new(source.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        assert!(compiler.diagnostics().is_empty());
+        RiscInterpreter::new(compiler.program().expect("Didn't generate"))
+    }
+
+    #[test]
+    fn loop_runs_until_halt() {
+        let mut interp = interpreter(
+            "MOV r0, #5\nMOV r1, #0\nloop: ADD r1, #3\nSUB r0, #1\nJNZ loop\nMOV result, r1\nend: JMP end\nDATA\nresult: DB 0",
+        );
+        assert_eq!(interp.run(1000), Ok(RunOutcome::Halted));
+        assert_eq!(interp.registers[1], 15);
+        assert_eq!(interp.bus.memory[0], 15);
+        assert!(interp.flags.z);
+    }
+
+    #[test]
+    fn flags_and_subroutines() {
+        let mut interp = interpreter(
+            "MOV r0, #0x7F\nJSR bump\nMOV r2, #0\nCMP r2, #1\nend: JMP end\nbump: ADD r0, #1\nRTS",
+        );
+        assert_eq!(interp.run(100), Ok(RunOutcome::Halted));
+        assert_eq!(interp.registers[0], 0x80);
+        assert!(interp.flags.c);
+        assert!(interp.flags.n);
+        assert!(interp.stack.is_empty());
+    }
+
+    #[test]
+    fn timer_interrupts_serviced() {
+        let mut compiler = RiscCompiler::new(
+            "JMP main\nJMP isr\nmain: MOV r0, #20\nMOV 0x82, r0\nMOV r0, #0x83\nMOV 0x83, r0\nSTI\nidle: CMP r1, #3\nJNZ idle\nCLI\nend: JMP end\nisr: MOV r2, 0x83\nADD r1, #1\nRTI"
+                .to_string(),
+            RiscCompilerConfig::default(),
+        );
+        compiler.compile();
+        let mut bus = DataBus::new(&[]);
+        attach_lab_board(&mut bus).expect("Attach failed");
+        let mut interp =
+            RiscInterpreter::with_bus(compiler.program().expect("Didn't generate"), bus);
+
+        interp.run(26).expect("Run failed");
+        assert_eq!(interp.pc, INTERRUPT_VECTOR);
+        assert!(interp.flags.if_ && !interp.flags.ie);
+        assert_eq!(interp.stack.len(), 1);
+
+        assert_eq!(interp.run(1000), Ok(RunOutcome::Halted));
+        assert_eq!(interp.registers[1], 3);
+        assert_eq!(interp.registers[2], 0x83 | 0x80);
+        assert!(interp.stack.is_empty());
+        assert!(!interp.flags.if_ && !interp.flags.ie);
+    }
+
+    #[test]
+    fn faults_and_cycle_limit() {
+        let mut interp = interpreter("loop: NOP\nJMP loop");
+        assert_eq!(interp.run(10), Ok(RunOutcome::CycleLimit));
+        assert_eq!(interp.cycles, 10);
+
+        let mut interp = interpreter("MOV r0, #1");
+        assert_eq!(
+            interp.run(10),
+            Err(InterpreterError::InvalidInstruction { pc: 1, word: 0 })
+        );
+
+        let mut interp = interpreter("RTS");
+        assert_eq!(
+            interp.run(10),
+            Err(InterpreterError::StackUnderflow {
+                pc: 0,
+                line: Some(1)
+            })
+        );
+
+        let mut interp = interpreter("NOP\nrecurse: JSR recurse").with_stack_depth(4);
+        let fault = interp.run(100).expect_err("Didn't overflow");
+        assert_eq!(
+            fault,
+            InterpreterError::StackOverflow {
+                pc: 1,
+                line: Some(2)
+            }
+        );
+        assert_eq!(fault.to_string(), "StackOverflow at 0x01 (line 2)");
+        assert_eq!(interp.stack.len(), 4);
+    }
+}
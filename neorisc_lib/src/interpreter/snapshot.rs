@@ -0,0 +1,267 @@
+use crate::error::SnapshotError;
+use crate::interpreter::history::History;
+use crate::interpreter::state::MachineState;
+use crate::interpreter::{Flags, RiscInterpreter, DATA_MEMORY_SIZE};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const SNAPSHOT_FORMAT: &str = "neorisc-snapshot";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    pub name: String,
+    pub base: u8,
+    pub state: Vec<u8>,
+}
+
+// The complete emulator state as a versioned JSON document, flags are stored as bytes.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub format: String,
+    pub version: u32,
+    pub registers: [u8; 16],
+    pub flags: u8,
+    pub pc: u8,
+    // (return address, flags) pairs, the innermost call last.
+    pub stack: Vec<(u8, u8)>,
+    pub stack_depth: usize,
+    pub cycles: u64,
+    pub halted: bool,
+    pub irq: bool,
+    pub code: Vec<u16>,
+    pub source_map: BTreeMap<usize, usize>,
+    pub data: Vec<u8>,
+    pub devices: Vec<DeviceSnapshot>,
+}
+
+impl Snapshot {
+    pub fn capture(interp: &RiscInterpreter) -> Self {
+        let state = MachineState::capture(interp);
+        let devices = interp
+            .bus
+            .devices()
+            .iter()
+            .zip(state.devices)
+            .map(|(mapped, state)| DeviceSnapshot {
+                name: mapped.device.name().to_string(),
+                base: *mapped.range.start(),
+                state,
+            })
+            .collect();
+        Self {
+            format: SNAPSHOT_FORMAT.to_string(),
+            version: SNAPSHOT_VERSION,
+            registers: state.registers,
+            flags: state.flags.to_byte(),
+            pc: state.pc,
+            stack: state
+                .stack
+                .iter()
+                .map(|(address, flags)| (*address, flags.to_byte()))
+                .collect(),
+            stack_depth: interp.stack_depth,
+            cycles: state.cycles,
+            halted: state.halted,
+            irq: state.irq,
+            code: interp.code.clone(),
+            source_map: interp.source_map.clone(),
+            data: state.memory,
+            devices,
+        }
+    }
+
+    pub fn has_device(&self, name: &str) -> bool {
+        self.devices.iter().any(|device| device.name == name)
+    }
+
+    // The interpreter needs the same devices at the same addresses, in any order.
+    // The recorded history is dropped as it belongs to the replaced state.
+    pub fn restore(&self, interp: &mut RiscInterpreter) -> Result<(), SnapshotError> {
+        if self.data.len() != DATA_MEMORY_SIZE {
+            return Err(SnapshotError::InvalidSnapshot(format!(
+                "data memory has {} bytes",
+                self.data.len()
+            )));
+        }
+        let mut devices = vec![];
+        for mapped in interp.bus.devices().iter() {
+            let (name, base) = (mapped.device.name(), *mapped.range.start());
+            let device = self
+                .devices
+                .iter()
+                .find(|device| device.name == name && device.base == base)
+                .ok_or_else(|| SnapshotError::UnexpectedDevice {
+                    name: name.to_string(),
+                    base,
+                })?;
+            devices.push(device.state.clone());
+        }
+        if let Some(device) = self.devices.iter().find(|device| {
+            !interp.bus.devices().iter().any(|mapped| {
+                mapped.device.name() == device.name && *mapped.range.start() == device.base
+            })
+        }) {
+            return Err(SnapshotError::MissingDevice {
+                name: device.name.clone(),
+                base: device.base,
+            });
+        }
+
+        MachineState {
+            registers: self.registers,
+            flags: Flags::from_byte(self.flags),
+            pc: self.pc,
+            stack: self
+                .stack
+                .iter()
+                .map(|(address, flags)| (*address, Flags::from_byte(*flags)))
+                .collect(),
+            cycles: self.cycles,
+            halted: self.halted,
+            irq: self.irq,
+            memory: self.data.clone(),
+            devices,
+        }
+        .restore(interp)?;
+        interp.stack_depth = self.stack_depth;
+        interp.code = self.code.clone();
+        interp.source_map = self.source_map.clone();
+        if let Some(history) = interp.history.as_mut() {
+            *history = History::new(history.capacity, history.checkpoint_interval);
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Snapshot serialization failed")
+    }
+
+    // Checks the format and version before reading the rest, so newer files fail clearly.
+    pub fn from_json(text: &str) -> Result<Self, SnapshotError> {
+        let invalid = |err: serde_json::Error| SnapshotError::InvalidSnapshot(err.to_string());
+        let value: Value = serde_json::from_str(text).map_err(invalid)?;
+        if value["format"] != SNAPSHOT_FORMAT {
+            return Err(SnapshotError::InvalidSnapshot(
+                "not a neorisc snapshot".to_string(),
+            ));
+        }
+        match value["version"].as_u64() {
+            Some(version) if version == SNAPSHOT_VERSION as u64 => {}
+            version => {
+                return Err(SnapshotError::UnsupportedVersion(
+                    version.unwrap_or_default(),
+                ))
+            }
+        }
+        serde_json::from_value(value).map_err(invalid)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        fs::write(path, self.to_json()).map_err(|err| SnapshotError::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let text = fs::read_to_string(path).map_err(|err| SnapshotError::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        })?;
+        Self::from_json(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::error::SnapshotError;
+    use crate::interpreter::board::attach_lab_board;
+    use crate::interpreter::bus::DataBus;
+    use crate::interpreter::snapshot::Snapshot;
+    use crate::interpreter::timer::Timer;
+    use crate::interpreter::{RiscInterpreter, RunOutcome};
+    use crate::program::RiscProgram;
+
+    const SOURCE: &str = "
+    MOV r0, #5
+    MOV 0x82, r0
+    MOV r0, #0x03
+    MOV 0x83, r0
+loop: JSR bump
+    CMP r1, #40
+    JNZ loop
+end: JMP end
+bump: ADD r1, #1
+    MOV 0x80, r1
+    MOV count, r1
+    RTS
+DATA
+count: DB 0
+";
+
+    fn program() -> RiscProgram {
+        let mut compiler = RiscCompiler::new(SOURCE.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        assert!(compiler.diagnostics().is_empty());
+        compiler.program().expect("Didn't generate").clone()
+    }
+
+    fn interpreter(program: &RiscProgram) -> RiscInterpreter {
+        let mut bus = DataBus::new(&program.data);
+        attach_lab_board(&mut bus).expect("Attach failed");
+        RiscInterpreter::with_bus(program, bus)
+    }
+
+    #[test]
+    fn restored_run_matches() {
+        let program = program();
+        let mut original = interpreter(&program);
+        for _ in 0..47 {
+            original.step().expect("Step failed");
+        }
+        let snapshot = Snapshot::capture(&original);
+        assert_eq!(snapshot.stack.len(), 1);
+        let loaded = Snapshot::from_json(&snapshot.to_json()).expect("Parse failed");
+        assert_eq!(loaded, snapshot);
+
+        let mut restored = interpreter(&RiscProgram::new(0, 0));
+        loaded.restore(&mut restored).expect("Restore failed");
+        assert_eq!(Snapshot::capture(&restored), snapshot);
+        assert_eq!(original.run(10_000), Ok(RunOutcome::Halted));
+        assert_eq!(restored.run(10_000), Ok(RunOutcome::Halted));
+        assert_eq!(Snapshot::capture(&restored), Snapshot::capture(&original));
+        assert_eq!(
+            restored.bus.device::<Timer>().map(|timer| timer.reload),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn mismatches_rejected() {
+        let program = program();
+        let snapshot = Snapshot::capture(&interpreter(&program));
+        assert_eq!(
+            snapshot.restore(&mut RiscInterpreter::new(&program)),
+            Err(SnapshotError::MissingDevice {
+                name: "leds".to_string(),
+                base: 0x80
+            })
+        );
+
+        let mut newer = snapshot.clone();
+        newer.version = 2;
+        assert_eq!(
+            Snapshot::from_json(&newer.to_json()),
+            Err(SnapshotError::UnsupportedVersion(2))
+        );
+        assert!(matches!(
+            Snapshot::from_json("{\"format\": \"other\"}"),
+            Err(SnapshotError::InvalidSnapshot(_))
+        ));
+    }
+}
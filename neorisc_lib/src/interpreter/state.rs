@@ -0,0 +1,46 @@
+use crate::error::BusError;
+use crate::interpreter::{Flags, RiscInterpreter};
+
+// Everything that changes while a program runs, code memory and wiring excluded.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MachineState {
+    pub registers: [u8; 16],
+    pub flags: Flags,
+    pub pc: u8,
+    pub stack: Vec<(u8, Flags)>,
+    pub cycles: u64,
+    pub halted: bool,
+    pub irq: bool,
+    pub memory: Vec<u8>,
+    pub devices: Vec<Vec<u8>>,
+}
+
+impl MachineState {
+    pub fn capture(interp: &RiscInterpreter) -> Self {
+        Self {
+            registers: interp.registers,
+            flags: interp.flags,
+            pc: interp.pc,
+            stack: interp.stack.clone(),
+            cycles: interp.cycles,
+            halted: interp.halted,
+            irq: interp.irq,
+            memory: interp.bus.memory.clone(),
+            devices: interp.bus.save_device_states(),
+        }
+    }
+
+    // The interpreter must have the same devices attached as when the state was captured.
+    pub fn restore(&self, interp: &mut RiscInterpreter) -> Result<(), BusError> {
+        interp.bus.restore_device_states(&self.devices)?;
+        interp.registers = self.registers;
+        interp.flags = self.flags;
+        interp.pc = self.pc;
+        interp.stack = self.stack.clone();
+        interp.cycles = self.cycles;
+        interp.halted = self.halted;
+        interp.irq = self.irq;
+        interp.bus.memory.copy_from_slice(&self.memory);
+        Ok(())
+    }
+}
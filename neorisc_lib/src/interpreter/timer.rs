@@ -0,0 +1,173 @@
+use crate::interpreter::bus::Device;
+use std::any::Any;
+use std::convert::TryInto;
+
+pub const TIMER_ADDRESS: u8 = 0x82;
+pub const TIMER_REGISTER_COUNT: u8 = 2;
+
+// TC bits, the prescaler divides the clock by 2^PRESCALER.
+pub const CONTROL_ENABLE: u8 = 0x01;
+pub const CONTROL_REPEAT: u8 = 0x02;
+pub const CONTROL_PRESCALER_SHIFT: u8 = 4;
+pub const CONTROL_PRESCALER_MASK: u8 = 0x70;
+pub const CONTROL_INTERRUPT_ENABLE: u8 = 0x80;
+
+// TS bits
+pub const STATUS_EXPIRED: u8 = 0x80;
+
+// TR / TM (reload value, counter) and TC / TS (control, status) registers.
+// Reading TS clears the expired flag.
+#[derive(Debug, Default, Clone)]
+pub struct Timer {
+    pub reload: u8,
+    pub counter: u8,
+    pub control: u8,
+    pub expired: bool,
+    prescaler_cycles: u64,
+}
+
+impl Timer {
+    pub const COUNTER: u8 = 0;
+    pub const CONTROL: u8 = 1;
+
+    pub fn prescaler(&self) -> u64 {
+        1 << ((self.control & CONTROL_PRESCALER_MASK) >> CONTROL_PRESCALER_SHIFT)
+    }
+
+    pub fn status(&self) -> u8 {
+        (self.control & (CONTROL_ENABLE | CONTROL_REPEAT | CONTROL_PRESCALER_MASK))
+            | if self.expired { STATUS_EXPIRED } else { 0 }
+    }
+
+    // Counts one prescaled clock, the counter expires when it reaches zero.
+    fn count(&mut self) {
+        self.counter = self.counter.wrapping_sub(1);
+        if self.counter != 0 {
+            return;
+        }
+
+        self.expired = true;
+        if self.control & CONTROL_REPEAT != 0 {
+            self.counter = self.reload;
+        } else {
+            self.control &= !CONTROL_ENABLE;
+        }
+    }
+}
+
+impl Device for Timer {
+    fn name(&self) -> &str {
+        "timer"
+    }
+
+    fn read(&mut self, offset: u8) -> u8 {
+        let value = self.peek(offset);
+        if offset == Self::CONTROL {
+            self.expired = false;
+        }
+        value
+    }
+
+    fn peek(&self, offset: u8) -> u8 {
+        match offset {
+            Self::COUNTER => self.counter,
+            _ => self.status(),
+        }
+    }
+
+    fn write(&mut self, offset: u8, value: u8) {
+        match offset {
+            Self::COUNTER => {
+                self.reload = value;
+                self.counter = value;
+            }
+            _ => {
+                if value & CONTROL_ENABLE != 0 && self.control & CONTROL_ENABLE == 0 {
+                    self.counter = self.reload;
+                    self.prescaler_cycles = 0;
+                }
+                self.control = value;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        if self.control & CONTROL_ENABLE == 0 {
+            return;
+        }
+
+        self.prescaler_cycles += cycles;
+        let prescaler = self.prescaler();
+        while self.prescaler_cycles >= prescaler && self.control & CONTROL_ENABLE != 0 {
+            self.prescaler_cycles -= prescaler;
+            self.count();
+        }
+    }
+
+    fn interrupt_pending(&self) -> bool {
+        self.expired && self.control & CONTROL_INTERRUPT_ENABLE != 0
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![self.reload, self.counter, self.control, self.expired as u8];
+        state.extend_from_slice(&self.prescaler_cycles.to_le_bytes());
+        state
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [reload, counter, control, expired, prescaler @ ..] if prescaler.len() == 8 => {
+                self.reload = *reload;
+                self.counter = *counter;
+                self.control = *control;
+                self.expired = *expired != 0;
+                self.prescaler_cycles = u64::from_le_bytes(prescaler.try_into().expect("8 bytes"));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::bus::{Bus, DataBus};
+    use crate::interpreter::timer::{Timer, TIMER_ADDRESS, TIMER_REGISTER_COUNT};
+
+    #[test]
+    fn counts_prescaled_cycles() {
+        let mut bus = DataBus::new(&[]);
+        bus.attach(TIMER_ADDRESS, TIMER_REGISTER_COUNT, Timer::default())
+            .expect("Attach failed");
+        bus.write(0x82, 3);
+        bus.write(0x83, 0x93);
+
+        bus.tick(5);
+        assert_eq!(bus.peek(0x82), 1);
+        assert!(!bus.interrupt_pending());
+        bus.tick(1);
+        assert_eq!(bus.peek(0x82), 3);
+        assert!(bus.interrupt_pending());
+        assert_eq!(bus.read(0x83), 0x93);
+        assert!(!bus.interrupt_pending());
+
+        bus.write(0x83, 0x00);
+        bus.write(0x83, 0x01);
+        bus.tick(3);
+        assert_eq!(bus.read(0x83), 0x80);
+        bus.tick(10);
+        assert_eq!(bus.peek(0x83), 0x00);
+    }
+}
@@ -0,0 +1,528 @@
+use crate::error::TraceError;
+use crate::interpreter::{Flags, RiscInterpreter, DATA_MEMORY_SIZE};
+use crate::isa::Instruction;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+const BINARY_MAGIC: &[u8; 4] = b"NRTR";
+const BINARY_VERSION: u8 = 1;
+const TAG_INTERRUPT: u8 = 0x01;
+const TAG_LINE: u8 = 0x02;
+const TAG_FLAGS: u8 = 0x04;
+
+// Which instructions are recorded (by address) and which register changes they keep.
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    pub addresses: Option<RangeInclusive<u8>>,
+    pub registers: Option<BTreeSet<u8>>,
+}
+
+impl TraceFilter {
+    fn includes_address(&self, pc: u8) -> bool {
+        self.addresses
+            .as_ref()
+            .map_or(true, |addresses| addresses.contains(&pc))
+    }
+
+    fn includes_register(&self, register: u8) -> bool {
+        self.registers
+            .as_ref()
+            .map_or(true, |registers| registers.contains(&register))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub address: u8,
+    pub value: u8,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct RegisterChange {
+    pub register: u8,
+    pub old: u8,
+    pub new: u8,
+}
+
+// One executed instruction; a missing word marks an interrupt entry.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct TraceEntry {
+    pub cycle: u64,
+    pub pc: u8,
+    pub next_pc: u8,
+    pub word: Option<u16>,
+    pub line: Option<usize>,
+    pub registers: Vec<RegisterChange>,
+    pub flags: Option<(u8, u8)>,
+    pub memory: Vec<MemoryAccess>,
+}
+
+impl TraceEntry {
+    pub fn instruction(&self) -> String {
+        match self.word {
+            None => "<interrupt>".to_string(),
+            Some(word) => Instruction::decode(word)
+                .map(|instruction| instruction.to_string())
+                .unwrap_or_else(|| format!("?? 0x{:04X}", word)),
+        }
+    }
+
+    fn format_registers(&self) -> String {
+        self.registers
+            .iter()
+            .map(|change| {
+                format!(
+                    "r{}: 0x{:02X} -> 0x{:02X}",
+                    change.register, change.old, change.new
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn format_flags(&self) -> String {
+        self.flags
+            .map(|(old, new)| format!("flags: 0x{:02X} -> 0x{:02X}", old, new))
+            .unwrap_or_default()
+    }
+
+    fn format_memory(&self) -> String {
+        self.memory
+            .iter()
+            .map(|access| match access.kind {
+                AccessKind::Read => format!("R[0x{:02X}] = 0x{:02X}", access.address, access.value),
+                AccessKind::Write => {
+                    format!("W[0x{:02X}] = 0x{:02X}", access.address, access.value)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Machine state before a step, turned into an entry once the step completed.
+pub(crate) struct PendingEntry {
+    cycle: u64,
+    pc: u8,
+    word: Option<u16>,
+    registers: [u8; 16],
+    flags: Flags,
+}
+
+impl PendingEntry {
+    pub(crate) fn capture(interp: &RiscInterpreter) -> Self {
+        let interrupt = interp.flags.ie && interp.interrupt_requested();
+        Self {
+            cycle: interp.cycles,
+            pc: interp.pc,
+            word: if interrupt {
+                None
+            } else {
+                interp.code.get(interp.pc as usize).copied()
+            },
+            registers: interp.registers,
+            flags: interp.flags,
+        }
+    }
+
+    pub(crate) fn finish(
+        self,
+        interp: &RiscInterpreter,
+        filter: &TraceFilter,
+    ) -> Option<TraceEntry> {
+        if !filter.includes_address(self.pc) {
+            return None;
+        }
+
+        let memory = match self.word.and_then(Instruction::decode) {
+            Some(Instruction::Load { rx, address }) => Some((AccessKind::Read, address, rx)),
+            Some(Instruction::Store { rx, address }) => Some((AccessKind::Write, address, rx)),
+            Some(Instruction::LoadIndirect { rx, ry }) => {
+                Some((AccessKind::Read, self.registers[ry as usize], rx))
+            }
+            Some(Instruction::StoreIndirect { rx, ry }) => {
+                Some((AccessKind::Write, self.registers[ry as usize], rx))
+            }
+            _ => None,
+        }
+        .map(|(kind, address, rx)| MemoryAccess {
+            kind,
+            address,
+            value: match kind {
+                AccessKind::Read => interp.registers[rx as usize],
+                AccessKind::Write => self.registers[rx as usize],
+            },
+        });
+
+        let registers = (0..16u8)
+            .filter(|register| filter.includes_register(*register))
+            .filter_map(|register| {
+                let (old, new) = (
+                    self.registers[register as usize],
+                    interp.registers[register as usize],
+                );
+                if old != new {
+                    Some(RegisterChange { register, old, new })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let (old_flags, new_flags) = (self.flags.to_byte(), interp.flags.to_byte());
+
+        Some(TraceEntry {
+            cycle: self.cycle,
+            pc: self.pc,
+            next_pc: interp.pc,
+            word: self.word,
+            line: interp.source_line(self.pc),
+            registers,
+            flags: if old_flags != new_flags {
+                Some((old_flags, new_flags))
+            } else {
+                None
+            },
+            memory: memory.into_iter().collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub filter: TraceFilter,
+    pub entries: Vec<TraceEntry>,
+}
+
+fn csv_field(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], TraceError> {
+        if self.bytes.len() < count {
+            return Err(TraceError::TruncatedTrace);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, TraceError> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+impl Trace {
+    pub fn new(filter: TraceFilter) -> Self {
+        Self {
+            filter,
+            entries: vec![],
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in self.entries.iter() {
+            let line = entry
+                .line
+                .map(|line| format!("L{}", line))
+                .unwrap_or_default();
+            let changes: Vec<String> = vec![
+                entry.format_registers(),
+                entry.format_flags(),
+                entry.format_memory(),
+            ]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect();
+            let row = format!(
+                "{:>8}  0x{:02X}  {:<18} {:<6} {}",
+                entry.cycle,
+                entry.pc,
+                entry.instruction(),
+                line,
+                changes.join("  ")
+            );
+            writeln!(text, "{}", row.trim_end()).expect("Write to string");
+        }
+        text
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("cycle,pc,instruction,line,registers,flags,memory\n");
+        for entry in self.entries.iter() {
+            writeln!(
+                csv,
+                "{},0x{:02X},{},{},{},{},{}",
+                entry.cycle,
+                entry.pc,
+                csv_field(&entry.instruction()),
+                entry.line.map(|line| line.to_string()).unwrap_or_default(),
+                csv_field(&entry.format_registers()),
+                csv_field(&entry.format_flags()),
+                csv_field(&entry.format_memory())
+            )
+            .expect("Write to string");
+        }
+        csv
+    }
+
+    // Little-endian records behind a magic and version byte, see `from_binary`.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.push(BINARY_VERSION);
+        for entry in self.entries.iter() {
+            let mut tag = 0;
+            if entry.word.is_none() {
+                tag |= TAG_INTERRUPT;
+            }
+            if entry.line.is_some() {
+                tag |= TAG_LINE;
+            }
+            if entry.flags.is_some() {
+                tag |= TAG_FLAGS;
+            }
+            bytes.extend_from_slice(&entry.cycle.to_le_bytes());
+            bytes.extend_from_slice(&[entry.pc, entry.next_pc, tag]);
+            if let Some(word) = entry.word {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+            if let Some(line) = entry.line {
+                bytes.extend_from_slice(&(line as u32).to_le_bytes());
+            }
+            if let Some((old, new)) = entry.flags {
+                bytes.extend_from_slice(&[old, new]);
+            }
+            bytes.push(entry.registers.len() as u8);
+            for change in entry.registers.iter() {
+                bytes.extend_from_slice(&[change.register, change.old, change.new]);
+            }
+            bytes.push(entry.memory.len() as u8);
+            for access in entry.memory.iter() {
+                let kind = match access.kind {
+                    AccessKind::Read => 0,
+                    AccessKind::Write => 1,
+                };
+                bytes.extend_from_slice(&[kind, access.address, access.value]);
+            }
+        }
+        bytes
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, TraceError> {
+        let mut reader = Reader { bytes };
+        if reader.take(BINARY_MAGIC.len())? != BINARY_MAGIC || reader.byte()? != BINARY_VERSION {
+            return Err(TraceError::InvalidTraceHeader);
+        }
+
+        let mut entries = vec![];
+        while !reader.bytes.is_empty() {
+            let cycle = u64::from_le_bytes(reader.take(8)?.try_into().expect("8 bytes"));
+            let (pc, next_pc, tag) = (reader.byte()?, reader.byte()?, reader.byte()?);
+            let word = if tag & TAG_INTERRUPT == 0 {
+                Some(u16::from_le_bytes(
+                    reader.take(2)?.try_into().expect("2 bytes"),
+                ))
+            } else {
+                None
+            };
+            let line = if tag & TAG_LINE != 0 {
+                Some(u32::from_le_bytes(reader.take(4)?.try_into().expect("4 bytes")) as usize)
+            } else {
+                None
+            };
+            let flags = if tag & TAG_FLAGS != 0 {
+                Some((reader.byte()?, reader.byte()?))
+            } else {
+                None
+            };
+            let mut registers = vec![];
+            for _ in 0..reader.byte()? {
+                let change = reader.take(3)?;
+                registers.push(RegisterChange {
+                    register: change[0],
+                    old: change[1],
+                    new: change[2],
+                });
+            }
+            let mut memory = vec![];
+            for _ in 0..reader.byte()? {
+                let access = reader.take(3)?;
+                memory.push(MemoryAccess {
+                    kind: if access[0] == 0 {
+                        AccessKind::Read
+                    } else {
+                        AccessKind::Write
+                    },
+                    address: access[1],
+                    value: access[2],
+                });
+            }
+            entries.push(TraceEntry {
+                cycle,
+                pc,
+                next_pc,
+                word,
+                line,
+                registers,
+                flags,
+                memory,
+            });
+        }
+
+        Ok(Self {
+            filter: TraceFilter::default(),
+            entries,
+        })
+    }
+}
+
+// Registers, flags, PC and data memory rebuilt by replaying recorded entries.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReplayState {
+    pub registers: [u8; 16],
+    pub flags: u8,
+    pub pc: u8,
+    pub memory: Vec<u8>,
+}
+
+impl ReplayState {
+    pub fn new(data: &[u8]) -> Self {
+        let mut memory = vec![0; DATA_MEMORY_SIZE];
+        let len = data.len().min(DATA_MEMORY_SIZE);
+        memory[..len].copy_from_slice(&data[..len]);
+        Self {
+            registers: [0; 16],
+            flags: 0,
+            pc: 0,
+            memory,
+        }
+    }
+
+    pub fn apply(&mut self, entry: &TraceEntry) {
+        for change in entry.registers.iter() {
+            self.registers[change.register as usize] = change.new;
+        }
+        if let Some((_, flags)) = entry.flags {
+            self.flags = flags;
+        }
+        for access in entry.memory.iter() {
+            if access.kind == AccessKind::Write {
+                self.memory[access.address as usize] = access.value;
+            }
+        }
+        self.pc = entry.next_pc;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::interpreter::trace::{AccessKind, MemoryAccess, ReplayState, Trace, TraceFilter};
+    use crate::interpreter::{RiscInterpreter, DATA_MEMORY_SIZE};
+    use std::collections::BTreeSet;
+
+    const SOURCE: &str = "DATA
+value: DB 5
+sum: DB 0
+CODE
+    MOV r0, value
+    ADD r0, #3
+    MOV sum, r0
+    MOV r1, #sum
+    MOV r2, (r1)
+end: JMP end
+";
+
+    fn traced(filter: TraceFilter) -> (RiscInterpreter, Vec<u8>) {
+        let mut compiler = RiscCompiler::new(SOURCE.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        assert!(compiler.diagnostics().is_empty());
+        let program = compiler.program().expect("Didn't generate");
+        let mut interp = RiscInterpreter::new(program);
+        interp.trace = Some(Trace::new(filter));
+        interp.run(100).expect("Run failed");
+        (interp, program.data.clone())
+    }
+
+    #[test]
+    fn records_changes_and_accesses() {
+        let (interp, _) = traced(TraceFilter::default());
+        let trace = interp.trace.expect("Trace missing");
+        assert_eq!(trace.entries.len(), 5);
+        let first = &trace.entries[0];
+        assert_eq!(
+            (first.cycle, first.pc, first.next_pc, first.line),
+            (0, 0, 1, Some(5))
+        );
+        assert_eq!(
+            first.memory,
+            vec![MemoryAccess {
+                kind: AccessKind::Read,
+                address: 0,
+                value: 5
+            }]
+        );
+        assert_eq!(trace.entries[2].memory[0].kind, AccessKind::Write);
+        assert_eq!(trace.entries[4].memory[0].address, 1);
+
+        let text = trace.to_text();
+        assert!(text
+            .lines()
+            .nth(1)
+            .expect("Second line")
+            .contains("r0: 0x05 -> 0x08"));
+        let csv = trace.to_csv();
+        assert!(csv.starts_with("cycle,pc,instruction,line,registers,flags,memory\n"));
+        assert_eq!(csv.lines().count(), 6);
+    }
+
+    #[test]
+    fn binary_round_trip_replays() {
+        let (interp, data) = traced(TraceFilter::default());
+        let trace = interp.trace.as_ref().expect("Trace missing");
+        let decoded = Trace::from_binary(&trace.to_binary()).expect("Decode failed");
+        assert_eq!(decoded.entries, trace.entries);
+        assert!(Trace::from_binary(&trace.to_binary()[..20]).is_err());
+
+        let mut state = ReplayState::new(&data);
+        for entry in decoded.entries.iter() {
+            state.apply(entry);
+        }
+        assert_eq!(state.registers, interp.registers);
+        assert_eq!(state.pc, interp.pc);
+        assert_eq!(state.memory, interp.bus.dump());
+        assert_eq!(
+            ReplayState::new(&[0xAA; 300]).memory,
+            vec![0xAA; DATA_MEMORY_SIZE]
+        );
+    }
+
+    #[test]
+    fn filters_applied() {
+        let filter = TraceFilter {
+            addresses: Some(1..=3),
+            registers: Some([1].iter().copied().collect::<BTreeSet<u8>>()),
+        };
+        let (interp, _) = traced(filter);
+        let trace = interp.trace.expect("Trace missing");
+        let pcs: Vec<u8> = trace.entries.iter().map(|entry| entry.pc).collect();
+        assert_eq!(pcs, vec![1, 2, 3]);
+        assert!(trace.entries[0].registers.is_empty());
+        assert_eq!(trace.entries[2].registers[0].register, 1);
+    }
+}
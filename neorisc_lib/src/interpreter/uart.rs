@@ -0,0 +1,416 @@
+use crate::interpreter::bus::Device;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub const UART_ADDRESS: u8 = 0x88;
+pub const UART_REGISTER_COUNT: u8 = 4;
+pub const FIFO_DEPTH: usize = 16;
+
+// UC bits
+pub const CONTROL_TX_ENABLE: u8 = 0x01;
+pub const CONTROL_RX_ENABLE: u8 = 0x02;
+pub const CONTROL_TX_CLEAR: u8 = 0x04;
+pub const CONTROL_RX_CLEAR: u8 = 0x08;
+
+// US bits
+pub const STATUS_RX_NOT_EMPTY: u8 = 0x01;
+pub const STATUS_TX_NOT_FULL: u8 = 0x02;
+pub const STATUS_TX_EMPTY: u8 = 0x04;
+
+// UIE bits
+pub const INTERRUPT_RX: u8 = 0x01;
+pub const INTERRUPT_TX: u8 = 0x02;
+
+// Cycles of one 8N1 frame (10 bits) at the given clock and baud rate.
+pub fn cycles_per_byte(clock_hz: u64, baud: u64) -> u64 {
+    clock_hz * 10 / baud.max(1)
+}
+
+// The host side of the serial line.
+pub trait SerialHost: Send {
+    fn transmit(&mut self, byte: u8);
+    // Must not block, None when there is no input yet.
+    fn receive(&mut self) -> Option<u8>;
+}
+
+// In-memory line for tests and embedders, clones share the buffers.
+#[derive(Clone, Default)]
+pub struct SerialBuffer {
+    input: Arc<Mutex<VecDeque<u8>>>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SerialBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_input(&self, bytes: &[u8]) {
+        self.input
+            .lock()
+            .expect("Serial buffer poisoned")
+            .extend(bytes.iter());
+    }
+
+    pub fn output(&self) -> Vec<u8> {
+        self.output.lock().expect("Serial buffer poisoned").clone()
+    }
+
+    pub fn take_output(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.output.lock().expect("Serial buffer poisoned"))
+    }
+}
+
+impl SerialHost for SerialBuffer {
+    fn transmit(&mut self, byte: u8) {
+        self.output
+            .lock()
+            .expect("Serial buffer poisoned")
+            .push(byte);
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.input
+            .lock()
+            .expect("Serial buffer poisoned")
+            .pop_front()
+    }
+}
+
+// Byte streams, the input is read on a background thread so blocking sources like stdin
+// or pipes never stall the emulator.
+pub struct StreamHost {
+    input: Option<Receiver<u8>>,
+    output: Box<dyn Write + Send>,
+}
+
+impl StreamHost {
+    pub fn new<R: Read + Send + 'static>(input: Option<R>, output: Box<dyn Write + Send>) -> Self {
+        let input = input.map(|mut reader| {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let mut byte = [0];
+                while let Ok(1) = reader.read(&mut byte) {
+                    if sender.send(byte[0]).is_err() {
+                        break;
+                    }
+                }
+            });
+            receiver
+        });
+
+        Self { input, output }
+    }
+
+    pub fn terminal() -> Self {
+        Self::new(Some(io::stdin()), Box::new(io::stdout()))
+    }
+}
+
+impl SerialHost for StreamHost {
+    fn transmit(&mut self, byte: u8) {
+        // A closed output behaves like a disconnected line.
+        let _ = self
+            .output
+            .write_all(&[byte])
+            .and_then(|_| self.output.flush());
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        self.input.as_ref()?.try_recv().ok()
+    }
+}
+
+// UC (control), US (status), UIE (interrupt enable) and UD (data) registers.
+pub struct Uart {
+    pub control: u8,
+    pub interrupt_enable: u8,
+    pub tx_fifo: VecDeque<u8>,
+    pub rx_fifo: VecDeque<u8>,
+    pub cycles_per_byte: u64,
+    tx_timer: u64,
+    rx_timer: u64,
+    host: Box<dyn SerialHost>,
+}
+
+impl Uart {
+    pub const CONTROL: u8 = 0;
+    pub const STATUS: u8 = 1;
+    pub const INTERRUPT_ENABLE: u8 = 2;
+    pub const DATA: u8 = 3;
+
+    pub fn new<H: SerialHost + 'static>(host: H) -> Self {
+        Self {
+            control: CONTROL_TX_ENABLE | CONTROL_RX_ENABLE,
+            interrupt_enable: 0,
+            tx_fifo: VecDeque::new(),
+            rx_fifo: VecDeque::new(),
+            cycles_per_byte: 0,
+            tx_timer: 0,
+            rx_timer: 0,
+            host: Box::new(host),
+        }
+    }
+
+    // Zero cycles per byte moves data as soon as the emulator ticks.
+    pub fn with_timing(mut self, cycles_per_byte: u64) -> Self {
+        self.cycles_per_byte = cycles_per_byte;
+        self
+    }
+
+    pub fn status(&self) -> u8 {
+        let mut status = 0;
+        if !self.rx_fifo.is_empty() {
+            status |= STATUS_RX_NOT_EMPTY;
+        }
+        if self.tx_fifo.len() < FIFO_DEPTH {
+            status |= STATUS_TX_NOT_FULL;
+        }
+        if self.tx_fifo.is_empty() {
+            status |= STATUS_TX_EMPTY;
+        }
+        status
+    }
+
+    fn transmit(&mut self, cycles: u64) {
+        if self.control & CONTROL_TX_ENABLE == 0 || self.tx_fifo.is_empty() {
+            self.tx_timer = 0;
+            return;
+        }
+
+        self.tx_timer += cycles;
+        while !self.tx_fifo.is_empty() && self.tx_timer >= self.cycles_per_byte {
+            let byte = self.tx_fifo.pop_front().expect("Empty TX FIFO");
+            self.host.transmit(byte);
+            self.tx_timer -= self.cycles_per_byte;
+        }
+    }
+
+    fn receive(&mut self, cycles: u64) {
+        if self.control & CONTROL_RX_ENABLE == 0 {
+            self.rx_timer = 0;
+            return;
+        }
+
+        self.rx_timer += cycles;
+        while self.rx_fifo.len() < FIFO_DEPTH && self.rx_timer >= self.cycles_per_byte {
+            match self.host.receive() {
+                Some(byte) => self.rx_fifo.push_back(byte),
+                None => {
+                    self.rx_timer = self.rx_timer.min(self.cycles_per_byte);
+                    return;
+                }
+            }
+            self.rx_timer -= self.cycles_per_byte;
+        }
+    }
+}
+
+impl Device for Uart {
+    fn name(&self) -> &str {
+        "uart"
+    }
+
+    fn read(&mut self, offset: u8) -> u8 {
+        match offset {
+            Self::DATA => self.rx_fifo.pop_front().unwrap_or(0),
+            _ => self.peek(offset),
+        }
+    }
+
+    fn peek(&self, offset: u8) -> u8 {
+        match offset {
+            Self::CONTROL => self.control,
+            Self::STATUS => self.status(),
+            Self::INTERRUPT_ENABLE => self.interrupt_enable,
+            _ => self.rx_fifo.front().copied().unwrap_or(0),
+        }
+    }
+
+    fn write(&mut self, offset: u8, value: u8) {
+        match offset {
+            Self::CONTROL => {
+                if value & CONTROL_TX_CLEAR != 0 {
+                    self.tx_fifo.clear();
+                }
+                if value & CONTROL_RX_CLEAR != 0 {
+                    self.rx_fifo.clear();
+                }
+                self.control = value & (CONTROL_TX_ENABLE | CONTROL_RX_ENABLE);
+            }
+            Self::STATUS => {}
+            Self::INTERRUPT_ENABLE => self.interrupt_enable = value & (INTERRUPT_RX | INTERRUPT_TX),
+            _ => {
+                if self.tx_fifo.len() < FIFO_DEPTH {
+                    self.tx_fifo.push_back(value);
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.control = CONTROL_TX_ENABLE | CONTROL_RX_ENABLE;
+        self.interrupt_enable = 0;
+        self.tx_fifo.clear();
+        self.rx_fifo.clear();
+        self.tx_timer = 0;
+        self.rx_timer = 0;
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.transmit(cycles);
+        self.receive(cycles);
+    }
+
+    // Control, interrupt enable, timing, then the TX FIFO behind its length and the RX FIFO.
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![self.control, self.interrupt_enable];
+        for value in [self.cycles_per_byte, self.tx_timer, self.rx_timer].iter() {
+            state.extend_from_slice(&value.to_le_bytes());
+        }
+        state.push(self.tx_fifo.len() as u8);
+        state.extend(self.tx_fifo.iter());
+        state.extend(self.rx_fifo.iter());
+        state
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> bool {
+        const FIXED: usize = 2 + 3 * 8 + 1;
+        if state.len() < FIXED || state.len() - FIXED < state[FIXED - 1] as usize {
+            return false;
+        }
+        let value = |index: usize| {
+            let start = 2 + index * 8;
+            u64::from_le_bytes(state[start..start + 8].try_into().expect("8 bytes"))
+        };
+        let (tx, rx) = state[FIXED..].split_at(state[FIXED - 1] as usize);
+        if tx.len() > FIFO_DEPTH || rx.len() > FIFO_DEPTH {
+            return false;
+        }
+        self.control = state[0];
+        self.interrupt_enable = state[1];
+        self.cycles_per_byte = value(0);
+        self.tx_timer = value(1);
+        self.rx_timer = value(2);
+        self.tx_fifo = tx.iter().copied().collect();
+        self.rx_fifo = rx.iter().copied().collect();
+        true
+    }
+
+    fn interrupt_pending(&self) -> bool {
+        let status = self.status();
+        (self.interrupt_enable & INTERRUPT_RX != 0 && status & STATUS_RX_NOT_EMPTY != 0)
+            || (self.interrupt_enable & INTERRUPT_TX != 0 && status & STATUS_TX_EMPTY != 0)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::interpreter::bus::{Bus, DataBus, Device};
+    use crate::interpreter::uart::{
+        cycles_per_byte, SerialBuffer, StreamHost, Uart, UART_ADDRESS, UART_REGISTER_COUNT,
+    };
+    use crate::interpreter::{RiscInterpreter, RunOutcome};
+
+    #[test]
+    fn program_echoes_serial_input() {
+        let source = "
+wait: MOV r0, 0x89
+      TST r0, #0x01
+      JZ wait
+      MOV r1, 0x8B
+      CMP r1, #'.'
+      JZ end
+      ADD r1, #1
+      MOV 0x8B, r1
+      JMP wait
+end:  JMP end";
+        let mut compiler = RiscCompiler::new(source.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        let serial = SerialBuffer::new();
+        serial.push_input(b"HAL.");
+        let mut bus = DataBus::new(&[]);
+        bus.attach(
+            UART_ADDRESS,
+            UART_REGISTER_COUNT,
+            Uart::new(serial.clone()).with_timing(cycles_per_byte(16, 4)),
+        )
+        .expect("Attach failed");
+        let mut interp =
+            RiscInterpreter::with_bus(compiler.program().expect("Didn't generate"), bus);
+
+        assert_eq!(interp.run(1000), Ok(RunOutcome::Halted));
+        interp.bus.tick(100);
+        assert_eq!(serial.take_output(), b"IBM");
+        assert!(interp.cycles >= 4 * 40);
+    }
+
+    #[test]
+    fn fifo_status_and_interrupts() {
+        let serial = SerialBuffer::new();
+        let mut bus = DataBus::new(&[]);
+        bus.attach(
+            UART_ADDRESS,
+            UART_REGISTER_COUNT,
+            Uart::new(serial.clone()).with_timing(10),
+        )
+        .expect("Attach failed");
+
+        for byte in 0..20 {
+            bus.write(0x8B, byte);
+        }
+        assert_eq!(bus.read(0x89), 0x00);
+        bus.write(0x8A, 0x02);
+        assert!(!bus.interrupt_pending());
+
+        bus.tick(9);
+        assert!(serial.output().is_empty());
+        bus.tick(1);
+        assert_eq!(serial.output(), vec![0]);
+        bus.tick(1000);
+        assert_eq!(serial.output().len(), 16);
+        assert!(bus.interrupt_pending());
+
+        serial.push_input(b"ab");
+        bus.write(0x8A, 0x01);
+        bus.tick(10);
+        assert_eq!(bus.peek(0x8B), b'a');
+        assert!(bus.interrupt_pending());
+        bus.write(0x88, 0x0B);
+        assert_eq!(bus.read(0x89) & 0x01, 0);
+
+        let uart = bus.device_mut::<Uart>().expect("Uart missing");
+        uart.reset();
+        assert_eq!(uart.control, 0x03);
+    }
+
+    #[test]
+    fn stream_host_reads_in_background() {
+        use crate::interpreter::uart::SerialHost;
+
+        let mut host = StreamHost::new(Some(&b"x"[..]), Box::new(Vec::new()));
+        let mut received = None;
+        for _ in 0..1000 {
+            received = host.receive();
+            if received.is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(received, Some(b'x'));
+    }
+}
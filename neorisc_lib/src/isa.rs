@@ -0,0 +1,430 @@
+use crate::lang::LangCommand;
+use std::fmt;
+
+// Instruction word layout (16 bit), 0x0000 is never valid so zero-filled code memory faults:
+//   0x0X0O  shift/rotate rX with ShiftOp index O - 1
+//   0xEXAA  MOV rX, AA        load from data memory
+//   0x1XAA  MOV AA, rX        store to data memory
+//   0x2XII  MOV rX, #II       ALU with immediate, opcode = 0x2 + AluOp index
+//   ...
+//   0xBXII  TST rX, #II
+//   0xCKAA  jump of kind K to AA
+//   0xDXYO  ALU rX, rY with AluOp index O, O = 0xA/0xB for MOV rX, (rY) / MOV (rY), rX
+//   0xFKY0  jump of kind K to (rY), K = 0xA..0xF for RTS, RTI, CLI, STI, NOP
+
+pub const RESET_VECTOR: u8 = 0x00;
+pub const INTERRUPT_VECTOR: u8 = 0x01;
+pub const NOP_WORD: u16 = 0xFF00;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AluOp {
+    Mov,
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    Cmp,
+    And,
+    Or,
+    Xor,
+    Tst,
+}
+
+const ALU_OPS: [AluOp; 10] = [
+    AluOp::Mov,
+    AluOp::Add,
+    AluOp::Adc,
+    AluOp::Sub,
+    AluOp::Sbc,
+    AluOp::Cmp,
+    AluOp::And,
+    AluOp::Or,
+    AluOp::Xor,
+    AluOp::Tst,
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ShiftOp {
+    Sl0,
+    Sl1,
+    Sr0,
+    Sr1,
+    Asr,
+    Rol,
+    Ror,
+    Rlc,
+    Rrc,
+    Swp,
+}
+
+const SHIFT_OPS: [ShiftOp; 10] = [
+    ShiftOp::Sl0,
+    ShiftOp::Sl1,
+    ShiftOp::Sr0,
+    ShiftOp::Sr1,
+    ShiftOp::Asr,
+    ShiftOp::Rol,
+    ShiftOp::Ror,
+    ShiftOp::Rlc,
+    ShiftOp::Rrc,
+    ShiftOp::Swp,
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JumpKind {
+    Jmp,
+    Jz,
+    Jnz,
+    Jc,
+    Jnc,
+    Jn,
+    Jnn,
+    Jv,
+    Jnv,
+    Jsr,
+}
+
+const JUMP_KINDS: [JumpKind; 10] = [
+    JumpKind::Jmp,
+    JumpKind::Jz,
+    JumpKind::Jnz,
+    JumpKind::Jc,
+    JumpKind::Jnc,
+    JumpKind::Jn,
+    JumpKind::Jnn,
+    JumpKind::Jv,
+    JumpKind::Jnv,
+    JumpKind::Jsr,
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Operand {
+    Immediate(u8),
+    Register(u8),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Instruction {
+    Load { rx: u8, address: u8 },
+    Store { rx: u8, address: u8 },
+    LoadIndirect { rx: u8, ry: u8 },
+    StoreIndirect { rx: u8, ry: u8 },
+    Alu { op: AluOp, rx: u8, operand: Operand },
+    Shift { op: ShiftOp, rx: u8 },
+    Jump { kind: JumpKind, target: Operand },
+    Rts,
+    Rti,
+    Cli,
+    Sti,
+    Nop,
+}
+
+fn index_of<T: PartialEq>(table: &[T], item: &T) -> u16 {
+    table
+        .iter()
+        .position(|entry| entry == item)
+        .expect("Operation missing from table") as u16
+}
+
+impl AluOp {
+    pub fn from_command(command: &LangCommand) -> Option<AluOp> {
+        match command {
+            LangCommand::MOV => Some(AluOp::Mov),
+            LangCommand::ADD => Some(AluOp::Add),
+            LangCommand::ADC => Some(AluOp::Adc),
+            LangCommand::SUB => Some(AluOp::Sub),
+            LangCommand::SBC => Some(AluOp::Sbc),
+            LangCommand::CMP => Some(AluOp::Cmp),
+            LangCommand::AND => Some(AluOp::And),
+            LangCommand::OR => Some(AluOp::Or),
+            LangCommand::XOR => Some(AluOp::Xor),
+            LangCommand::TST => Some(AluOp::Tst),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            AluOp::Mov => "MOV",
+            AluOp::Add => "ADD",
+            AluOp::Adc => "ADC",
+            AluOp::Sub => "SUB",
+            AluOp::Sbc => "SBC",
+            AluOp::Cmp => "CMP",
+            AluOp::And => "AND",
+            AluOp::Or => "OR",
+            AluOp::Xor => "XOR",
+            AluOp::Tst => "TST",
+        }
+    }
+}
+
+impl ShiftOp {
+    pub fn from_command(command: &LangCommand) -> Option<ShiftOp> {
+        match command {
+            LangCommand::SL0 => Some(ShiftOp::Sl0),
+            LangCommand::SL1 => Some(ShiftOp::Sl1),
+            LangCommand::SR0 => Some(ShiftOp::Sr0),
+            LangCommand::SR1 => Some(ShiftOp::Sr1),
+            LangCommand::ASR => Some(ShiftOp::Asr),
+            LangCommand::ROL => Some(ShiftOp::Rol),
+            LangCommand::ROR => Some(ShiftOp::Ror),
+            LangCommand::RLC => Some(ShiftOp::Rlc),
+            LangCommand::RRC => Some(ShiftOp::Rrc),
+            LangCommand::SWP => Some(ShiftOp::Swp),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            ShiftOp::Sl0 => "SL0",
+            ShiftOp::Sl1 => "SL1",
+            ShiftOp::Sr0 => "SR0",
+            ShiftOp::Sr1 => "SR1",
+            ShiftOp::Asr => "ASR",
+            ShiftOp::Rol => "ROL",
+            ShiftOp::Ror => "ROR",
+            ShiftOp::Rlc => "RLC",
+            ShiftOp::Rrc => "RRC",
+            ShiftOp::Swp => "SWP",
+        }
+    }
+}
+
+impl JumpKind {
+    pub fn from_command(command: &LangCommand) -> Option<JumpKind> {
+        match command {
+            LangCommand::JMP => Some(JumpKind::Jmp),
+            LangCommand::JZ => Some(JumpKind::Jz),
+            LangCommand::JNZ => Some(JumpKind::Jnz),
+            LangCommand::JC => Some(JumpKind::Jc),
+            LangCommand::JNC => Some(JumpKind::Jnc),
+            LangCommand::JN => Some(JumpKind::Jn),
+            LangCommand::JNN => Some(JumpKind::Jnn),
+            LangCommand::JV => Some(JumpKind::Jv),
+            LangCommand::JNV => Some(JumpKind::Jnv),
+            LangCommand::JSR => Some(JumpKind::Jsr),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            JumpKind::Jmp => "JMP",
+            JumpKind::Jz => "JZ",
+            JumpKind::Jnz => "JNZ",
+            JumpKind::Jc => "JC",
+            JumpKind::Jnc => "JNC",
+            JumpKind::Jn => "JN",
+            JumpKind::Jnn => "JNN",
+            JumpKind::Jv => "JV",
+            JumpKind::Jnv => "JNV",
+            JumpKind::Jsr => "JSR",
+        }
+    }
+}
+
+impl Instruction {
+    pub fn encode(&self) -> u16 {
+        let reg = |reg: u8| (reg as u16 & 0xF) << 8;
+        match *self {
+            Instruction::Load { rx, address } => 0xE000 | reg(rx) | address as u16,
+            Instruction::Store { rx, address } => 0x1000 | reg(rx) | address as u16,
+            Instruction::Alu {
+                op,
+                rx,
+                operand: Operand::Immediate(value),
+            } => ((0x2 + index_of(&ALU_OPS, &op)) << 12) | reg(rx) | value as u16,
+            Instruction::Alu {
+                op,
+                rx,
+                operand: Operand::Register(ry),
+            } => 0xD000 | reg(rx) | (ry as u16 & 0xF) << 4 | index_of(&ALU_OPS, &op),
+            Instruction::LoadIndirect { rx, ry } => 0xD00A | reg(rx) | (ry as u16 & 0xF) << 4,
+            Instruction::StoreIndirect { rx, ry } => 0xD00B | reg(rx) | (ry as u16 & 0xF) << 4,
+            Instruction::Shift { op, rx } => reg(rx) | (index_of(&SHIFT_OPS, &op) + 1),
+            Instruction::Jump {
+                kind,
+                target: Operand::Immediate(address),
+            } => 0xC000 | index_of(&JUMP_KINDS, &kind) << 8 | address as u16,
+            Instruction::Jump {
+                kind,
+                target: Operand::Register(ry),
+            } => 0xF000 | index_of(&JUMP_KINDS, &kind) << 8 | (ry as u16 & 0xF) << 4,
+            Instruction::Rts => 0xFA00,
+            Instruction::Rti => 0xFB00,
+            Instruction::Cli => 0xFC00,
+            Instruction::Sti => 0xFD00,
+            Instruction::Nop => NOP_WORD,
+        }
+    }
+
+    pub fn decode(word: u16) -> Option<Instruction> {
+        let opcode = (word >> 12) as usize;
+        let rx = ((word >> 8) & 0xF) as u8;
+        let ry = ((word >> 4) & 0xF) as u8;
+        let low = (word & 0xF) as usize;
+        let byte = (word & 0xFF) as u8;
+
+        match opcode {
+            0x0 if ry == 0 && low > 0 => SHIFT_OPS
+                .get(low - 1)
+                .map(|op| Instruction::Shift { op: *op, rx }),
+            0x1 => Some(Instruction::Store { rx, address: byte }),
+            0x2..=0xB => Some(Instruction::Alu {
+                op: ALU_OPS[opcode - 0x2],
+                rx,
+                operand: Operand::Immediate(byte),
+            }),
+            0xC => JUMP_KINDS.get(rx as usize).map(|kind| Instruction::Jump {
+                kind: *kind,
+                target: Operand::Immediate(byte),
+            }),
+            0xD => match low {
+                0xA => Some(Instruction::LoadIndirect { rx, ry }),
+                0xB => Some(Instruction::StoreIndirect { rx, ry }),
+                _ => ALU_OPS.get(low).map(|op| Instruction::Alu {
+                    op: *op,
+                    rx,
+                    operand: Operand::Register(ry),
+                }),
+            },
+            0xE => Some(Instruction::Load { rx, address: byte }),
+            0xF if low == 0 => match rx {
+                0xA if ry == 0 => Some(Instruction::Rts),
+                0xB if ry == 0 => Some(Instruction::Rti),
+                0xC if ry == 0 => Some(Instruction::Cli),
+                0xD if ry == 0 => Some(Instruction::Sti),
+                0xF if ry == 0 => Some(Instruction::Nop),
+                _ => JUMP_KINDS.get(rx as usize).map(|kind| Instruction::Jump {
+                    kind: *kind,
+                    target: Operand::Register(ry),
+                }),
+            },
+            _ => None,
+        }
+    }
+
+    pub fn cycles(&self) -> u64 {
+        1
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Load { rx, address } => write!(f, "MOV r{}, 0x{:02X}", rx, address),
+            Instruction::Store { rx, address } => write!(f, "MOV 0x{:02X}, r{}", address, rx),
+            Instruction::LoadIndirect { rx, ry } => write!(f, "MOV r{}, (r{})", rx, ry),
+            Instruction::StoreIndirect { rx, ry } => write!(f, "MOV (r{}), r{}", ry, rx),
+            Instruction::Alu {
+                op,
+                rx,
+                operand: Operand::Immediate(value),
+            } => write!(f, "{} r{}, #0x{:02X}", op.mnemonic(), rx, value),
+            Instruction::Alu {
+                op,
+                rx,
+                operand: Operand::Register(ry),
+            } => write!(f, "{} r{}, r{}", op.mnemonic(), rx, ry),
+            Instruction::Shift { op, rx } => write!(f, "{} r{}", op.mnemonic(), rx),
+            Instruction::Jump {
+                kind,
+                target: Operand::Immediate(address),
+            } => write!(f, "{} 0x{:02X}", kind.mnemonic(), address),
+            Instruction::Jump {
+                kind,
+                target: Operand::Register(ry),
+            } => write!(f, "{} (r{})", kind.mnemonic(), ry),
+            Instruction::Rts => write!(f, "RTS"),
+            Instruction::Rti => write!(f, "RTI"),
+            Instruction::Cli => write!(f, "CLI"),
+            Instruction::Sti => write!(f, "STI"),
+            Instruction::Nop => write!(f, "NOP"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::isa::{AluOp, Instruction, JumpKind, Operand, ShiftOp};
+
+    #[test]
+    fn encoding_round_trips() {
+        let instructions = vec![
+            Instruction::Load {
+                rx: 1,
+                address: 0x80,
+            },
+            Instruction::Store {
+                rx: 15,
+                address: 0x7F,
+            },
+            Instruction::LoadIndirect { rx: 2, ry: 3 },
+            Instruction::StoreIndirect { rx: 4, ry: 5 },
+            Instruction::Alu {
+                op: AluOp::Tst,
+                rx: 6,
+                operand: Operand::Immediate(0xAA),
+            },
+            Instruction::Alu {
+                op: AluOp::Mov,
+                rx: 7,
+                operand: Operand::Register(8),
+            },
+            Instruction::Shift {
+                op: ShiftOp::Swp,
+                rx: 9,
+            },
+            Instruction::Load { rx: 0, address: 0 },
+            Instruction::Shift {
+                op: ShiftOp::Sl0,
+                rx: 0,
+            },
+            Instruction::Jump {
+                kind: JumpKind::Jsr,
+                target: Operand::Immediate(0x42),
+            },
+            Instruction::Jump {
+                kind: JumpKind::Jnv,
+                target: Operand::Register(10),
+            },
+            Instruction::Rts,
+            Instruction::Rti,
+            Instruction::Cli,
+            Instruction::Sti,
+            Instruction::Nop,
+        ];
+
+        for instruction in instructions {
+            assert_eq!(Instruction::decode(instruction.encode()), Some(instruction));
+        }
+    }
+
+    #[test]
+    fn known_encodings() {
+        let mov = Instruction::Alu {
+            op: AluOp::Mov,
+            rx: 0,
+            operand: Operand::Immediate(100),
+        };
+        assert_eq!(mov.encode(), 0x2064);
+        assert_eq!(mov.to_string(), "MOV r0, #0x64");
+        assert_eq!(
+            Instruction::decode(0xC005).map(|i| i.to_string()),
+            Some("JMP 0x05".to_string())
+        );
+        assert_eq!(Instruction::decode(0x0000), None);
+        assert_eq!(Instruction::decode(0x000F), None);
+        let load = Instruction::Load { rx: 0, address: 0 };
+        assert_eq!(load.encode(), 0xE000);
+        assert_eq!(Instruction::decode(0xE000), Some(load));
+        let shift = Instruction::Shift {
+            op: ShiftOp::Sl0,
+            rx: 0,
+        };
+        assert_eq!(shift.encode(), 0x0001);
+        assert_eq!(Instruction::decode(0xFE00), None);
+    }
+}
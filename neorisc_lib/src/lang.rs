@@ -2,22 +2,126 @@ use crate::error::LangParseError;
 use regex::Regex;
 
 lazy_static! {
+    static ref REGISTER_REGEX: Regex = Regex::new(r"^r[0-9]+$").unwrap();
     static ref LABEL_REGEX: Regex = Regex::new(r"^[A-Za-z_0-9]+$").unwrap();
+    static ref LOCAL_LABEL_REGEX: Regex = Regex::new(r"^[.@][A-Za-z_0-9]+$").unwrap();
+    static ref ANONYMOUS_LABEL_REGEX: Regex = Regex::new(r"^(\++|-+)$").unwrap();
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum LangCommand {
     DEF,
     CODE,
     DATA,
     DB,
     ORG,
+    MACRO,
+    ENDM,
+    IF,
+    IFDEF,
+    IFNDEF,
+    ELSE,
+    ENDIF,
+    REPT,
+    ENDR,
+    TIMES,
+    DS,
+    FILL,
+    ALIGN,
     ADD,
     JMP,
     MOV,
+    ADC,
+    SUB,
+    SBC,
+    CMP,
+    AND,
+    OR,
+    XOR,
+    TST,
+    SL0,
+    SL1,
+    SR0,
+    SR1,
+    ASR,
+    ROL,
+    ROR,
+    RLC,
+    RRC,
+    SWP,
+    JZ,
+    JNZ,
+    JC,
+    JNC,
+    JN,
+    JNN,
+    JV,
+    JNV,
+    JSR,
+    RTS,
+    RTI,
+    CLI,
+    STI,
+    NOP,
 }
 
 impl LangCommand {
+    pub const ALL: [LangCommand; 53] = [
+        LangCommand::DEF,
+        LangCommand::CODE,
+        LangCommand::DATA,
+        LangCommand::DB,
+        LangCommand::ORG,
+        LangCommand::MACRO,
+        LangCommand::ENDM,
+        LangCommand::IF,
+        LangCommand::IFDEF,
+        LangCommand::IFNDEF,
+        LangCommand::ELSE,
+        LangCommand::ENDIF,
+        LangCommand::REPT,
+        LangCommand::ENDR,
+        LangCommand::TIMES,
+        LangCommand::DS,
+        LangCommand::FILL,
+        LangCommand::ALIGN,
+        LangCommand::ADD,
+        LangCommand::JMP,
+        LangCommand::MOV,
+        LangCommand::ADC,
+        LangCommand::SUB,
+        LangCommand::SBC,
+        LangCommand::CMP,
+        LangCommand::AND,
+        LangCommand::OR,
+        LangCommand::XOR,
+        LangCommand::TST,
+        LangCommand::SL0,
+        LangCommand::SL1,
+        LangCommand::SR0,
+        LangCommand::SR1,
+        LangCommand::ASR,
+        LangCommand::ROL,
+        LangCommand::ROR,
+        LangCommand::RLC,
+        LangCommand::RRC,
+        LangCommand::SWP,
+        LangCommand::JZ,
+        LangCommand::JNZ,
+        LangCommand::JC,
+        LangCommand::JNC,
+        LangCommand::JN,
+        LangCommand::JNN,
+        LangCommand::JV,
+        LangCommand::JNV,
+        LangCommand::JSR,
+        LangCommand::RTS,
+        LangCommand::RTI,
+        LangCommand::CLI,
+        LangCommand::STI,
+        LangCommand::NOP,
+    ];
+
     pub fn from_string(ss: &str) -> Option<LangCommand> {
         match ss {
             "DEF" => Some(LangCommand::DEF),
@@ -25,18 +129,89 @@ impl LangCommand {
             "DATA" => Some(LangCommand::DATA),
             "DB" => Some(LangCommand::DB),
             "ORG" => Some(LangCommand::ORG),
+            "MACRO" => Some(LangCommand::MACRO),
+            "ENDM" => Some(LangCommand::ENDM),
+            "IF" => Some(LangCommand::IF),
+            "IFDEF" => Some(LangCommand::IFDEF),
+            "IFNDEF" => Some(LangCommand::IFNDEF),
+            "ELSE" => Some(LangCommand::ELSE),
+            "ENDIF" => Some(LangCommand::ENDIF),
+            "REPT" => Some(LangCommand::REPT),
+            "ENDR" => Some(LangCommand::ENDR),
+            "TIMES" => Some(LangCommand::TIMES),
+            "DS" => Some(LangCommand::DS),
+            "FILL" => Some(LangCommand::FILL),
+            "ALIGN" => Some(LangCommand::ALIGN),
             "ADD" => Some(LangCommand::ADD),
             "JMP" => Some(LangCommand::JMP),
             "MOV" => Some(LangCommand::MOV),
+            "ADC" => Some(LangCommand::ADC),
+            "SUB" => Some(LangCommand::SUB),
+            "SBC" => Some(LangCommand::SBC),
+            "CMP" => Some(LangCommand::CMP),
+            "AND" => Some(LangCommand::AND),
+            "OR" => Some(LangCommand::OR),
+            "XOR" => Some(LangCommand::XOR),
+            "TST" => Some(LangCommand::TST),
+            "SL0" => Some(LangCommand::SL0),
+            "SL1" => Some(LangCommand::SL1),
+            "SR0" => Some(LangCommand::SR0),
+            "SR1" => Some(LangCommand::SR1),
+            "ASR" => Some(LangCommand::ASR),
+            "ROL" => Some(LangCommand::ROL),
+            "ROR" => Some(LangCommand::ROR),
+            "RLC" => Some(LangCommand::RLC),
+            "RRC" => Some(LangCommand::RRC),
+            "SWP" => Some(LangCommand::SWP),
+            "JZ" => Some(LangCommand::JZ),
+            "JNZ" => Some(LangCommand::JNZ),
+            "JC" => Some(LangCommand::JC),
+            "JNC" => Some(LangCommand::JNC),
+            "JN" => Some(LangCommand::JN),
+            "JNN" => Some(LangCommand::JNN),
+            "JV" => Some(LangCommand::JV),
+            "JNV" => Some(LangCommand::JNV),
+            "JSR" => Some(LangCommand::JSR),
+            "RTS" => Some(LangCommand::RTS),
+            "RTI" => Some(LangCommand::RTI),
+            "CLI" => Some(LangCommand::CLI),
+            "STI" => Some(LangCommand::STI),
+            "NOP" => Some(LangCommand::NOP),
             _ => None,
         }
     }
+
+    pub fn is_instruction(&self) -> bool {
+        !matches!(
+            self,
+            LangCommand::DEF
+                | LangCommand::CODE
+                | LangCommand::DATA
+                | LangCommand::DB
+                | LangCommand::ORG
+                | LangCommand::MACRO
+                | LangCommand::ENDM
+                | LangCommand::IF
+                | LangCommand::IFDEF
+                | LangCommand::IFNDEF
+                | LangCommand::ELSE
+                | LangCommand::ENDIF
+                | LangCommand::REPT
+                | LangCommand::ENDR
+                | LangCommand::TIMES
+                | LangCommand::DS
+                | LangCommand::FILL
+                | LangCommand::ALIGN
+        )
+    }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum LangLiteral {
     Register(u8),
+    Indirect(u8),
     Constant(u8),
+    ConstantSymbol(String),
     Address(u8),
     Char(char),
     String(String),
@@ -45,17 +220,31 @@ pub enum LangLiteral {
 
 impl LangLiteral {
     pub fn validate_symbol_name(string: &str) -> Result<&str, LangParseError> {
-        if string == "" {
+        if string.is_empty() {
             Err(LangParseError::LabelNameExpected)
-        } else if !LABEL_REGEX.is_match(string) {
-            Err(LangParseError::InvalidSymbolName)
-        } else if LangCommand::from_string(string).is_some() {
+        } else if !LABEL_REGEX.is_match(string) || LangCommand::from_string(string).is_some() {
             Err(LangParseError::InvalidSymbolName)
         } else {
             Ok(string)
         }
     }
 
+    pub fn is_local_label(string: &str) -> bool {
+        LOCAL_LABEL_REGEX.is_match(string)
+    }
+
+    pub fn is_anonymous_label(string: &str) -> bool {
+        ANONYMOUS_LABEL_REGEX.is_match(string)
+    }
+
+    pub fn validate_label_name(string: &str) -> Result<&str, LangParseError> {
+        if Self::is_local_label(string) || Self::is_anonymous_label(string) {
+            Ok(string)
+        } else {
+            Self::validate_symbol_name(string)
+        }
+    }
+
     fn parse_radix_to_try_from_str(ss: &str) -> u32 {
         if ss.starts_with("0x") {
             16
@@ -123,31 +312,55 @@ impl LangLiteral {
             (0, 255),
             0,
             true,
-            LangParseError::InvalidConstantArgument,
-            LangParseError::ConstantOutOfBounds,
+            LangParseError::InvalidAddressArgument,
+            LangParseError::AddressOutOfBounds,
         )?;
         Ok(LangLiteral::Address(byte))
     }
 
     fn parse_potential_char(ss: &str) -> Result<LangLiteral, LangParseError> {
-        if ss.len() != 3 && ss.ends_with('\'') {
-            let char = ss
-                .chars()
-                .next()
-                .ok_or(LangParseError::InvalidCharArgument)?;
-            if char.len_utf8() != 1 {
-                Err(LangParseError::CharOutOfBounds)
-            } else {
-                Ok(LangLiteral::Char(char))
+        let inner = ss
+            .strip_prefix('\'')
+            .and_then(|inner| inner.strip_suffix('\''))
+            .ok_or(LangParseError::InvalidCharArgument)?;
+        let mut chars = inner.chars();
+        match (chars.next(), chars.next()) {
+            (Some(char), None) if char.is_ascii() => Ok(LangLiteral::Char(char)),
+            (Some(_), None) => Err(LangParseError::CharOutOfBounds),
+            _ => Err(LangParseError::InvalidCharArgument),
+        }
+    }
+
+    fn parse_potential_immediate(ss: &str) -> Result<LangLiteral, LangParseError> {
+        let value = &ss[1..];
+        if value.starts_with('\'') {
+            match Self::parse_potential_char(value)? {
+                LangLiteral::Char(char) => Ok(LangLiteral::Constant(char as u8)),
+                _ => Err(LangParseError::InvalidCharArgument),
             }
+        } else if Self::string_starts_with_number(value) {
+            Self::parse_potential_constant(ss)
         } else {
-            Err(LangParseError::InvalidCharArgument)
+            LangLiteral::validate_label_name(value)
+                .map(|name| LangLiteral::ConstantSymbol(name.to_string()))
+                .map_err(|_| LangParseError::InvalidConstantArgument)
+        }
+    }
+
+    fn parse_potential_indirect(ss: &str) -> Result<LangLiteral, LangParseError> {
+        let inner = ss
+            .strip_prefix('(')
+            .and_then(|inner| inner.strip_suffix(')'))
+            .ok_or(LangParseError::InvalidRegisterArgument)?;
+        match Self::parse_potential_register(inner)? {
+            LangLiteral::Register(reg) => Ok(LangLiteral::Indirect(reg)),
+            _ => Err(LangParseError::InvalidRegisterArgument),
         }
     }
 
     fn string_starts_with_number(ss: &str) -> bool {
         if let Some(chr) = ss.chars().next() {
-            chr.is_digit(10)
+            chr.is_ascii_digit()
         } else {
             false
         }
@@ -155,18 +368,20 @@ impl LangLiteral {
 
     fn parse_potential_symbol(ss: &str) -> Result<LangLiteral, LangParseError> {
         Ok(LangLiteral::Symbol(
-            Self::validate_symbol_name(ss)?.to_string(),
+            Self::validate_label_name(ss)?.to_string(),
         ))
     }
 
     pub fn from_string(ss: &str) -> Result<LangLiteral, LangParseError> {
-        if ss.starts_with('r') {
+        if REGISTER_REGEX.is_match(ss) {
             Self::parse_potential_register(ss)
+        } else if ss.starts_with('(') {
+            Self::parse_potential_indirect(ss)
         } else if ss.starts_with('#') {
-            Self::parse_potential_constant(ss)
+            Self::parse_potential_immediate(ss)
         } else if ss.starts_with('\"') {
             Ok(LangLiteral::String(ss.to_string()))
-        } else if ss.starts_with("#'") {
+        } else if ss.starts_with('\'') {
             Self::parse_potential_char(ss)
         } else if Self::string_starts_with_number(ss) {
             Self::parse_potential_address(ss)
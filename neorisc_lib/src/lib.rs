@@ -2,7 +2,17 @@
 extern crate lazy_static;
 
 pub mod compiler;
+pub mod dap;
+pub mod debugger;
 pub mod error;
+pub mod formatter;
+pub mod framing;
+pub mod gdb;
 pub mod interpreter;
+pub mod isa;
 pub mod lang;
+pub mod lsp;
 pub mod parser;
+pub mod preprocessor;
+pub mod program;
+pub mod testing;
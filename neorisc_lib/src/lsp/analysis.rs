@@ -0,0 +1,276 @@
+use crate::compiler::diagnostics::Diagnostic;
+use crate::compiler::labels::qualify_local_label;
+use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+use crate::lang::{LangCommand, LangLiteral};
+use crate::lsp::docs::command_documentation;
+use crate::parser::highlight::{highlight_line, HighlightKind, HighlightSpan};
+use crate::program::{ProgramSection, RiscProgram};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OccurrenceKind {
+    Label,
+    Definition,
+    Macro,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SymbolOccurrence {
+    pub name: String,
+    pub line: usize,
+    pub span: Range<usize>,
+    pub is_definition: bool,
+    pub kind: OccurrenceKind,
+}
+
+pub struct DocumentAnalysis {
+    pub lines: Vec<String>,
+    pub highlights: Vec<Vec<HighlightSpan>>,
+    pub occurrences: Vec<SymbolOccurrence>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub program: Option<RiscProgram>,
+}
+
+struct IndexContext {
+    scope: String,
+    occurrences: Vec<SymbolOccurrence>,
+}
+
+impl IndexContext {
+    fn qualify(&self, name: &str) -> String {
+        if LangLiteral::is_local_label(name) {
+            qualify_local_label(&self.scope, name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn push(
+        &mut self,
+        line: usize,
+        span: Range<usize>,
+        name: &str,
+        kind: OccurrenceKind,
+        def: bool,
+    ) {
+        if LangLiteral::is_anonymous_label(name) || name.starts_with("%%") {
+            return;
+        }
+        self.occurrences.push(SymbolOccurrence {
+            name: self.qualify(name),
+            line,
+            span,
+            is_definition: def,
+            kind,
+        });
+    }
+}
+
+fn index_line(ctx: &mut IndexContext, line: usize, spans: &[HighlightSpan]) {
+    let command = spans
+        .iter()
+        .find(|span| span.kind == HighlightKind::Directive)
+        .and_then(|span| LangCommand::from_string(&span.text));
+    let mut first_argument = true;
+
+    for span in spans.iter() {
+        match span.kind {
+            HighlightKind::Label => {
+                if !LangLiteral::is_local_label(&span.text)
+                    && !LangLiteral::is_anonymous_label(&span.text)
+                {
+                    ctx.scope = span.text.clone();
+                }
+                ctx.push(
+                    line,
+                    span.span.clone(),
+                    &span.text,
+                    OccurrenceKind::Label,
+                    true,
+                );
+            }
+            HighlightKind::Macro => ctx.push(
+                line,
+                span.span.clone(),
+                &span.text,
+                OccurrenceKind::Macro,
+                false,
+            ),
+            HighlightKind::Symbol => {
+                let (kind, def) = match command {
+                    Some(LangCommand::DEF) if first_argument => (OccurrenceKind::Definition, true),
+                    Some(LangCommand::MACRO) if first_argument => (OccurrenceKind::Macro, true),
+                    Some(LangCommand::MACRO) => continue,
+                    _ => (OccurrenceKind::Label, false),
+                };
+                ctx.push(line, span.span.clone(), &span.text, kind, def);
+            }
+            HighlightKind::Immediate => {
+                let name = &span.text[1..];
+                if LangLiteral::validate_label_name(name).is_ok() {
+                    let range = span.span.start + 1..span.span.end;
+                    ctx.push(line, range, name, OccurrenceKind::Label, false);
+                }
+            }
+            _ => {}
+        }
+        if !matches!(
+            span.kind,
+            HighlightKind::Label
+                | HighlightKind::Punctuation
+                | HighlightKind::Directive
+                | HighlightKind::Instruction
+                | HighlightKind::Macro
+        ) {
+            first_argument = false;
+        }
+    }
+}
+
+impl DocumentAnalysis {
+    pub fn new(text: &str) -> Self {
+        let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+        let highlights: Vec<Vec<HighlightSpan>> =
+            lines.iter().map(|line| highlight_line(line)).collect();
+
+        let mut ctx = IndexContext {
+            scope: String::new(),
+            occurrences: vec![],
+        };
+        for (line, spans) in highlights.iter().enumerate() {
+            index_line(&mut ctx, line, spans);
+        }
+
+        let mut compiler = RiscCompiler::new(text.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        let diagnostics = compiler.diagnostics();
+        let program = compiler.program().cloned();
+
+        Self {
+            lines,
+            highlights,
+            occurrences: ctx.occurrences,
+            diagnostics,
+            program,
+        }
+    }
+
+    pub fn span_at(&self, line: usize, offset: usize) -> Option<&HighlightSpan> {
+        self.highlights
+            .get(line)?
+            .iter()
+            .find(|span| span.span.start <= offset && offset <= span.span.end)
+    }
+
+    pub fn occurrence_at(&self, line: usize, offset: usize) -> Option<&SymbolOccurrence> {
+        self.occurrences.iter().find(|occurrence| {
+            occurrence.line == line
+                && occurrence.span.start <= offset
+                && offset <= occurrence.span.end
+        })
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&SymbolOccurrence> {
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.is_definition && occurrence.name == name)
+    }
+
+    pub fn references(&self, name: &str, include_declaration: bool) -> Vec<&SymbolOccurrence> {
+        self.occurrences
+            .iter()
+            .filter(|occurrence| {
+                occurrence.name == name && (include_declaration || !occurrence.is_definition)
+            })
+            .collect()
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &SymbolOccurrence> {
+        self.occurrences
+            .iter()
+            .filter(|occurrence| occurrence.is_definition)
+    }
+
+    fn encoded_words(&self, line: usize) -> Vec<String> {
+        let program = match &self.program {
+            Some(program) => program,
+            None => return vec![],
+        };
+
+        program
+            .source_map
+            .iter()
+            .filter(|(_, source_line)| **source_line == line)
+            .map(|(address, _)| format!("0x{:02X}: {:04X}", address, program.code[*address]))
+            .collect()
+    }
+
+    fn symbol_value(&self, name: &str) -> Option<String> {
+        let symbol = self.program.as_ref()?.symbols.get(name)?;
+        Some(match symbol.section {
+            ProgramSection::Code => format!("code address 0x{:02X}", symbol.value),
+            ProgramSection::Data => format!("data address 0x{:02X}", symbol.value),
+            ProgramSection::Constant => {
+                format!("constant 0x{:02X} ({})", symbol.value, symbol.value)
+            }
+        })
+    }
+
+    pub fn hover(&self, line: usize, offset: usize) -> Option<String> {
+        if let Some(occurrence) = self.occurrence_at(line, offset) {
+            let value = self
+                .symbol_value(&occurrence.name)
+                .unwrap_or_else(|| "unresolved".to_string());
+            return Some(format!("`{}`: {}", occurrence.name, value));
+        }
+
+        let span = self.span_at(line, offset)?;
+        match span.kind {
+            HighlightKind::Instruction | HighlightKind::Directive => {
+                let command = LangCommand::from_string(&span.text)?;
+                let mut text = format!("```\n{}\n```", command_documentation(&command));
+                let words = self.encoded_words(line);
+                if !words.is_empty() {
+                    text.push_str(&format!("\n\nEncoded: `{}`", words.join("`, `")));
+                }
+                Some(text)
+            }
+            HighlightKind::Register => Some(format!("Register `{}`", span.text)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::analysis::{DocumentAnalysis, OccurrenceKind};
+
+    const SOURCE: &str = "DEF LIMIT 3\nmain: MOV r0, #LIMIT\n.loop: SUB r0, #1\nJNZ .loop\nJSR main\nMACRO wait\nNOP\nENDM\nwait";
+
+    #[test]
+    fn symbols_indexed_with_scopes() {
+        let analysis = DocumentAnalysis::new(SOURCE);
+        let limit = analysis.definition("LIMIT").expect("Definition missing");
+        assert_eq!(limit.kind, OccurrenceKind::Definition);
+        assert_eq!(limit.line, 0);
+        assert_eq!(analysis.references("LIMIT", false)[0].span, 15..20);
+        assert_eq!(analysis.references("main.loop", true).len(), 2);
+        let at = analysis.occurrence_at(3, 5).expect("Occurrence missing");
+        assert_eq!(at.name, "main.loop");
+        assert_eq!(analysis.definition("wait").map(|def| def.line), Some(5));
+        assert_eq!(analysis.references("wait", false)[0].line, 8);
+    }
+
+    #[test]
+    fn hover_shows_docs_and_encoding() {
+        let analysis = DocumentAnalysis::new(SOURCE);
+        let hover = analysis.hover(1, 7).expect("Hover missing");
+        assert!(hover.contains("Copies data"));
+        assert!(hover.contains("0x00: 2003"));
+        assert_eq!(
+            analysis.hover(4, 5).as_deref(),
+            Some("`main`: code address 0x00")
+        );
+        assert!(analysis.diagnostics.is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::lang::LangCommand;
+
+pub fn command_documentation(command: &LangCommand) -> &'static str {
+    match command {
+        LangCommand::DEF => "DEF name value\n\nDefines a constant symbol.",
+        LangCommand::CODE => "CODE\n\nSwitches to the code section.",
+        LangCommand::DATA => "DATA\n\nSwitches to the data section.",
+        LangCommand::DB => "DB value, ...\n\nPlaces bytes, chars and strings in data memory.",
+        LangCommand::ORG => "ORG address\n\nMoves the location counter of the current section.",
+        LangCommand::MACRO => "MACRO name param, ...\n\nStarts a macro definition, closed by ENDM.",
+        LangCommand::ENDM => "ENDM\n\nEnds a macro definition.",
+        LangCommand::IF => "IF condition\n\nAssembles the block if the condition is non-zero.",
+        LangCommand::IFDEF => "IFDEF name\n\nAssembles the block if the symbol is defined.",
+        LangCommand::IFNDEF => "IFNDEF name\n\nAssembles the block if the symbol is not defined.",
+        LangCommand::ELSE => "ELSE\n\nStarts the alternative block of a conditional.",
+        LangCommand::ENDIF => "ENDIF\n\nEnds a conditional block.",
+        LangCommand::REPT => "REPT count\n\nRepeats the block up to ENDR count times.",
+        LangCommand::ENDR => "ENDR\n\nEnds a REPT block.",
+        LangCommand::TIMES => "TIMES count line\n\nRepeats a single line count times.",
+        LangCommand::DS => "DS count\n\nReserves count zeroed bytes of data memory.",
+        LangCommand::FILL => "FILL count, value\n\nPlaces count copies of value in data memory.",
+        LangCommand::ALIGN => "ALIGN n\n\nPads the current section to a multiple of n.",
+        LangCommand::MOV => {
+            "MOV rX, #imm | rX, rY | rX, addr | addr, rX | rX, (rY) | (rY), rX\n\nCopies data between registers and memory. Immediate and register forms set Z and N."
+        }
+        LangCommand::ADD => "ADD rX, #imm | rX, rY\n\nrX = rX + op. Sets Z, C, N, V.",
+        LangCommand::ADC => "ADC rX, #imm | rX, rY\n\nrX = rX + op + C. Sets Z, C, N, V.",
+        LangCommand::SUB => "SUB rX, #imm | rX, rY\n\nrX = rX - op. C is the borrow. Sets Z, C, N, V.",
+        LangCommand::SBC => "SBC rX, #imm | rX, rY\n\nrX = rX - op - C. Sets Z, C, N, V.",
+        LangCommand::CMP => "CMP rX, #imm | rX, rY\n\nComputes rX - op for the flags only.",
+        LangCommand::AND => "AND rX, #imm | rX, rY\n\nrX = rX & op. Sets Z, N.",
+        LangCommand::OR => "OR rX, #imm | rX, rY\n\nrX = rX | op. Sets Z, N.",
+        LangCommand::XOR => "XOR rX, #imm | rX, rY\n\nrX = rX ^ op. Sets Z, N.",
+        LangCommand::TST => "TST rX, #imm | rX, rY\n\nComputes rX & op for the flags only.",
+        LangCommand::SL0 => "SL0 rX\n\nShifts left, shifting in 0. C is the bit shifted out.",
+        LangCommand::SL1 => "SL1 rX\n\nShifts left, shifting in 1. C is the bit shifted out.",
+        LangCommand::SR0 => "SR0 rX\n\nShifts right, shifting in 0. C is the bit shifted out.",
+        LangCommand::SR1 => "SR1 rX\n\nShifts right, shifting in 1. C is the bit shifted out.",
+        LangCommand::ASR => "ASR rX\n\nArithmetic shift right, keeps the sign bit.",
+        LangCommand::ROL => "ROL rX\n\nRotates left.",
+        LangCommand::ROR => "ROR rX\n\nRotates right.",
+        LangCommand::RLC => "RLC rX\n\nRotates left through the carry.",
+        LangCommand::RRC => "RRC rX\n\nRotates right through the carry.",
+        LangCommand::SWP => "SWP rX\n\nSwaps the two nibbles.",
+        LangCommand::JMP => "JMP addr | (rY)\n\nJumps unconditionally. A jump to itself halts the emulator.",
+        LangCommand::JZ => "JZ addr | (rY)\n\nJumps if Z is set.",
+        LangCommand::JNZ => "JNZ addr | (rY)\n\nJumps if Z is clear.",
+        LangCommand::JC => "JC addr | (rY)\n\nJumps if C is set.",
+        LangCommand::JNC => "JNC addr | (rY)\n\nJumps if C is clear.",
+        LangCommand::JN => "JN addr | (rY)\n\nJumps if N is set.",
+        LangCommand::JNN => "JNN addr | (rY)\n\nJumps if N is clear.",
+        LangCommand::JV => "JV addr | (rY)\n\nJumps if V is set.",
+        LangCommand::JNV => "JNV addr | (rY)\n\nJumps if V is clear.",
+        LangCommand::JSR => "JSR addr | (rY)\n\nCalls a subroutine, pushing the return address and flags.",
+        LangCommand::RTS => "RTS\n\nReturns from a subroutine.",
+        LangCommand::RTI => "RTI\n\nReturns from an interrupt, restoring the flags.",
+        LangCommand::CLI => "CLI\n\nDisables interrupts.",
+        LangCommand::STI => "STI\n\nEnables interrupts.",
+        LangCommand::NOP => "NOP\n\nDoes nothing for one cycle.",
+    }
+}
@@ -0,0 +1,452 @@
+pub mod analysis;
+pub mod docs;
+
+use crate::framing::{read_message, write_message};
+use crate::lang::LangCommand;
+use crate::lsp::analysis::{DocumentAnalysis, OccurrenceKind, SymbolOccurrence};
+use crate::parser::highlight::HighlightKind;
+use crate::program::ProgramSection;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+const TOKEN_TYPES: [&str; 8] = [
+    "function",
+    "keyword",
+    "macro",
+    "parameter",
+    "number",
+    "string",
+    "variable",
+    "comment",
+];
+
+const COMPLETION_FUNCTION: u64 = 3;
+const COMPLETION_VARIABLE: u64 = 6;
+const COMPLETION_KEYWORD: u64 = 14;
+const COMPLETION_REFERENCE: u64 = 18;
+const COMPLETION_CONSTANT: u64 = 21;
+
+const SYMBOL_FUNCTION: u64 = 12;
+const SYMBOL_VARIABLE: u64 = 13;
+const SYMBOL_CONSTANT: u64 = 14;
+
+fn token_type(kind: HighlightKind) -> Option<usize> {
+    match kind {
+        HighlightKind::Label => Some(0),
+        HighlightKind::Instruction | HighlightKind::Directive => Some(1),
+        HighlightKind::Macro => Some(2),
+        HighlightKind::Register => Some(3),
+        HighlightKind::Immediate | HighlightKind::Number => Some(4),
+        HighlightKind::String => Some(5),
+        HighlightKind::Symbol => Some(6),
+        HighlightKind::Comment => Some(7),
+        HighlightKind::Punctuation | HighlightKind::Invalid => None,
+    }
+}
+
+fn utf16_column(line: &str, offset: usize) -> usize {
+    line[..offset.min(line.len())].encode_utf16().count()
+}
+
+fn byte_offset(line: &str, column: usize) -> usize {
+    let mut units = 0;
+    for (offset, chr) in line.char_indices() {
+        if units >= column {
+            return offset;
+        }
+        units += chr.len_utf16();
+    }
+    line.len()
+}
+
+#[derive(Default)]
+pub struct LanguageServer {
+    documents: HashMap<String, DocumentAnalysis>,
+    shutdown: bool,
+    finished: bool,
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn range(&self, document: &DocumentAnalysis, line: usize, span: &Range<usize>) -> Value {
+        let text = document.lines.get(line).map(String::as_str).unwrap_or("");
+        json!({
+            "start": { "line": line, "character": utf16_column(text, span.start) },
+            "end": { "line": line, "character": utf16_column(text, span.end) },
+        })
+    }
+
+    fn location(
+        &self,
+        uri: &str,
+        document: &DocumentAnalysis,
+        occurrence: &SymbolOccurrence,
+    ) -> Value {
+        json!({ "uri": uri, "range": self.range(document, occurrence.line, &occurrence.span) })
+    }
+
+    fn position<'a>(
+        &'a self,
+        params: &'a Value,
+    ) -> Option<(&'a str, &'a DocumentAnalysis, usize, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+        let line = params["position"]["line"].as_u64()? as usize;
+        let column = params["position"]["character"].as_u64()? as usize;
+        let text = document.lines.get(line)?;
+        Some((uri, document, line, byte_offset(text, column)))
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let diagnostics: Vec<Value> = self
+            .documents
+            .get(uri)
+            .map(|document| {
+                document
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        let line = diagnostic.line.saturating_sub(1);
+                        let length = document.lines.get(line).map_or(0, |text| text.len());
+                        json!({
+                            "range": self.range(document, line, &(0..length)),
+                            "severity": 1,
+                            "source": "neorisc",
+                            "message": diagnostic.message,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let (uri, document, line, offset) = match self.position(params) {
+            Some(position) => position,
+            None => return Value::Null,
+        };
+        document
+            .occurrence_at(line, offset)
+            .and_then(|occurrence| document.definition(&occurrence.name))
+            .map_or(Value::Null, |definition| {
+                self.location(uri, document, definition)
+            })
+    }
+
+    fn references(&self, params: &Value) -> Value {
+        let (uri, document, line, offset) = match self.position(params) {
+            Some(position) => position,
+            None => return Value::Null,
+        };
+        let include_declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+        let occurrence = match document.occurrence_at(line, offset) {
+            Some(occurrence) => occurrence,
+            None => return json!([]),
+        };
+        let locations: Vec<Value> = document
+            .references(&occurrence.name, include_declaration)
+            .into_iter()
+            .map(|reference| self.location(uri, document, reference))
+            .collect();
+        json!(locations)
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let (_, document, line, offset) = match self.position(params) {
+            Some(position) => position,
+            None => return Value::Null,
+        };
+        document.hover(line, offset).map_or(
+            Value::Null,
+            |text| json!({ "contents": { "kind": "markdown", "value": text } }),
+        )
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let mut items: Vec<Value> = LangCommand::ALL
+            .iter()
+            .map(|command| {
+                json!({
+                    "label": format!("{:?}", command),
+                    "kind": COMPLETION_KEYWORD,
+                    "documentation": docs::command_documentation(command),
+                })
+            })
+            .collect();
+        items.extend(
+            (0..16).map(|reg| json!({ "label": format!("r{}", reg), "kind": COMPLETION_VARIABLE })),
+        );
+
+        let document = params["textDocument"]["uri"]
+            .as_str()
+            .and_then(|uri| self.documents.get(uri));
+        if let Some(document) = document {
+            for definition in document.definitions() {
+                let kind = match definition.kind {
+                    OccurrenceKind::Label => COMPLETION_REFERENCE,
+                    OccurrenceKind::Definition => COMPLETION_CONSTANT,
+                    OccurrenceKind::Macro => COMPLETION_FUNCTION,
+                };
+                items.push(json!({ "label": definition.name, "kind": kind }));
+            }
+        }
+
+        json!(items)
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return json!([]),
+        };
+
+        let symbols: Vec<Value> = document
+            .definitions()
+            .map(|definition| {
+                let kind = match definition.kind {
+                    OccurrenceKind::Definition => SYMBOL_CONSTANT,
+                    OccurrenceKind::Macro => SYMBOL_FUNCTION,
+                    OccurrenceKind::Label => match document
+                        .program
+                        .as_ref()
+                        .and_then(|program| program.symbols.get(&definition.name))
+                    {
+                        Some(symbol) if symbol.section == ProgramSection::Data => SYMBOL_VARIABLE,
+                        _ => SYMBOL_FUNCTION,
+                    },
+                };
+                let line_length = document.lines[definition.line].len();
+                json!({
+                    "name": definition.name,
+                    "kind": kind,
+                    "range": self.range(document, definition.line, &(0..line_length)),
+                    "selectionRange": self.range(document, definition.line, &definition.span),
+                })
+            })
+            .collect();
+        json!(symbols)
+    }
+
+    fn semantic_tokens(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return json!({ "data": [] }),
+        };
+
+        let mut data = vec![];
+        let (mut last_line, mut last_start) = (0, 0);
+        for (line, spans) in document.highlights.iter().enumerate() {
+            let text = &document.lines[line];
+            for span in spans.iter() {
+                let token = match token_type(span.kind) {
+                    Some(token) => token,
+                    None => continue,
+                };
+                let start = utf16_column(text, span.span.start);
+                let length = utf16_column(text, span.span.end) - start;
+                let delta_start = if line == last_line {
+                    start - last_start
+                } else {
+                    start
+                };
+                data.extend_from_slice(&[line - last_line, delta_start, length, token, 0]);
+                last_line = line;
+                last_start = start;
+            }
+        }
+
+        json!({ "data": data })
+    }
+
+    fn open_document(&mut self, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = params["textDocument"]["text"]
+            .as_str()
+            .or_else(|| {
+                params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+            })
+            .unwrap_or_default();
+        self.documents
+            .insert(uri.to_string(), DocumentAnalysis::new(text));
+        vec![self.publish_diagnostics(uri)]
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        match method {
+            "textDocument/didOpen" | "textDocument/didChange" => self.open_document(params),
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                vec![self.publish_diagnostics(uri)]
+            }
+            "exit" => {
+                self.finished = true;
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        if self.shutdown {
+            return Err((-32600, "Server is shut down".to_string()));
+        }
+
+        Ok(match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                    "semanticTokensProvider": {
+                        "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": [] },
+                        "full": true,
+                    },
+                },
+                "serverInfo": { "name": "neorisc" },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            _ => return Err((-32601, format!("Unsupported method: {}", method))),
+        })
+    }
+
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notification(method, params),
+        };
+
+        match self.request(method, params) {
+            Ok(result) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+            Err((code, message)) => vec![json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            })],
+        }
+    }
+
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        while !self.finished {
+            let message = match read_message(&mut input)? {
+                Some(message) => message,
+                None => break,
+            };
+            for reply in self.handle(&message) {
+                write_message(&mut output, &reply)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::LanguageServer;
+    use serde_json::{json, Value};
+
+    const URI: &str = "file:///test.s";
+
+    fn request(id: i64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn at(line: usize, character: usize) -> Value {
+        json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+    }
+
+    #[test]
+    fn requests_answered_for_open_document() {
+        let mut server = LanguageServer::new();
+        let init = server.handle(&request(1, "initialize", json!({})));
+        assert_eq!(init[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        let published = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "text": "main: MOV r0, #1\nJMP main\nFOO r1" } },
+        }));
+        let diagnostics = &published[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().map(Vec::len), Some(1));
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 2);
+
+        let definition = server.handle(&request(2, "textDocument/definition", at(1, 5)));
+        assert_eq!(definition[0]["result"]["range"]["start"]["line"], 0);
+        assert_eq!(definition[0]["result"]["range"]["end"]["character"], 4);
+
+        let mut params = at(0, 1);
+        params["context"] = json!({ "includeDeclaration": false });
+        let references = server.handle(&request(3, "textDocument/references", params));
+        assert_eq!(references[0]["result"][0]["range"]["start"]["line"], 1);
+
+        let completion = server.handle(&request(4, "textDocument/completion", at(1, 0)));
+        let labels: Vec<&str> = completion[0]["result"]
+            .as_array()
+            .expect("Completion missing")
+            .iter()
+            .filter_map(|item| item["label"].as_str())
+            .collect();
+        assert!(labels.contains(&"MOV") && labels.contains(&"r15") && labels.contains(&"main"));
+
+        let symbols = server.handle(&request(
+            5,
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": URI } }),
+        ));
+        assert_eq!(symbols[0]["result"][0]["name"], "main");
+
+        let tokens = server.handle(&request(
+            6,
+            "textDocument/semanticTokens/full",
+            json!({ "textDocument": { "uri": URI } }),
+        ));
+        let data = tokens[0]["result"]["data"]
+            .as_array()
+            .expect("Tokens missing");
+        assert_eq!(
+            Value::from(data[..10].to_vec()),
+            json!([0, 0, 4, 0, 0, 0, 6, 3, 1, 0])
+        );
+
+        server.handle(&request(7, "shutdown", Value::Null));
+        server.handle(&json!({ "jsonrpc": "2.0", "method": "exit" }));
+        assert!(server.is_finished());
+    }
+}
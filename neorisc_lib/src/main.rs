@@ -1,3 +1,821 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use neorisc_lib::compiler::diagnostics::Diagnostic;
+use neorisc_lib::compiler::{RiscCompiler, RiscCompilerConfig};
+use neorisc_lib::dap::DapServer;
+use neorisc_lib::debugger::repl::{execute, format_location, ReplAction};
+use neorisc_lib::debugger::Debugger;
+use neorisc_lib::formatter::format_source;
+use neorisc_lib::gdb::GdbStub;
+use neorisc_lib::interpreter::board::{attach_lab_board, Leds, SevenSegmentDisplay, Switches};
+use neorisc_lib::interpreter::snapshot::Snapshot;
+use neorisc_lib::interpreter::trace::{Trace, TraceFilter};
+use neorisc_lib::interpreter::uart::{StreamHost, Uart, UART_ADDRESS, UART_REGISTER_COUNT};
+use neorisc_lib::interpreter::{RiscInterpreter, RunOutcome, DEFAULT_STACK_DEPTH};
+use neorisc_lib::lsp::LanguageServer;
+use neorisc_lib::preprocessor::conditionals::resolve_value;
+use neorisc_lib::program::RiscProgram;
+use neorisc_lib::testing::junit::junit_xml;
+use neorisc_lib::testing::{discover, SuiteReport, TestSuite};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const EXIT_OK: i32 = 0;
+const EXIT_DIAGNOSTICS: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_CYCLE_LIMIT: i32 = 3;
+const EXIT_FAULT: i32 = 4;
+
+const DEFAULT_CYCLE_LIMIT: &str = "1000000";
+const DEFAULT_GDB_PORT: &str = "1234";
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+struct CliFailure {
+    code: i32,
+    message: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl CliFailure {
+    fn usage(message: String) -> Self {
+        Self {
+            code: EXIT_USAGE,
+            message,
+            diagnostics: vec![],
+        }
+    }
+}
+
+type CliResult<T> = Result<T, CliFailure>;
+
+fn read_file(path: &str) -> CliResult<String> {
+    fs::read_to_string(path).map_err(|err| CliFailure::usage(format!("{}: {}", path, err)))
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> CliResult<()> {
+    fs::write(path, contents)
+        .map_err(|err| CliFailure::usage(format!("{}: {}", path.display(), err)))
+}
+
+fn parse_defines(matches: &ArgMatches) -> CliResult<RiscCompilerConfig> {
+    let mut config = RiscCompilerConfig::default();
+    for define in matches.values_of("define").into_iter().flatten() {
+        let mut parts = define.splitn(2, '=');
+        let name = parts.next().unwrap_or_default();
+        let value = match parts.next() {
+            Some(value) => resolve_value(value, &config.preprocessor.defines)
+                .map_err(|_| CliFailure::usage(format!("Invalid define value: {}", define)))?,
+            None => 1,
+        };
+        config.preprocessor.defines.insert(name.to_string(), value);
+    }
+
+    Ok(config)
+}
+
+fn assemble(path: &str, matches: &ArgMatches) -> CliResult<(RiscCompiler, Vec<Diagnostic>)> {
+    let source = read_file(path)?;
+    let mut compiler = RiscCompiler::new(source, parse_defines(matches)?);
+    compiler.compile();
+    let diagnostics = compiler.diagnostics();
+
+    Ok((compiler, diagnostics))
+}
+
+fn load_program(path: &str, matches: &ArgMatches) -> CliResult<RiscProgram> {
+    let config = RiscCompilerConfig::default();
+    if path.ends_with(".json") {
+        RiscProgram::from_json(&read_file(path)?)
+            .map_err(|err| CliFailure::usage(format!("{}: {}", path, err)))
+    } else if path.ends_with(".bin") {
+        let bytes =
+            fs::read(path).map_err(|err| CliFailure::usage(format!("{}: {}", path, err)))?;
+        RiscProgram::from_binary(&bytes, config.max_instruction_count)
+            .ok_or_else(|| CliFailure::usage(format!("{}: Binary image too short", path)))
+    } else {
+        let (compiler, diagnostics) = assemble(path, matches)?;
+        if !diagnostics.is_empty() {
+            return Err(CliFailure {
+                code: EXIT_DIAGNOSTICS,
+                message: format!("{}: {} error(s)", path, diagnostics.len()),
+                diagnostics,
+            });
+        }
+
+        Ok(compiler.program().expect("Didn't generate").clone())
+    }
+}
+
+fn output_base(input: &str, matches: &ArgMatches) -> PathBuf {
+    match matches.value_of("output") {
+        Some(output) => PathBuf::from(output),
+        None => Path::new(input).with_extension(""),
+    }
+}
+
+fn with_suffix(base: &Path, suffix: &str) -> PathBuf {
+    let mut path = base.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn print_diagnostics(format: OutputFormat, path: &str, diagnostics: &[Diagnostic]) {
+    match format {
+        OutputFormat::Json => println!("{}", json!({ "file": path, "diagnostics": diagnostics })),
+        OutputFormat::Text => {
+            for diagnostic in diagnostics {
+                eprintln!("{}: {}", path, diagnostic);
+            }
+        }
+    }
+}
+
+fn command_check(matches: &ArgMatches, format: OutputFormat) -> CliResult<i32> {
+    let input = matches.value_of("INPUT").expect("INPUT is required");
+    let (_, diagnostics) = assemble(input, matches)?;
+    if format == OutputFormat::Json || !diagnostics.is_empty() {
+        print_diagnostics(format, input, &diagnostics);
+    }
+
+    Ok(if diagnostics.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_DIAGNOSTICS
+    })
+}
+
+fn command_asm(matches: &ArgMatches, format: OutputFormat) -> CliResult<i32> {
+    let input = matches.value_of("INPUT").expect("INPUT is required");
+    let (compiler, diagnostics) = assemble(input, matches)?;
+    if !diagnostics.is_empty() {
+        print_diagnostics(format, input, &diagnostics);
+        return Ok(EXIT_DIAGNOSTICS);
+    }
+
+    let program = compiler.program().expect("Didn't generate");
+    let base = output_base(input, matches);
+    let mut written = vec![];
+    for emit in matches.values_of("emit").into_iter().flatten() {
+        let outputs = match emit {
+            "json" => vec![(".json", program.to_json().into_bytes())],
+            "bin" => vec![(".bin", program.to_binary())],
+            "memh" => vec![
+                (".code.memh", program.code_memh().into_bytes()),
+                (".data.memh", program.data_memh().into_bytes()),
+            ],
+            "lst" => vec![(".lst", program.disassemble().into_bytes())],
+            _ => unreachable!("Emit formats are validated by clap"),
+        };
+        for (suffix, contents) in outputs {
+            let path = with_suffix(&base, suffix);
+            write_file(&path, &contents)?;
+            written.push(path.display().to_string());
+        }
+    }
+
+    if format == OutputFormat::Json {
+        println!(
+            "{}",
+            json!({ "file": input, "diagnostics": diagnostics, "outputs": written })
+        );
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn parse_stack_depth(matches: &ArgMatches) -> CliResult<usize> {
+    matches
+        .value_of("stack-depth")
+        .map_or(Ok(DEFAULT_STACK_DEPTH), |depth| {
+            depth
+                .parse::<usize>()
+                .map_err(|_| CliFailure::usage("Invalid stack depth".to_string()))
+        })
+}
+
+fn attach_uart(
+    interpreter: &mut RiscInterpreter,
+    target: &str,
+    input: Option<&str>,
+) -> CliResult<()> {
+    let open_input = |path: &str| {
+        fs::File::open(path).map_err(|err| CliFailure::usage(format!("{}: {}", path, err)))
+    };
+    let host = match (target, input) {
+        ("stdio", None) => StreamHost::terminal(),
+        ("stdio", Some(path)) => StreamHost::new(Some(open_input(path)?), Box::new(io::stdout())),
+        (path, input) => {
+            let output = fs::File::create(path)
+                .map_err(|err| CliFailure::usage(format!("{}: {}", path, err)))?;
+            let input = input.map(open_input).transpose()?;
+            StreamHost::new(input, Box::new(output))
+        }
+    };
+
+    interpreter
+        .bus
+        .attach(UART_ADDRESS, UART_REGISTER_COUNT, Uart::new(host))
+        .map_err(|err| CliFailure::usage(err.to_string()))
+}
+
+fn parse_trace_filter(matches: &ArgMatches) -> CliResult<TraceFilter> {
+    let mut filter = TraceFilter::default();
+    if let Some(range) = matches.value_of("trace-range") {
+        let invalid = || CliFailure::usage(format!("Invalid trace range: {}", range));
+        let (start, end) = range.split_at(range.find('-').ok_or_else(invalid)?);
+        let address =
+            |value: &str| resolve_value(value.trim(), &HashMap::new()).map_err(|_| invalid());
+        filter.addresses = Some(address(start)?..=address(&end[1..])?);
+    }
+    if let Some(registers) = matches.value_of("trace-registers") {
+        filter.registers = Some(
+            registers
+                .split(',')
+                .map(|register| {
+                    register
+                        .trim()
+                        .trim_start_matches(['r', 'R'])
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|register| *register < 16)
+                        .ok_or_else(|| {
+                            CliFailure::usage(format!("Invalid trace register: {}", register))
+                        })
+                })
+                .collect::<CliResult<_>>()?,
+        );
+    }
+    Ok(filter)
+}
+
+fn write_trace(trace: &Trace, matches: &ArgMatches) -> CliResult<()> {
+    let path = Path::new(matches.value_of("trace").expect("trace is required"));
+    let contents = match matches.value_of("trace-format").unwrap_or("text") {
+        "csv" => trace.to_csv().into_bytes(),
+        "bin" => trace.to_binary(),
+        _ => trace.to_text().into_bytes(),
+    };
+    write_file(path, &contents)
+}
+
+fn command_run(matches: &ArgMatches, format: OutputFormat) -> CliResult<i32> {
+    let cycles = matches
+        .value_of("cycles")
+        .unwrap_or(DEFAULT_CYCLE_LIMIT)
+        .parse::<u64>()
+        .map_err(|_| CliFailure::usage("Invalid cycle limit".to_string()))?;
+    let program = match matches.value_of("INPUT") {
+        Some(input) => load_program(input, matches)?,
+        None => RiscProgram::new(0, 0),
+    };
+    let snapshot = matches
+        .value_of("load-state")
+        .map(|path| Snapshot::load(Path::new(path)))
+        .transpose()
+        .map_err(|err| CliFailure::usage(err.to_string()))?;
+    let mut interpreter =
+        RiscInterpreter::new(&program).with_stack_depth(parse_stack_depth(matches)?);
+    let board = matches.is_present("board")
+        || matches.is_present("switches")
+        || snapshot
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.has_device("leds"));
+    if board {
+        attach_lab_board(&mut interpreter.bus).expect("Lab board address map overlaps");
+    }
+    if let Some(target) = matches.value_of("uart") {
+        attach_uart(&mut interpreter, target, matches.value_of("uart-input"))?;
+    }
+    if let Some(snapshot) = &snapshot {
+        snapshot
+            .restore(&mut interpreter)
+            .map_err(|err| CliFailure::usage(err.to_string()))?;
+    }
+    if let Some(switches) = matches.value_of("switches") {
+        let value = resolve_value(switches, &HashMap::new())
+            .map_err(|_| CliFailure::usage(format!("Invalid switches: {}", switches)))?;
+        interpreter
+            .bus
+            .device_mut::<Switches>()
+            .expect("Switches missing")
+            .value = value;
+    }
+    if matches.is_present("trace") {
+        interpreter.trace = Some(Trace::new(parse_trace_filter(matches)?));
+    }
+    let result = interpreter.run(cycles);
+    if let Some(trace) = &interpreter.trace {
+        write_trace(trace, matches)?;
+    }
+    if let Some(path) = matches.value_of("save-state") {
+        Snapshot::capture(&interpreter)
+            .save(Path::new(path))
+            .map_err(|err| CliFailure::usage(err.to_string()))?;
+    }
+    let leds = interpreter.bus.device::<Leds>().map(|leds| leds.value);
+    let display = interpreter
+        .bus
+        .device::<SevenSegmentDisplay>()
+        .map(SevenSegmentDisplay::text);
+
+    let (outcome, code, error) = match &result {
+        Ok(RunOutcome::Halted) => ("halted", EXIT_OK, None),
+        Ok(RunOutcome::CycleLimit) => ("cycle_limit", EXIT_CYCLE_LIMIT, None),
+        Err(err) => ("fault", EXIT_FAULT, Some(err.to_string())),
+    };
+
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "outcome": outcome,
+                "error": error,
+                "cycles": interpreter.cycles,
+                "pc": interpreter.pc,
+                "registers": interpreter.registers,
+                "flags": interpreter.flags,
+                "board": if board {
+                    json!({ "leds": leds, "display": display })
+                } else {
+                    Value::Null
+                },
+            })
+        ),
+        OutputFormat::Text => {
+            println!(
+                "{} after {} cycles at pc 0x{:02X}",
+                outcome, interpreter.cycles, interpreter.pc
+            );
+            if let Some(error) = error {
+                println!("error: {}", error);
+            }
+            for (ii, value) in interpreter.registers.iter().enumerate() {
+                print!(
+                    "r{:<2} = 0x{:02X}{}",
+                    ii,
+                    value,
+                    if ii % 4 == 3 { "\n" } else { "  " }
+                );
+            }
+            let flags = interpreter.flags;
+            println!(
+                "Z={} C={} N={} V={} IE={} IF={}",
+                flags.z as u8,
+                flags.c as u8,
+                flags.n as u8,
+                flags.v as u8,
+                flags.ie as u8,
+                flags.if_ as u8
+            );
+            if let (Some(leds), Some(display)) = (leds, display) {
+                println!("LEDs = {:08b}  display = [{}]", leds, display);
+            }
+        }
+    }
+
+    Ok(code)
+}
+
+fn command_disasm(matches: &ArgMatches, format: OutputFormat) -> CliResult<i32> {
+    let input = matches.value_of("INPUT").expect("INPUT is required");
+    let program = load_program(input, matches)?;
+    match format {
+        OutputFormat::Text => print!("{}", program.disassemble()),
+        OutputFormat::Json => println!("{}", json!({ "code": program.disassembly() })),
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn command_debug(matches: &ArgMatches, _format: OutputFormat) -> CliResult<i32> {
+    let input = matches.value_of("INPUT").expect("INPUT is required");
+    let program = load_program(input, matches)?;
+    let source = if input.ends_with(".json") || input.ends_with(".bin") {
+        None
+    } else {
+        Some(read_file(input)?)
+    };
+    let mut debugger = Debugger::new(program, source.as_deref()).with_history();
+    debugger.interpreter.stack_depth = parse_stack_depth(matches)?;
+
+    println!("{}", format_location(&debugger));
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(neorisc) ");
+        io::stdout().flush().expect("Failed to flush stdout");
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        match execute(&mut debugger, &line) {
+            ReplAction::Output(output) if output.is_empty() => {}
+            ReplAction::Output(output) => println!("{}", output),
+            ReplAction::Quit => break,
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn command_gdb(matches: &ArgMatches, _format: OutputFormat) -> CliResult<i32> {
+    let input = matches.value_of("INPUT").expect("INPUT is required");
+    let port = matches
+        .value_of("port")
+        .unwrap_or(DEFAULT_GDB_PORT)
+        .parse::<u16>()
+        .map_err(|_| CliFailure::usage("Invalid port".to_string()))?;
+    let program = load_program(input, matches)?;
+    let mut debugger = Debugger::new(program, None);
+    if matches.is_present("reverse") {
+        debugger = debugger.with_history();
+    }
+    let mut stub = GdbStub::new(debugger);
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|err| CliFailure::usage(format!("Port {}: {}", port, err)))?;
+    eprintln!("Waiting for a GDB connection on 127.0.0.1:{}", port);
+    stub.serve(listener)
+        .map_err(|err| CliFailure::usage(format!("GDB connection: {}", err)))?;
+
+    Ok(EXIT_OK)
+}
+
+fn command_dap() -> CliResult<i32> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    DapServer::new()
+        .serve(stdin.lock(), stdout.lock())
+        .map_err(|err| CliFailure::usage(format!("Debug adapter: {}", err)))?;
+
+    Ok(EXIT_OK)
+}
+
+fn command_lsp() -> CliResult<i32> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    LanguageServer::new()
+        .serve(stdin.lock(), stdout.lock())
+        .map_err(|err| CliFailure::usage(format!("Language server: {}", err)))?;
+
+    Ok(EXIT_OK)
+}
+
+fn command_fmt(matches: &ArgMatches, format: OutputFormat) -> CliResult<i32> {
+    let mut unformatted = vec![];
+    for input in matches.values_of("INPUT").into_iter().flatten() {
+        let source = read_file(input)?;
+        let formatted = format_source(&source);
+        if matches.is_present("write") {
+            if formatted != source {
+                write_file(Path::new(input), formatted.as_bytes())?;
+            }
+        } else if matches.is_present("check") {
+            if formatted != source {
+                unformatted.push(input.to_string());
+            }
+        } else {
+            print!("{}", formatted);
+        }
+    }
+
+    if matches.is_present("check") {
+        match format {
+            OutputFormat::Json => println!("{}", json!({ "unformatted": unformatted })),
+            OutputFormat::Text => {
+                for input in unformatted.iter() {
+                    eprintln!("{}: not formatted", input);
+                }
+            }
+        }
+    }
+
+    Ok(if unformatted.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_DIAGNOSTICS
+    })
+}
+
+fn print_suite(suite: &SuiteReport) {
+    println!("{} ({})", suite.name, suite.source);
+    for case in suite.cases.iter() {
+        if case.passed() {
+            println!("  PASS {} ({} cycles)", case.name, case.cycles);
+            continue;
+        }
+        println!("  FAIL {}", case.name);
+        for line in case.error.iter().flat_map(|error| error.lines()) {
+            println!("       error: {}", line);
+        }
+        for failure in case.failures.iter() {
+            println!("       {}", failure);
+        }
+    }
+}
+
+fn command_test(matches: &ArgMatches, format: OutputFormat) -> CliResult<i32> {
+    let paths: Vec<PathBuf> = matches
+        .values_of("INPUT")
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect();
+    let config = parse_defines(matches)?;
+    let mut suites = vec![];
+    for path in discover(&paths) {
+        suites.push(match TestSuite::load(&path) {
+            Ok(suite) => suite.run(config.clone()),
+            Err(err) => SuiteReport::load_failed(&path, &err),
+        });
+    }
+
+    if let Some(junit) = matches.value_of("junit") {
+        write_file(Path::new(junit), junit_xml(&suites).as_bytes())?;
+    }
+
+    let total: usize = suites.iter().map(|suite| suite.cases.len()).sum();
+    let failed: usize = suites.iter().map(SuiteReport::failed).sum();
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            json!({ "suites": suites, "passed": total - failed, "failed": failed })
+        ),
+        OutputFormat::Text => {
+            for suite in suites.iter() {
+                print_suite(suite);
+            }
+            println!("{} passed, {} failed", total - failed, failed);
+        }
+    }
+
+    Ok(if failed == 0 {
+        EXIT_OK
+    } else {
+        EXIT_DIAGNOSTICS
+    })
+}
+
+fn app() -> App<'static, 'static> {
+    let input = Arg::with_name("INPUT")
+        .help("Assembly source, or an assembled .json/.bin program")
+        .required(true);
+    let define = Arg::with_name("define")
+        .short("D")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Predefines a symbol as NAME or NAME=VALUE");
+    let stack_depth = Arg::with_name("stack-depth")
+        .long("stack-depth")
+        .takes_value(true)
+        .help("Hardware call stack depth (default 16)");
+
+    App::new("neorisc")
+        .about("MiniRISC assembler and emulator")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text"),
+        )
+        .subcommand(
+            SubCommand::with_name("asm")
+                .about("Assembles a source file")
+                .arg(input.clone())
+                .arg(define.clone())
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Output path without extension"),
+                )
+                .arg(
+                    Arg::with_name("emit")
+                        .long("emit")
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .possible_values(&["json", "bin", "memh", "lst"])
+                        .default_value("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Reports diagnostics without writing output")
+                .arg(input.clone())
+                .arg(define.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a program in the emulator")
+                .arg(input.clone().required_unless("load-state"))
+                .arg(define.clone())
+                .arg(stack_depth.clone())
+                .arg(
+                    Arg::with_name("cycles")
+                        .long("cycles")
+                        .takes_value(true)
+                        .help("Cycle limit"),
+                )
+                .arg(
+                    Arg::with_name("board")
+                        .long("board")
+                        .help("Attaches the lab board peripherals at 0x80-0xFF"),
+                )
+                .arg(
+                    Arg::with_name("switches")
+                        .long("switches")
+                        .takes_value(true)
+                        .help("DIP switch positions, implies --board"),
+                )
+                .arg(
+                    Arg::with_name("uart")
+                        .long("uart")
+                        .takes_value(true)
+                        .value_name("stdio|FILE")
+                        .help("Attaches the UART at 0x88, transmitting to the terminal or a file"),
+                )
+                .arg(
+                    Arg::with_name("uart-input")
+                        .long("uart-input")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .requires("uart")
+                        .help("File or pipe the UART receives from"),
+                )
+                .arg(
+                    Arg::with_name("load-state")
+                        .long("load-state")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Starts from a saved snapshot, INPUT is optional then"),
+                )
+                .arg(
+                    Arg::with_name("save-state")
+                        .long("save-state")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Saves the final state as a snapshot, e.g. for bug reports"),
+                )
+                .arg(
+                    Arg::with_name("trace")
+                        .long("trace")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Records every executed instruction to a trace file"),
+                )
+                .arg(
+                    Arg::with_name("trace-format")
+                        .long("trace-format")
+                        .takes_value(true)
+                        .possible_values(&["text", "csv", "bin"])
+                        .requires("trace")
+                        .help("Trace file format, bin can be replayed"),
+                )
+                .arg(
+                    Arg::with_name("trace-range")
+                        .long("trace-range")
+                        .takes_value(true)
+                        .value_name("START-END")
+                        .requires("trace")
+                        .help("Only records instructions in the code address range"),
+                )
+                .arg(
+                    Arg::with_name("trace-registers")
+                        .long("trace-registers")
+                        .takes_value(true)
+                        .value_name("r0,r1,...")
+                        .requires("trace")
+                        .help("Only records changes of these registers"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about("Debugs a program in an interactive prompt")
+                .arg(input.clone())
+                .arg(define.clone())
+                .arg(stack_depth),
+        )
+        .subcommand(
+            SubCommand::with_name("gdb")
+                .about("Serves a program over the GDB remote serial protocol")
+                .arg(input.clone())
+                .arg(define.clone())
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .help("TCP port on 127.0.0.1 (default 1234)"),
+                )
+                .arg(
+                    Arg::with_name("reverse")
+                        .long("reverse")
+                        .help("Records history for reverse stepping and continuing"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dap").about("Runs a debug adapter protocol server on stdio"),
+        )
+        .subcommand(SubCommand::with_name("lsp").about("Runs a language server on stdio"))
+        .subcommand(
+            SubCommand::with_name("disasm")
+                .about("Disassembles a program")
+                .arg(input.clone())
+                .arg(define.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Runs the test cases of .test.toml files against their programs")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Test files, sources with a test file next to them, or directories")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(define)
+                .arg(
+                    Arg::with_name("junit")
+                        .long("junit")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Writes a JUnit XML report"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Formats source files")
+                .arg(input.multiple(true))
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .conflicts_with("write"),
+                )
+                .arg(Arg::with_name("write").long("write")),
+        )
+}
+
 fn main() {
-    println!("Hello, world!");
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(err) => match err.kind {
+            clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => err.exit(),
+            _ => {
+                eprintln!("{}", err.message);
+                process::exit(EXIT_USAGE);
+            }
+        },
+    };
+
+    let format = match matches.value_of("format") {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Text,
+    };
+    let result = match matches.subcommand() {
+        ("asm", Some(sub)) => command_asm(sub, format),
+        ("check", Some(sub)) => command_check(sub, format),
+        ("run", Some(sub)) => command_run(sub, format),
+        ("debug", Some(sub)) => command_debug(sub, format),
+        ("gdb", Some(sub)) => command_gdb(sub, format),
+        ("dap", Some(_)) => command_dap(),
+        ("lsp", Some(_)) => command_lsp(),
+        ("disasm", Some(sub)) => command_disasm(sub, format),
+        ("fmt", Some(sub)) => command_fmt(sub, format),
+        ("test", Some(sub)) => command_test(sub, format),
+        _ => Err(CliFailure::usage("Unknown command".to_string())),
+    };
+
+    let code = match result {
+        Ok(code) => code,
+        Err(failure) => {
+            if failure.diagnostics.is_empty() {
+                match format {
+                    OutputFormat::Json => println!("{}", json!({ "error": failure.message })),
+                    OutputFormat::Text => eprintln!("{}", failure.message),
+                }
+            } else {
+                let path = matches
+                    .subcommand()
+                    .1
+                    .and_then(|sub| sub.value_of("INPUT"))
+                    .unwrap_or_default();
+                print_diagnostics(format, path, &failure.diagnostics);
+            }
+            failure.code
+        }
+    };
+
+    process::exit(code);
 }
@@ -1,6 +1,7 @@
 use crate::error::LangParseError;
 use crate::lang::{LangCommand, LangLiteral};
 use crate::parser::line_tokenizer::tokenize_source_line;
+use crate::preprocessor::LineOrigin;
 
 pub struct LangCommandParserContext {
     line: SourceLine,
@@ -36,13 +37,38 @@ pub struct SourceLine {
     pub label: Option<String>,
     pub mnemonic: Option<LangCommand>,
     pub arguments: Vec<LangLiteral>,
+    pub origin: LineOrigin,
+    pub error: Option<LangParseError>,
+}
+
+impl SourceLine {
+    pub fn from_error(line: &str, origin: LineOrigin, error: LangParseError) -> Self {
+        Self {
+            label: recover_label(line),
+            mnemonic: None,
+            arguments: vec![],
+            origin,
+            error: Some(error),
+        }
+    }
+}
+
+fn recover_label(line: &str) -> Option<String> {
+    let tokens = tokenize_source_line(line).ok()?;
+    if tokens.len() >= 2 && tokens[1] == ":" {
+        LangLiteral::validate_label_name(&tokens[0])
+            .ok()
+            .map(|label| label.to_string())
+    } else {
+        None
+    }
 }
 
 fn handle_expected_label(
     mut ctx: LangCommandParserContext,
     token_ctx: &LangCommandParserTokenContext,
 ) -> Result<LangCommandParserContext, LangParseError> {
-    ctx.line.label = Some(LangLiteral::validate_symbol_name(token_ctx.at_token)?.to_string());
+    ctx.line.label = Some(LangLiteral::validate_label_name(token_ctx.at_token)?.to_string());
     ctx.last_token_was_label = true;
 
     Ok(ctx)
@@ -72,6 +98,8 @@ pub fn parse_command_line(line: &str) -> Result<SourceLine, LangParseError> {
             label: None,
             mnemonic: None,
             arguments: vec![],
+            origin: LineOrigin::new(0),
+            error: None,
         },
         last_token_was_argument: false,
         last_token_was_label: false,
@@ -99,7 +127,9 @@ pub fn parse_command_line(line: &str) -> Result<SourceLine, LangParseError> {
         } else if ctx.line.mnemonic.is_none() {
             ctx = handle_expected_mnemonic(ctx, &token_ctx)?;
         } else {
-            if ctx.last_token_was_argument && token != "," {
+            let is_definition_value =
+                ctx.line.mnemonic == Some(LangCommand::DEF) && ctx.line.arguments.len() == 1;
+            if ctx.last_token_was_argument && token != "," && !is_definition_value {
                 return Err(LangParseError::ExpectedComma);
             } else if token_ctx
                 .previous_token()
@@ -107,7 +137,7 @@ pub fn parse_command_line(line: &str) -> Result<SourceLine, LangParseError> {
                 && token == ","
             {
                 return Err(LangParseError::UnexpectedToken);
-            } else if !ctx.last_token_was_argument && token != "," {
+            } else if (!ctx.last_token_was_argument || is_definition_value) && token != "," {
                 let arg = LangLiteral::from_string(token)?;
                 ctx.line.arguments.push(arg);
                 to_set_was_arg = true;
@@ -152,6 +182,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn definition_parsed_without_comma() {
+        let source_line = parse_command_line("DEF LD 0x80").expect("Line parsing failed");
+        assert_eq!(
+            source_line.arguments,
+            vec![
+                LangLiteral::Symbol("LD".to_string()),
+                LangLiteral::Address(0x80)
+            ]
+        );
+        assert!(parse_command_line("MOV r0 r1").is_err());
+    }
+
+    #[test]
+    fn scoped_labels_parsed() {
+        let source_line = parse_command_line(".loop: JMP @loop").expect("Line parsing failed");
+        assert_eq!(source_line.label.expect("Label is empty"), ".loop");
+        assert_eq!(
+            source_line.arguments,
+            vec![LangLiteral::Symbol("@loop".to_string())]
+        );
+
+        let source_line = parse_command_line("-: JMP --").expect("Line parsing failed");
+        assert_eq!(source_line.label.expect("Label is empty"), "-");
+        assert_eq!(
+            source_line.arguments,
+            vec![LangLiteral::Symbol("--".to_string())]
+        );
+        assert!(parse_command_line("a.b: JMP +-").is_err());
+    }
+
     #[test]
     fn args_parsed() {
         let source_line =
@@ -160,31 +221,31 @@ mod tests {
         if let LangLiteral::Register(reg) = source_line.arguments[0] {
             assert_eq!(reg, 0);
         } else {
-            assert!(false);
+            panic!();
         }
 
         if let LangLiteral::Address(addr) = source_line.arguments[1] {
             assert_eq!(addr, 100);
         } else {
-            assert!(false);
+            panic!();
         }
 
         if let LangLiteral::Symbol(symb) = source_line.arguments[2].borrow() {
             assert_eq!(symb, "LD");
         } else {
-            assert!(false);
+            panic!();
         }
 
         if let LangLiteral::Address(addr) = source_line.arguments[3] {
             assert_eq!(addr, 255);
         } else {
-            assert!(false);
+            panic!();
         }
 
         if let LangLiteral::Constant(addr) = source_line.arguments[4] {
             assert_eq!(addr, 5);
         } else {
-            assert!(false);
+            panic!();
         }
     }
 }
@@ -0,0 +1,127 @@
+use crate::lang::{LangCommand, LangLiteral};
+use crate::parser::line_tokenizer::tokenize_source_spans;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HighlightKind {
+    Label,
+    Instruction,
+    Directive,
+    Macro,
+    Register,
+    Immediate,
+    Number,
+    String,
+    Symbol,
+    Punctuation,
+    Comment,
+    Invalid,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HighlightSpan {
+    pub span: Range<usize>,
+    pub kind: HighlightKind,
+    pub text: String,
+}
+
+fn classify_argument(token: &str) -> HighlightKind {
+    match LangLiteral::from_string(token) {
+        Ok(LangLiteral::Register(_)) | Ok(LangLiteral::Indirect(_)) => HighlightKind::Register,
+        Ok(LangLiteral::Constant(_)) | Ok(LangLiteral::ConstantSymbol(_)) => {
+            HighlightKind::Immediate
+        }
+        Ok(LangLiteral::Address(_)) => HighlightKind::Number,
+        Ok(LangLiteral::Char(_)) | Ok(LangLiteral::String(_)) => HighlightKind::String,
+        Ok(LangLiteral::Symbol(_)) => HighlightKind::Symbol,
+        Err(_) if token.starts_with("%%") => HighlightKind::Symbol,
+        Err(_) => HighlightKind::Invalid,
+    }
+}
+
+// Classifies the raw tokens of one source line, spans are byte ranges into the line.
+pub fn highlight_line(line: &str) -> Vec<HighlightSpan> {
+    let tokenized = tokenize_source_spans(line);
+    let tokens = &tokenized.tokens;
+    let mut spans = vec![];
+    let mut seen_mnemonic = false;
+
+    for (ii, token) in tokens.iter().enumerate() {
+        let text = &line[token.span.clone()];
+        let next_is_colon = tokens.get(ii + 1).is_some_and(|next| next.text == ":");
+        let kind = if text == "," || text == ":" {
+            HighlightKind::Punctuation
+        } else if !seen_mnemonic && ii == 0 && next_is_colon {
+            HighlightKind::Label
+        } else if !seen_mnemonic {
+            seen_mnemonic = true;
+            match LangCommand::from_string(text) {
+                Some(command) if command.is_instruction() => HighlightKind::Instruction,
+                Some(_) => HighlightKind::Directive,
+                None => HighlightKind::Macro,
+            }
+        } else {
+            classify_argument(text)
+        };
+
+        spans.push(HighlightSpan {
+            span: token.span.clone(),
+            kind,
+            text: text.to_string(),
+        });
+    }
+
+    if let Some(comment) = tokenized.comment {
+        spans.push(HighlightSpan {
+            text: line[comment.clone()].to_string(),
+            span: comment,
+            kind: HighlightKind::Comment,
+        });
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::highlight::{highlight_line, HighlightKind};
+
+    #[test]
+    fn tokens_classified() {
+        let kinds: Vec<HighlightKind> = highlight_line("loop: ADD r0, #LIMIT ; again")
+            .iter()
+            .map(|span| span.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HighlightKind::Label,
+                HighlightKind::Punctuation,
+                HighlightKind::Instruction,
+                HighlightKind::Register,
+                HighlightKind::Punctuation,
+                HighlightKind::Immediate,
+                HighlightKind::Comment,
+            ]
+        );
+
+        let kinds: Vec<HighlightKind> = highlight_line("DB \"x\", 0x10, buf, r99")
+            .iter()
+            .map(|span| span.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HighlightKind::Directive,
+                HighlightKind::String,
+                HighlightKind::Punctuation,
+                HighlightKind::Number,
+                HighlightKind::Punctuation,
+                HighlightKind::Symbol,
+                HighlightKind::Punctuation,
+                HighlightKind::Invalid,
+            ]
+        );
+        assert_eq!(highlight_line("wait r1")[0].kind, HighlightKind::Macro);
+    }
+}
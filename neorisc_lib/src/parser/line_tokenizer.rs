@@ -1,23 +1,45 @@
 use crate::error::LangParseError;
 
+use std::ops::Range;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SpannedToken {
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct TokenizedLine {
+    pub tokens: Vec<SpannedToken>,
+    pub comment: Option<Range<usize>>,
+}
+
 struct LangTokenizerContext {
     pub buffer: String,
+    pub buffer_start: Option<usize>,
     pub in_char: bool,
     pub in_string: bool,
-    pub tokens: Vec<String>,
+    pub tokens: Vec<SpannedToken>,
     pub last_backslash: bool,
-    pub next_char_to_add: Option<char>,
 }
 
-fn flush_buffer(mut ctx: LangTokenizerContext) -> LangTokenizerContext {
-    if ctx.buffer.len() > 0 {
-        ctx.tokens.push(ctx.buffer);
+fn flush_buffer(mut ctx: LangTokenizerContext, end: usize) -> LangTokenizerContext {
+    if let Some(start) = ctx.buffer_start.take() {
+        ctx.tokens.push(SpannedToken {
+            text: ctx.buffer,
+            span: start..end,
+        });
     }
     ctx.buffer = String::new();
 
     ctx
 }
 
+fn push_char(ctx: &mut LangTokenizerContext, at: usize, char: char) {
+    ctx.buffer_start.get_or_insert(at);
+    ctx.buffer.push(char);
+}
+
 fn get_escaped_character(next_char: char) -> char {
     match next_char {
         '\\' => '\\',
@@ -28,75 +50,79 @@ fn get_escaped_character(next_char: char) -> char {
     }
 }
 
-fn get_next_escaped_char(ctx: &LangTokenizerContext, next_char: char) -> char {
-    if !ctx.last_backslash {
-        next_char
-    } else {
-        get_escaped_character(next_char)
-    }
-}
-
-pub fn tokenize_source_line(line: &str) -> Result<Vec<String>, LangParseError> {
+pub fn tokenize_source_spans(line: &str) -> TokenizedLine {
     let mut ctx = LangTokenizerContext {
         buffer: String::new(),
+        buffer_start: None,
         in_char: false,
         in_string: false,
         tokens: vec![],
         last_backslash: false,
-        next_char_to_add: None,
     };
+    let mut comment = None;
+
+    for (at, char) in line.char_indices() {
+        let in_literal = ctx.in_char || ctx.in_string;
+        if ctx.last_backslash {
+            push_char(&mut ctx, at, get_escaped_character(char));
+            ctx.last_backslash = false;
+            continue;
+        }
 
-    for char in line.chars() {
         match char {
             '\\' => {
-                if ctx.last_backslash {
-                    ctx.buffer.push('\\');
-                } else {
-                    ctx.last_backslash = true;
-                }
+                ctx.buffer_start.get_or_insert(at);
+                ctx.last_backslash = true;
             }
             ' ' | '\r' | '\n' | '\t' => {
-                if ctx.in_char || ctx.in_string {
-                    ctx.buffer.push(char);
+                if in_literal {
+                    push_char(&mut ctx, at, char);
                 } else {
-                    ctx = flush_buffer(ctx);
+                    ctx = flush_buffer(ctx, at);
                 }
             }
-            '\"' | '\'' => {
-                if !ctx.last_backslash {
-                    if char == '\"' {
-                        ctx.in_string = !ctx.in_string;
-                    } else {
-                        ctx.in_char = !ctx.in_char;
-                    }
-                }
-                ctx.buffer.push(char);
+            '\"' if !ctx.in_char => {
+                ctx.in_string = !ctx.in_string;
+                push_char(&mut ctx, at, char);
             }
-            ',' | ':' => {
-                ctx = flush_buffer(ctx);
-                ctx.buffer.push(char);
-                ctx = flush_buffer(ctx);
+            '\'' if !ctx.in_string => {
+                ctx.in_char = !ctx.in_char;
+                push_char(&mut ctx, at, char);
             }
-            ';' => {
-                if ctx.last_backslash || ctx.in_string || ctx.in_char {
-                    ctx.buffer.push(char);
-                } else {
-                    break;
-                }
+            ',' | ':' if !in_literal => {
+                ctx = flush_buffer(ctx, at);
+                push_char(&mut ctx, at, char);
+                ctx = flush_buffer(ctx, at + 1);
+            }
+            ';' if !in_literal => {
+                comment = Some(at..line.len());
+                break;
             }
             _ => {
-                ctx.buffer.push(get_next_escaped_char(&ctx, char));
+                push_char(&mut ctx, at, char);
             }
         }
     }
-    ctx = flush_buffer(ctx);
+    let end = comment.as_ref().map_or(line.len(), |comment| comment.start);
+    ctx = flush_buffer(ctx, end);
 
-    Ok(ctx.tokens)
+    TokenizedLine {
+        tokens: ctx.tokens,
+        comment,
+    }
+}
+
+pub fn tokenize_source_line(line: &str) -> Result<Vec<String>, LangParseError> {
+    Ok(tokenize_source_spans(line)
+        .tokens
+        .into_iter()
+        .map(|token| token.text)
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::line_tokenizer::tokenize_source_line;
+    use crate::parser::line_tokenizer::{tokenize_source_line, tokenize_source_spans};
 
     #[test]
     fn it_works() {
@@ -115,6 +141,21 @@ mod tests {
         assert_eq!(tokens.len(), 2);
     }
 
+    #[test]
+    fn separators_inside_literals_kept() {
+        let tokens =
+            tokenize_source_line(r#"DB "a, b: c;", ',', ';' ; done"#).expect("Tokenizer failed");
+
+        assert_eq!(tokens, vec!["DB", "\"a, b: c;\"", ",", "','", ",", "';'"]);
+    }
+
+    #[test]
+    fn escapes_resolved_once() {
+        let tokens = tokenize_source_line(r#"DB "\"x\n\\", 1"#).expect("Tokenizer failed");
+
+        assert_eq!(tokens, vec!["DB", "\"\"x\n\\\"", ",", "1"]);
+    }
+
     #[test]
     fn str_parse() {
         let tokens =
@@ -124,4 +165,17 @@ mod tests {
         println!("last: {}", tokens[3]);
         assert_eq!(tokens.len(), 4);
     }
+
+    #[test]
+    fn spans_point_into_line() {
+        let line = "lbl: DB \"a\\\"b\", 'c' ; note";
+        let tokenized = tokenize_source_spans(line);
+        let spans: Vec<&str> = tokenized
+            .tokens
+            .iter()
+            .map(|token| &line[token.span.clone()])
+            .collect();
+        assert_eq!(spans, vec!["lbl", ":", "DB", "\"a\\\"b\"", ",", "'c'"]);
+        assert_eq!(tokenized.comment, Some(20..line.len()));
+    }
 }
@@ -1,3 +1,4 @@
 pub mod command_parser;
+pub mod highlight;
 pub mod line_tokenizer;
 pub mod source_parser;
@@ -1,45 +1,110 @@
 use crate::error::LangParseError;
 use crate::parser::command_parser::{parse_command_line, SourceLine};
+use crate::preprocessor::{LineOrigin, PreprocessedLine};
 use rayon::prelude::*;
 
 pub struct LangParsingArtifact {
-    pub lines: Option<Vec<SourceLine>>,
-    pub errors: Option<Vec<ParsingError>>,
+    pub lines: Vec<SourceLine>,
+    pub errors: Vec<ParsingError>,
 }
 
 pub struct ParsingError {
-    pub line: usize,
+    pub origin: LineOrigin,
     pub error: LangParseError,
 }
 
-pub fn parse_source(source: &str) -> LangParsingArtifact {
-    let results = source
-        .lines()
-        .collect::<Vec<&str>>()
-        .par_iter()
-        .filter(|&line| line != &"")
-        .map(|&line| parse_command_line(line))
-        .collect::<Vec<Result<SourceLine, LangParseError>>>();
-
-    let mut parsed_lines = Vec::new();
-    let mut errors = Vec::new();
-    for (ii, result) in results.into_iter().enumerate() {
-        match result {
-            Ok(source_line) => parsed_lines.push(source_line),
-            Err(err) => errors.push(ParsingError {
-                line: ii,
-                error: err,
-            }),
+fn parse_line(line: &PreprocessedLine) -> SourceLine {
+    match parse_command_line(&line.text) {
+        Ok(mut source_line) => {
+            source_line.origin = line.origin.clone();
+            source_line
         }
+        Err(err) => SourceLine::from_error(&line.text, line.origin.clone(), err),
     }
-    let had_errors = errors.len() > 0;
+}
+
+pub fn parse_lines(lines: &[PreprocessedLine]) -> LangParsingArtifact {
+    let parsed_lines = lines
+        .par_iter()
+        .filter(|line| !line.text.is_empty())
+        .map(parse_line)
+        .collect::<Vec<SourceLine>>();
+
+    let errors = parsed_lines
+        .iter()
+        .filter_map(|line| {
+            line.error.as_ref().map(|err| ParsingError {
+                origin: line.origin.clone(),
+                error: err.clone(),
+            })
+        })
+        .collect::<Vec<ParsingError>>();
 
     LangParsingArtifact {
-        errors: if had_errors { Some(errors) } else { None },
-        lines: if !had_errors {
-            Some(parsed_lines)
-        } else {
-            None
-        },
+        lines: parsed_lines,
+        errors,
+    }
+}
+
+pub fn parse_source(source: &str) -> LangParsingArtifact {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(ii, text)| PreprocessedLine {
+            text: text.to_string(),
+            origin: LineOrigin::new(ii),
+        })
+        .collect::<Vec<PreprocessedLine>>();
+
+    parse_lines(&lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::LangParseError;
+    use crate::parser::source_parser::parse_source;
+
+    #[test]
+    fn lines_kept_in_order_with_error_nodes() {
+        let art = parse_source("MOV r0, #1\n\nbad line here\nlbl: ADD r0, #1000\nJMP lbl");
+        assert_eq!(art.lines.len(), 4);
+        let origins: Vec<usize> = art.lines.iter().map(|l| l.origin.line).collect();
+        assert_eq!(origins, vec![0, 2, 3, 4]);
+
+        assert_eq!(art.errors.len(), 2);
+        assert_eq!(art.errors[0].origin.line, 2);
+        assert_eq!(art.errors[1].origin.line, 3);
+        assert_eq!(art.errors[1].error, LangParseError::ConstantOutOfBounds);
+
+        assert!(art.lines[0].error.is_none());
+        assert_eq!(art.lines[1].error, Some(LangParseError::InvalidMnemonic));
+        assert_eq!(art.lines[2].label.as_deref(), Some("lbl"));
+        assert!(art.lines[2].mnemonic.is_none());
+    }
+
+    #[test]
+    fn many_lines_keep_order() {
+        let source = (0..500)
+            .map(|ii| {
+                if ii % 7 == 0 {
+                    "???".to_string()
+                } else {
+                    format!("l{}: MOV r0, #{}", ii, ii % 256)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        let art = parse_source(&source);
+        assert_eq!(art.lines.len(), 500);
+        assert!(art
+            .lines
+            .iter()
+            .enumerate()
+            .all(|(ii, l)| l.origin.line == ii));
+        assert_eq!(art.errors.len(), 72);
+        assert!(art
+            .errors
+            .windows(2)
+            .all(|w| w[0].origin.line < w[1].origin.line));
     }
 }
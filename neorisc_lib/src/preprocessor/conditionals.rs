@@ -0,0 +1,120 @@
+use crate::error::LangPreprocessError;
+use crate::lang::{LangCommand, LangLiteral};
+use crate::preprocessor::LineOrigin;
+use std::collections::HashMap;
+
+pub struct ConditionalFrame {
+    pub opened_at: LineOrigin,
+    pub parent_active: bool,
+    pub taken: bool,
+    pub in_else: bool,
+}
+
+impl ConditionalFrame {
+    pub fn is_active(&self) -> bool {
+        self.parent_active && (self.taken != self.in_else)
+    }
+}
+
+pub fn is_conditional_command(command: &LangCommand) -> bool {
+    matches!(
+        command,
+        LangCommand::IF
+            | LangCommand::IFDEF
+            | LangCommand::IFNDEF
+            | LangCommand::ELSE
+            | LangCommand::ENDIF
+    )
+}
+
+pub fn resolve_value(
+    token: &str,
+    symbols: &HashMap<String, u8>,
+) -> Result<u8, LangPreprocessError> {
+    match LangLiteral::from_string(token) {
+        Ok(LangLiteral::Address(value)) | Ok(LangLiteral::Constant(value)) => Ok(value),
+        Ok(LangLiteral::Char(chr)) => Ok(chr as u8),
+        Ok(LangLiteral::Symbol(name)) => symbols
+            .get(&name)
+            .copied()
+            .ok_or(LangPreprocessError::UndefinedConditionSymbol),
+        _ => Err(LangPreprocessError::InvalidCondition),
+    }
+}
+
+pub fn evaluate_condition(
+    tokens: &[String],
+    symbols: &HashMap<String, u8>,
+) -> Result<bool, LangPreprocessError> {
+    match tokens {
+        [value] => Ok(resolve_value(value, symbols)? != 0),
+        [left, op, right] => {
+            let left = resolve_value(left, symbols)?;
+            let right = resolve_value(right, symbols)?;
+            match op.as_str() {
+                "==" => Ok(left == right),
+                "!=" => Ok(left != right),
+                _ => Err(LangPreprocessError::InvalidCondition),
+            }
+        }
+        _ => Err(LangPreprocessError::InvalidCondition),
+    }
+}
+
+pub fn evaluate_defined(
+    tokens: &[String],
+    symbols: &HashMap<String, u8>,
+) -> Result<bool, LangPreprocessError> {
+    match tokens {
+        [name] => {
+            LangLiteral::validate_symbol_name(name)
+                .map_err(|_| LangPreprocessError::InvalidCondition)?;
+            Ok(symbols.contains_key(name))
+        }
+        _ => Err(LangPreprocessError::InvalidCondition),
+    }
+}
+
+pub fn parse_definition(tokens: &[String], symbols: &HashMap<String, u8>) -> Option<(String, u8)> {
+    match tokens {
+        [name, value] => {
+            let name = LangLiteral::validate_symbol_name(name).ok()?;
+            let value = resolve_value(value, symbols).ok()?;
+            Some((name.to_string(), value))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preprocessor::conditionals::{evaluate_condition, evaluate_defined};
+    use std::collections::HashMap;
+
+    fn tokens(tt: &[&str]) -> Vec<String> {
+        tt.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn conditions_evaluated() {
+        let mut symbols = HashMap::new();
+        symbols.insert("BOARD".to_string(), 1);
+
+        assert!(evaluate_condition(&tokens(&["BOARD"]), &symbols).expect("Invalid"));
+        assert!(!evaluate_condition(&tokens(&["0"]), &symbols).expect("Invalid"));
+        assert!(evaluate_condition(&tokens(&["BOARD", "==", "0x01"]), &symbols).expect("Invalid"));
+        assert!(evaluate_condition(&tokens(&["BOARD", "!=", "2"]), &symbols).expect("Invalid"));
+        assert!(evaluate_condition(&tokens(&["SIM"]), &symbols).is_err());
+        assert!(evaluate_condition(&tokens(&["BOARD", "<", "2"]), &symbols).is_err());
+    }
+
+    #[test]
+    fn defined_evaluated() {
+        let mut symbols = HashMap::new();
+        symbols.insert("SIM".to_string(), 1);
+
+        assert!(evaluate_defined(&tokens(&["SIM"]), &symbols).expect("Invalid"));
+        assert!(!evaluate_defined(&tokens(&["BOARD"]), &symbols).expect("Invalid"));
+        assert!(evaluate_defined(&tokens(&[]), &symbols).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::error::LangPreprocessError;
+use crate::lang::LangLiteral;
+use crate::preprocessor::PreprocessedLine;
+
+#[derive(Debug, Clone)]
+pub struct MacroDefinition {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<PreprocessedLine>,
+    pub defined_at: usize,
+}
+
+fn split_on_commas(tokens: &[String]) -> Result<Vec<String>, LangPreprocessError> {
+    let mut groups = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for token in tokens.iter() {
+        if token == "," {
+            if current.is_empty() {
+                return Err(LangPreprocessError::MacroArgumentExpected);
+            }
+            groups.push(current.join(" "));
+            current.clear();
+        } else {
+            current.push(token);
+        }
+    }
+
+    if !current.is_empty() {
+        groups.push(current.join(" "));
+    } else if !groups.is_empty() {
+        return Err(LangPreprocessError::MacroArgumentExpected);
+    }
+
+    Ok(groups)
+}
+
+pub fn parse_macro_header(
+    tokens: &[String],
+    defined_at: usize,
+) -> Result<MacroDefinition, LangPreprocessError> {
+    let name = tokens
+        .first()
+        .ok_or(LangPreprocessError::MacroNameExpected)?;
+    LangLiteral::validate_symbol_name(name).map_err(|_| LangPreprocessError::MacroNameExpected)?;
+
+    let params =
+        split_on_commas(&tokens[1..]).map_err(|_| LangPreprocessError::InvalidMacroParameter)?;
+    for (ii, param) in params.iter().enumerate() {
+        LangLiteral::validate_symbol_name(param)
+            .map_err(|_| LangPreprocessError::InvalidMacroParameter)?;
+        if params[..ii].contains(param) {
+            return Err(LangPreprocessError::InvalidMacroParameter);
+        }
+    }
+
+    Ok(MacroDefinition {
+        name: name.to_string(),
+        params,
+        body: vec![],
+        defined_at,
+    })
+}
+
+pub fn parse_macro_arguments(
+    definition: &MacroDefinition,
+    tokens: &[String],
+) -> Result<Vec<String>, LangPreprocessError> {
+    let args = split_on_commas(tokens)?;
+    if args.len() != definition.params.len() {
+        return Err(LangPreprocessError::MacroArgumentCountMismatch {
+            expected: definition.params.len(),
+            found: args.len(),
+        });
+    }
+
+    Ok(args)
+}
+
+fn is_identifier_char(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || chr == '_' || chr == '%'
+}
+
+fn substitute_identifier(
+    ident: &str,
+    definition: &MacroDefinition,
+    args: &[String],
+    local_prefix: &str,
+) -> String {
+    if let Some(local) = ident.strip_prefix("%%") {
+        format!("{}{}", local_prefix, local)
+    } else if let Some(ii) = definition.params.iter().position(|param| param == ident) {
+        args[ii].clone()
+    } else {
+        ident.to_string()
+    }
+}
+
+pub fn expand_macro_line(
+    line: &str,
+    definition: &MacroDefinition,
+    args: &[String],
+    local_prefix: &str,
+) -> String {
+    let mut expanded = String::new();
+    let mut ident = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(chr) = chars.next() {
+        if quote.is_some() && chr == '\\' {
+            expanded.push(chr);
+            expanded.extend(chars.next());
+            continue;
+        }
+        if quote.is_none() && is_identifier_char(chr) {
+            ident.push(chr);
+            continue;
+        }
+
+        if !ident.is_empty() {
+            expanded.push_str(&substitute_identifier(
+                &ident,
+                definition,
+                args,
+                local_prefix,
+            ));
+            ident.clear();
+        }
+        expanded.push(chr);
+
+        match chr {
+            '\"' | '\'' if quote == Some(chr) => quote = None,
+            '\"' | '\'' if quote.is_none() => quote = Some(chr),
+            ';' if quote.is_none() => {
+                expanded.extend(chars);
+                return expanded;
+            }
+            _ => {}
+        }
+    }
+
+    if !ident.is_empty() {
+        expanded.push_str(&substitute_identifier(
+            &ident,
+            definition,
+            args,
+            local_prefix,
+        ));
+    }
+
+    expanded
+}
+
+pub fn local_label_prefix(definition: &MacroDefinition, expansion_id: usize) -> String {
+    format!(".__{}_{}_", definition.name, expansion_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preprocessor::macros::{expand_macro_line, local_label_prefix, parse_macro_header};
+
+    fn tokens(tt: &[&str]) -> Vec<String> {
+        tt.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn header_parsed() {
+        let def = parse_macro_header(&tokens(&["push2", "a", ",", "b"]), 0)
+            .expect("Header parsing failed");
+        assert_eq!(def.name, "push2");
+        assert_eq!(def.params, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn header_rejects_duplicate_params() {
+        assert!(parse_macro_header(&tokens(&["m", "a", ",", "a"]), 0).is_err());
+        assert!(parse_macro_header(&tokens(&["m", "a", ","]), 0).is_err());
+        assert!(parse_macro_header(&tokens(&["ADD"]), 0).is_err());
+    }
+
+    #[test]
+    fn arguments_substituted() {
+        let def = parse_macro_header(&tokens(&["m", "reg", ",", "val"]), 0)
+            .expect("Header parsing failed");
+        let args = vec!["r3".to_string(), "10".to_string()];
+        let prefix = local_label_prefix(&def, 1);
+        let line = expand_macro_line("%%loop: ADD reg, #val ; reg stays", &def, &args, &prefix);
+        assert_eq!(line, ".__m_1_loop: ADD r3, #10 ; reg stays");
+    }
+
+    #[test]
+    fn escaped_quotes_stay_in_string() {
+        let def = parse_macro_header(&tokens(&["m", "reg"]), 0).expect("Header parsing failed");
+        let args = vec!["r3".to_string()];
+        let line = expand_macro_line(
+            "DB \"a\\\"reg\", reg",
+            &def,
+            &args,
+            &local_label_prefix(&def, 1),
+        );
+        assert_eq!(line, "DB \"a\\\"reg\", r3");
+    }
+
+    #[test]
+    fn strings_left_alone() {
+        let def = parse_macro_header(&tokens(&["m", "reg"]), 0).expect("Header parsing failed");
+        let args = vec!["r1".to_string()];
+        let line = expand_macro_line("DB \"reg\", reg", &def, &args, &local_label_prefix(&def, 1));
+        assert_eq!(line, "DB \"reg\", r1");
+    }
+}
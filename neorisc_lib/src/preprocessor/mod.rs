@@ -0,0 +1,707 @@
+pub mod conditionals;
+pub mod macros;
+pub mod repeat;
+
+use crate::error::LangPreprocessError;
+use crate::lang::LangCommand;
+use crate::parser::line_tokenizer::tokenize_source_line;
+use crate::preprocessor::conditionals::{
+    evaluate_condition, evaluate_defined, is_conditional_command, parse_definition,
+    ConditionalFrame,
+};
+use crate::preprocessor::macros::{
+    expand_macro_line, local_label_prefix, parse_macro_arguments, parse_macro_header,
+    MacroDefinition,
+};
+use crate::preprocessor::repeat::{parse_repeat_count, split_times_line, RepeatBlock};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone)]
+pub struct PreprocessorConfig {
+    pub max_macro_depth: usize,
+    pub defines: HashMap<String, u8>,
+}
+
+impl Default for PreprocessorConfig {
+    fn default() -> Self {
+        Self {
+            max_macro_depth: 16,
+            defines: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MacroExpansion {
+    pub name: String,
+    pub defined_at: usize,
+    pub invoked_at: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LineOrigin {
+    pub line: usize,
+    pub expansions: Vec<MacroExpansion>,
+}
+
+impl LineOrigin {
+    pub fn new(line: usize) -> Self {
+        Self {
+            line,
+            expansions: vec![],
+        }
+    }
+
+    pub fn source_line(&self) -> usize {
+        self.expansions
+            .first()
+            .map_or(self.line, |expansion| expansion.invoked_at)
+    }
+}
+
+impl fmt::Display for LineOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line + 1)?;
+        for expansion in self.expansions.iter().rev() {
+            write!(
+                f,
+                " in macro {} (defined on line {}, invoked on line {})",
+                expansion.name,
+                expansion.defined_at + 1,
+                expansion.invoked_at + 1
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreprocessedLine {
+    pub text: String,
+    pub origin: LineOrigin,
+}
+
+#[derive(Debug)]
+pub struct PreprocessingError {
+    pub origin: LineOrigin,
+    pub error: LangPreprocessError,
+}
+
+pub struct LangPreprocessingArtifact {
+    pub lines: Vec<PreprocessedLine>,
+    pub errors: Vec<PreprocessingError>,
+}
+
+struct PreprocessorContext<'cfg> {
+    config: &'cfg PreprocessorConfig,
+    macros: HashMap<String, MacroDefinition>,
+    recording: Option<MacroDefinition>,
+    repeating: Option<RepeatBlock>,
+    expansion_count: usize,
+    symbols: HashMap<String, u8>,
+    conditionals: Vec<ConditionalFrame>,
+    conditional_floor: usize,
+    lines: Vec<PreprocessedLine>,
+    errors: Vec<PreprocessingError>,
+}
+
+impl<'cfg> PreprocessorContext<'cfg> {
+    fn is_active(&self) -> bool {
+        self.conditionals
+            .last()
+            .map_or(true, |frame| frame.is_active())
+    }
+}
+
+fn split_label(tokens: &[String]) -> (Option<&str>, &[String]) {
+    if tokens.len() >= 2 && tokens[1] == ":" {
+        (Some(&tokens[0]), &tokens[2..])
+    } else {
+        (None, tokens)
+    }
+}
+
+fn push_error<'cfg>(
+    mut ctx: PreprocessorContext<'cfg>,
+    origin: &LineOrigin,
+    error: LangPreprocessError,
+) -> PreprocessorContext<'cfg> {
+    ctx.errors.push(PreprocessingError {
+        origin: origin.clone(),
+        error,
+    });
+
+    ctx
+}
+
+fn close_conditionals(mut ctx: PreprocessorContext, floor: usize) -> PreprocessorContext {
+    while ctx.conditionals.len() > floor {
+        let frame = ctx.conditionals.pop().expect("Conditional stack is empty");
+        ctx = push_error(
+            ctx,
+            &frame.opened_at,
+            LangPreprocessError::UnterminatedConditional,
+        );
+    }
+
+    ctx
+}
+
+fn handle_conditional<'cfg>(
+    mut ctx: PreprocessorContext<'cfg>,
+    line: &PreprocessedLine,
+    command: &LangCommand,
+    args: &[String],
+) -> PreprocessorContext<'cfg> {
+    let active = ctx.is_active();
+    let has_open_frame = ctx.conditionals.len() > ctx.conditional_floor;
+
+    match command {
+        LangCommand::ELSE => match ctx.conditionals.last_mut() {
+            Some(frame) if has_open_frame && !frame.in_else => frame.in_else = true,
+            Some(_) if has_open_frame => {
+                ctx = push_error(ctx, &line.origin, LangPreprocessError::DuplicateElse)
+            }
+            _ => ctx = push_error(ctx, &line.origin, LangPreprocessError::UnexpectedElse),
+        },
+        LangCommand::ENDIF => {
+            if has_open_frame {
+                ctx.conditionals.pop();
+            } else {
+                ctx = push_error(ctx, &line.origin, LangPreprocessError::UnexpectedEndif);
+            }
+        }
+        _ => {
+            let result = if !active {
+                Ok(false)
+            } else if command == &LangCommand::IF {
+                evaluate_condition(args, &ctx.symbols)
+            } else {
+                evaluate_defined(args, &ctx.symbols)
+                    .map(|defined| defined == (command == &LangCommand::IFDEF))
+            };
+            let taken = match result {
+                Ok(taken) => taken,
+                Err(err) => {
+                    ctx = push_error(ctx, &line.origin, err);
+                    false
+                }
+            };
+            ctx.conditionals.push(ConditionalFrame {
+                opened_at: line.origin.clone(),
+                parent_active: active,
+                taken,
+                in_else: false,
+            });
+        }
+    }
+
+    ctx
+}
+
+fn handle_recorded_line<'cfg>(
+    mut ctx: PreprocessorContext<'cfg>,
+    line: PreprocessedLine,
+    command: Option<LangCommand>,
+) -> PreprocessorContext<'cfg> {
+    match command {
+        Some(LangCommand::ENDM) => {
+            let definition = ctx.recording.take().expect("Not recording a macro");
+            if ctx.macros.contains_key(&definition.name) {
+                let origin = LineOrigin::new(definition.defined_at);
+                return push_error(ctx, &origin, LangPreprocessError::DuplicateMacro);
+            }
+            ctx.macros.insert(definition.name.clone(), definition);
+        }
+        Some(LangCommand::MACRO) => {
+            ctx = push_error(
+                ctx,
+                &line.origin,
+                LangPreprocessError::NestedMacroDefinition,
+            );
+        }
+        _ => {
+            ctx.recording
+                .as_mut()
+                .expect("Not recording a macro")
+                .body
+                .push(line);
+        }
+    }
+
+    ctx
+}
+
+fn handle_macro_invocation<'cfg>(
+    mut ctx: PreprocessorContext<'cfg>,
+    line: &PreprocessedLine,
+    label: Option<&str>,
+    definition: MacroDefinition,
+    arg_tokens: &[String],
+    depth: usize,
+) -> PreprocessorContext<'cfg> {
+    if depth >= ctx.config.max_macro_depth {
+        return push_error(ctx, &line.origin, LangPreprocessError::MacroNestingTooDeep);
+    }
+
+    let args = match parse_macro_arguments(&definition, arg_tokens) {
+        Ok(args) => args,
+        Err(err) => return push_error(ctx, &line.origin, err),
+    };
+
+    if let Some(label) = label {
+        ctx.lines.push(PreprocessedLine {
+            text: format!("{}:", label),
+            origin: line.origin.clone(),
+        });
+    }
+
+    ctx.expansion_count += 1;
+    let outer_floor = ctx.conditional_floor;
+    ctx.conditional_floor = ctx.conditionals.len();
+    let local_prefix = local_label_prefix(&definition, ctx.expansion_count);
+    let mut expansions = line.origin.expansions.clone();
+    expansions.push(MacroExpansion {
+        name: definition.name.clone(),
+        defined_at: definition.defined_at,
+        invoked_at: line.origin.line,
+    });
+
+    for body_line in definition.body.iter() {
+        let expanded = PreprocessedLine {
+            text: expand_macro_line(&body_line.text, &definition, &args, &local_prefix),
+            origin: LineOrigin {
+                line: body_line.origin.line,
+                expansions: expansions.clone(),
+            },
+        };
+        ctx = process_line(ctx, expanded, depth + 1);
+    }
+
+    let floor = ctx.conditional_floor;
+    ctx = close_conditionals(ctx, floor);
+    ctx.conditional_floor = outer_floor;
+    ctx = close_repeat(ctx);
+
+    ctx
+}
+
+fn close_repeat(mut ctx: PreprocessorContext) -> PreprocessorContext {
+    if let Some(block) = ctx.repeating.take() {
+        ctx = push_error(
+            ctx,
+            &block.opened_at,
+            LangPreprocessError::UnterminatedRepeat,
+        );
+    }
+
+    ctx
+}
+
+fn handle_repeated_line<'cfg>(
+    mut ctx: PreprocessorContext<'cfg>,
+    line: PreprocessedLine,
+    command: Option<LangCommand>,
+    depth: usize,
+) -> PreprocessorContext<'cfg> {
+    let block = ctx
+        .repeating
+        .as_mut()
+        .expect("Not recording a repeat block");
+    match command {
+        Some(LangCommand::REPT) => {
+            block.nesting += 1;
+            block.body.push(line);
+        }
+        Some(LangCommand::ENDR) if block.nesting > 0 => {
+            block.nesting -= 1;
+            block.body.push(line);
+        }
+        Some(LangCommand::ENDR) => {
+            let block = ctx.repeating.take().expect("Not recording a repeat block");
+            for _ in 0..block.count {
+                let outer_floor = ctx.conditional_floor;
+                ctx.conditional_floor = ctx.conditionals.len();
+                for body_line in block.body.iter() {
+                    ctx = process_line(ctx, body_line.clone(), depth);
+                }
+                let floor = ctx.conditional_floor;
+                ctx = close_conditionals(ctx, floor);
+                ctx.conditional_floor = outer_floor;
+                ctx = close_repeat(ctx);
+            }
+        }
+        _ => block.body.push(line),
+    }
+
+    ctx
+}
+
+fn handle_times<'cfg>(
+    mut ctx: PreprocessorContext<'cfg>,
+    line: PreprocessedLine,
+    depth: usize,
+) -> PreprocessorContext<'cfg> {
+    let times = match split_times_line(&line.text) {
+        Some(times) => times,
+        None => return push_error(ctx, &line.origin, LangPreprocessError::InvalidRepeatCount),
+    };
+    let count = match parse_repeat_count(&[times.count], &ctx.symbols) {
+        Ok(count) => count,
+        Err(err) => return push_error(ctx, &line.origin, err),
+    };
+
+    if let Some(label) = times.label {
+        ctx.lines.push(PreprocessedLine {
+            text: format!("{}:", label),
+            origin: line.origin.clone(),
+        });
+    }
+    for _ in 0..count {
+        let repeated = PreprocessedLine {
+            text: times.line.clone(),
+            origin: line.origin.clone(),
+        };
+        ctx = process_line(ctx, repeated, depth);
+    }
+
+    ctx
+}
+
+fn process_line<'cfg>(
+    mut ctx: PreprocessorContext<'cfg>,
+    line: PreprocessedLine,
+    depth: usize,
+) -> PreprocessorContext<'cfg> {
+    let tokens = match tokenize_source_line(&line.text) {
+        Ok(tokens) => tokens,
+        Err(_) => {
+            ctx.lines.push(line);
+            return ctx;
+        }
+    };
+    let (label, rest) = split_label(&tokens);
+    let command = rest
+        .first()
+        .and_then(|token| LangCommand::from_string(token));
+
+    if ctx.recording.is_some() {
+        return handle_recorded_line(ctx, line, command);
+    }
+
+    if ctx.repeating.is_some() {
+        return handle_repeated_line(ctx, line, command, depth);
+    }
+
+    if let Some(conditional) = command.as_ref().filter(|cmd| is_conditional_command(cmd)) {
+        if let (Some(label), true) = (label, ctx.is_active()) {
+            ctx.lines.push(PreprocessedLine {
+                text: format!("{}:", label),
+                origin: line.origin.clone(),
+            });
+        }
+        return handle_conditional(ctx, &line, conditional, &rest[1..]);
+    }
+
+    if !ctx.is_active() {
+        return ctx;
+    }
+
+    match command {
+        Some(LangCommand::MACRO) => match parse_macro_header(&rest[1..], line.origin.line) {
+            Ok(definition) => ctx.recording = Some(definition),
+            Err(err) => ctx = push_error(ctx, &line.origin, err),
+        },
+        Some(LangCommand::ENDM) => {
+            ctx = push_error(ctx, &line.origin, LangPreprocessError::UnexpectedEndm);
+        }
+        Some(LangCommand::REPT) => match parse_repeat_count(&rest[1..], &ctx.symbols) {
+            Ok(count) => {
+                if let Some(label) = label {
+                    ctx.lines.push(PreprocessedLine {
+                        text: format!("{}:", label),
+                        origin: line.origin.clone(),
+                    });
+                }
+                ctx.repeating = Some(RepeatBlock {
+                    count,
+                    body: vec![],
+                    nesting: 0,
+                    opened_at: line.origin,
+                });
+            }
+            Err(err) => ctx = push_error(ctx, &line.origin, err),
+        },
+        Some(LangCommand::ENDR) => {
+            ctx = push_error(ctx, &line.origin, LangPreprocessError::UnexpectedEndr);
+        }
+        Some(LangCommand::TIMES) => return handle_times(ctx, line, depth),
+        Some(LangCommand::DEF) => {
+            if let Some((name, value)) = parse_definition(&rest[1..], &ctx.symbols) {
+                ctx.symbols.insert(name, value);
+            }
+            ctx.lines.push(line);
+        }
+        _ => {
+            if let Some(definition) = rest.first().and_then(|name| ctx.macros.get(name)) {
+                let definition = definition.clone();
+                return handle_macro_invocation(ctx, &line, label, definition, &rest[1..], depth);
+            }
+            ctx.lines.push(line);
+        }
+    }
+
+    ctx
+}
+
+pub fn preprocess_source(source: &str, config: &PreprocessorConfig) -> LangPreprocessingArtifact {
+    let mut ctx = PreprocessorContext {
+        config,
+        macros: HashMap::new(),
+        recording: None,
+        repeating: None,
+        expansion_count: 0,
+        symbols: config.defines.clone(),
+        conditionals: vec![],
+        conditional_floor: 0,
+        lines: vec![],
+        errors: vec![],
+    };
+
+    for (ii, text) in source.lines().enumerate() {
+        let line = PreprocessedLine {
+            text: text.to_string(),
+            origin: LineOrigin::new(ii),
+        };
+        ctx = process_line(ctx, line, 0);
+    }
+
+    if let Some(definition) = ctx.recording.take() {
+        let origin = LineOrigin::new(definition.defined_at);
+        ctx = push_error(ctx, &origin, LangPreprocessError::UnterminatedMacro);
+    }
+    ctx = close_repeat(ctx);
+    ctx = close_conditionals(ctx, 0);
+
+    LangPreprocessingArtifact {
+        lines: ctx.lines,
+        errors: ctx.errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::LangPreprocessError;
+    use crate::preprocessor::{preprocess_source, LangPreprocessingArtifact, PreprocessorConfig};
+
+    #[test]
+    fn macro_expanded() {
+        let artifact = preprocess_source(
+            "MACRO add16 lo, hi\nADD lo, #1\nADD hi, #0\nENDM\nadd16 r0, r1",
+            &PreprocessorConfig::default(),
+        );
+        assert!(artifact.errors.is_empty());
+        let texts: Vec<&str> = artifact.lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec!["ADD r0, #1", "ADD r1, #0"]);
+        assert_eq!(artifact.lines[0].origin.line, 1);
+        assert_eq!(artifact.lines[0].origin.expansions[0].invoked_at, 4);
+        assert_eq!(artifact.lines[0].origin.source_line(), 4);
+    }
+
+    #[test]
+    fn local_labels_unique() {
+        let artifact = preprocess_source(
+            "MACRO wait\n%%loop: JMP %%loop\nENDM\nwait\nwait",
+            &PreprocessorConfig::default(),
+        );
+        assert!(artifact.errors.is_empty());
+        assert_eq!(artifact.lines.len(), 2);
+        assert_ne!(artifact.lines[0].text, artifact.lines[1].text);
+        assert_eq!(artifact.lines[0].text, ".__wait_1_loop: JMP .__wait_1_loop");
+    }
+
+    #[test]
+    fn label_on_invocation_kept() {
+        let artifact = preprocess_source(
+            "MACRO nop1\nMOV r0, r0\nENDM\nstart: nop1",
+            &PreprocessorConfig::default(),
+        );
+        let texts: Vec<&str> = artifact.lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec!["start:", "MOV r0, r0"]);
+    }
+
+    #[test]
+    fn recursion_limited() {
+        let artifact = preprocess_source(
+            "MACRO inner\nMOV r0, r0\nENDM\nMACRO outer\ninner\nouter\nENDM\nouter",
+            &PreprocessorConfig {
+                max_macro_depth: 4,
+                ..PreprocessorConfig::default()
+            },
+        );
+        assert_eq!(artifact.errors.len(), 2);
+        assert!(artifact
+            .errors
+            .iter()
+            .all(|err| matches!(err.error, LangPreprocessError::MacroNestingTooDeep)));
+        assert_eq!(artifact.lines.len(), 3);
+        assert_eq!(artifact.errors[0].origin.expansions.len(), 4);
+    }
+
+    #[test]
+    fn unbalanced_definitions_reported() {
+        let artifact =
+            preprocess_source("ENDM\nMACRO m\nMOV r0, r0", &PreprocessorConfig::default());
+        assert_eq!(artifact.errors.len(), 2);
+        assert!(matches!(
+            artifact.errors[0].error,
+            LangPreprocessError::UnexpectedEndm
+        ));
+        assert!(matches!(
+            artifact.errors[1].error,
+            LangPreprocessError::UnterminatedMacro
+        ));
+        assert_eq!(artifact.errors[1].origin.line, 1);
+    }
+
+    #[test]
+    fn argument_count_checked() {
+        let artifact = preprocess_source(
+            "MACRO m a, b\nMOV a, b\nENDM\nm r0",
+            &PreprocessorConfig::default(),
+        );
+        assert!(matches!(
+            artifact.errors[0].error,
+            LangPreprocessError::MacroArgumentCountMismatch {
+                expected: 2,
+                found: 1
+            }
+        ));
+        assert_eq!(artifact.errors[0].origin.to_string(), "line 4".to_string());
+    }
+
+    fn texts(artifact: &LangPreprocessingArtifact) -> Vec<&str> {
+        artifact.lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn conditionals_use_def_symbols() {
+        let artifact = preprocess_source(
+            "DEF BOARD 1\nIF BOARD\nMOV r0, #1\nELSE\nMOV r0, #2\nENDIF",
+            &PreprocessorConfig::default(),
+        );
+        assert!(artifact.errors.is_empty());
+        assert_eq!(texts(&artifact), vec!["DEF BOARD 1", "MOV r0, #1"]);
+    }
+
+    #[test]
+    fn conditionals_use_config_defines() {
+        let mut config = PreprocessorConfig::default();
+        config.defines.insert("SIM".to_string(), 1);
+        let source = "IFDEF SIM\nMOV r0, #1\nENDIF\nIFNDEF SIM\nMOV r0, #2\nENDIF";
+
+        let artifact = preprocess_source(source, &config);
+        assert!(artifact.errors.is_empty());
+        assert_eq!(texts(&artifact), vec!["MOV r0, #1"]);
+
+        let artifact = preprocess_source(source, &PreprocessorConfig::default());
+        assert_eq!(texts(&artifact), vec!["MOV r0, #2"]);
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let artifact = preprocess_source(
+            "IF 0\nIF UNDEFINED\nMOV r0, #1\nELSE\nMOV r0, #2\nENDIF\nELSE\nIF 1\nMOV r0, #3\nENDIF\nENDIF",
+            &PreprocessorConfig::default(),
+        );
+        assert!(artifact.errors.is_empty());
+        assert_eq!(texts(&artifact), vec!["MOV r0, #3"]);
+    }
+
+    #[test]
+    fn unbalanced_conditionals_reported() {
+        let artifact = preprocess_source(
+            "ELSE\nENDIF\nIF 1\nELSE\nELSE\nENDIF\nIFDEF X",
+            &PreprocessorConfig::default(),
+        );
+        let errors: Vec<&LangPreprocessError> = artifact.errors.iter().map(|e| &e.error).collect();
+        assert!(matches!(errors[0], LangPreprocessError::UnexpectedElse));
+        assert!(matches!(errors[1], LangPreprocessError::UnexpectedEndif));
+        assert!(matches!(errors[2], LangPreprocessError::DuplicateElse));
+        assert!(matches!(
+            errors[3],
+            LangPreprocessError::UnterminatedConditional
+        ));
+        assert_eq!(artifact.errors[3].origin.line, 6);
+    }
+
+    #[test]
+    fn conditionals_inside_macros() {
+        let artifact = preprocess_source(
+            "MACRO m v\nIF v\nMOV r0, #v\nENDIF\nENDM\nm 0\nm 5\nMACRO bad\nIF 1\nENDM\nbad\nENDIF",
+            &PreprocessorConfig::default(),
+        );
+        assert_eq!(texts(&artifact), vec!["MOV r0, #5"]);
+        assert_eq!(artifact.errors.len(), 2);
+        assert!(matches!(
+            artifact.errors[0].error,
+            LangPreprocessError::UnterminatedConditional
+        ));
+        assert!(matches!(
+            artifact.errors[1].error,
+            LangPreprocessError::UnexpectedEndif
+        ));
+    }
+
+    #[test]
+    fn repeat_blocks_expanded() {
+        let artifact = preprocess_source(
+            "DEF N 2\nREPT N\nDB 1\nREPT 2\nDB 2\nENDR\nENDR\nTIMES 3 DB 0",
+            &PreprocessorConfig::default(),
+        );
+        assert!(artifact.errors.is_empty());
+        assert_eq!(
+            texts(&artifact),
+            vec!["DEF N 2", "DB 1", "DB 2", "DB 2", "DB 1", "DB 2", "DB 2", "DB 0", "DB 0", "DB 0"]
+        );
+        assert_eq!(artifact.lines[2].origin.line, 4);
+    }
+
+    #[test]
+    fn repeat_with_conditionals_and_macros() {
+        let artifact = preprocess_source(
+            "MACRO twice v\nREPT 2\nDB v\nENDR\nENDM\nIF 0\nREPT 5\nDB 9\nENDR\nENDIF\ntwice 7",
+            &PreprocessorConfig::default(),
+        );
+        assert!(artifact.errors.is_empty());
+        assert_eq!(texts(&artifact), vec!["DB 7", "DB 7"]);
+    }
+
+    #[test]
+    fn unbalanced_repeats_reported() {
+        let artifact = preprocess_source(
+            "ENDR\nREPT X\nTIMES DB 0\nREPT 2\nDB 0",
+            &PreprocessorConfig::default(),
+        );
+        let errors: Vec<&LangPreprocessError> = artifact.errors.iter().map(|e| &e.error).collect();
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(errors[0], LangPreprocessError::UnexpectedEndr));
+        assert!(matches!(errors[1], LangPreprocessError::InvalidRepeatCount));
+        assert!(matches!(errors[2], LangPreprocessError::InvalidRepeatCount));
+        assert!(matches!(errors[3], LangPreprocessError::UnterminatedRepeat));
+    }
+
+    #[test]
+    fn origin_shows_definition_and_invocation() {
+        let artifact = preprocess_source(
+            "MACRO m\nMOV r0, r0\nENDM\nm",
+            &PreprocessorConfig::default(),
+        );
+        assert_eq!(
+            artifact.lines[0].origin.to_string(),
+            "line 2 in macro m (defined on line 1, invoked on line 4)"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use crate::error::LangPreprocessError;
+use crate::preprocessor::conditionals::resolve_value;
+use crate::preprocessor::{LineOrigin, PreprocessedLine};
+use regex::Regex;
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref TIMES_REGEX: Regex =
+        Regex::new(r"^\s*(?:([^\s:;]+)\s*:)?\s*TIMES\s+([^\s;]+)\s+(.*)$").unwrap();
+}
+
+pub struct RepeatBlock {
+    pub count: u8,
+    pub body: Vec<PreprocessedLine>,
+    pub nesting: usize,
+    pub opened_at: LineOrigin,
+}
+
+pub struct TimesLine {
+    pub label: Option<String>,
+    pub count: String,
+    pub line: String,
+}
+
+pub fn parse_repeat_count(
+    tokens: &[String],
+    symbols: &HashMap<String, u8>,
+) -> Result<u8, LangPreprocessError> {
+    match tokens {
+        [count] => {
+            resolve_value(count, symbols).map_err(|_| LangPreprocessError::InvalidRepeatCount)
+        }
+        _ => Err(LangPreprocessError::InvalidRepeatCount),
+    }
+}
+
+pub fn split_times_line(text: &str) -> Option<TimesLine> {
+    let captures = TIMES_REGEX.captures(text)?;
+
+    Some(TimesLine {
+        label: captures.get(1).map(|label| label.as_str().to_string()),
+        count: captures[2].to_string(),
+        line: captures[3].to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preprocessor::repeat::split_times_line;
+
+    #[test]
+    fn times_line_split() {
+        let times = split_times_line("table: TIMES 4 DB 0x00 ; zeros").expect("Not a TIMES line");
+        assert_eq!(times.label.as_deref(), Some("table"));
+        assert_eq!(times.count, "4");
+        assert_eq!(times.line, "DB 0x00 ; zeros");
+        assert!(split_times_line("MOV r0, #1").is_none());
+    }
+}
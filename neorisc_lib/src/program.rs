@@ -0,0 +1,202 @@
+use crate::compiler::layout::Section;
+use crate::isa::Instruction;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgramSection {
+    Code,
+    Data,
+    Constant,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProgramSymbol {
+    pub section: ProgramSection,
+    pub value: usize,
+}
+
+impl From<Section> for ProgramSection {
+    fn from(section: Section) -> Self {
+        match section {
+            Section::Code => ProgramSection::Code,
+            Section::Data => ProgramSection::Data,
+        }
+    }
+}
+
+// One used code word, `line` is 1-based like the text listing.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct DisassembledWord {
+    pub address: usize,
+    pub word: u16,
+    pub mnemonic: String,
+    pub labels: Vec<String>,
+    pub line: Option<usize>,
+}
+
+// The RiscJson format: full code and data images, with the source line
+// (0-based) every used code address was assembled from.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RiscProgram {
+    pub code: Vec<u16>,
+    pub data: Vec<u8>,
+    pub source_map: BTreeMap<usize, usize>,
+    pub symbols: BTreeMap<String, ProgramSymbol>,
+}
+
+impl RiscProgram {
+    pub fn new(code_size: usize, data_size: usize) -> Self {
+        Self {
+            code: vec![0; code_size],
+            data: vec![0; data_size],
+            source_map: BTreeMap::new(),
+            symbols: BTreeMap::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Program serialization failed")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    // Code words big-endian, followed by the data image.
+    pub fn to_binary(&self) -> Vec<u8> {
+        self.code
+            .iter()
+            .flat_map(|word| word.to_be_bytes().to_vec())
+            .chain(self.data.iter().copied())
+            .collect()
+    }
+
+    pub fn from_binary(bytes: &[u8], code_size: usize) -> Option<Self> {
+        if bytes.len() < code_size * 2 {
+            return None;
+        }
+
+        let (code, data) = bytes.split_at(code_size * 2);
+        let mut program = Self::new(0, 0);
+        program.code = code
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect();
+        program.data = data.to_vec();
+
+        Some(program)
+    }
+
+    pub fn code_memh(&self) -> String {
+        self.code.iter().fold(String::new(), |mut out, word| {
+            writeln!(out, "{:04X}", word).expect("Write to string failed");
+            out
+        })
+    }
+
+    pub fn data_memh(&self) -> String {
+        self.data.iter().fold(String::new(), |mut out, byte| {
+            writeln!(out, "{:02X}", byte).expect("Write to string failed");
+            out
+        })
+    }
+
+    pub fn used_code_size(&self) -> usize {
+        match self.source_map.keys().next_back() {
+            Some(last) => last + 1,
+            None => self
+                .code
+                .iter()
+                .rposition(|word| *word != 0)
+                .map_or(0, |last| last + 1),
+        }
+    }
+
+    pub fn labels_at(&self, address: usize) -> Vec<&str> {
+        self.symbols
+            .iter()
+            .filter(|(_, symbol)| symbol.section == ProgramSection::Code && symbol.value == address)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn disassembly(&self) -> Vec<DisassembledWord> {
+        self.code
+            .iter()
+            .enumerate()
+            .take(self.used_code_size())
+            .map(|(address, word)| DisassembledWord {
+                address,
+                word: *word,
+                mnemonic: Instruction::decode(*word)
+                    .map_or_else(|| format!("DW 0x{:04X}", word), |instr| instr.to_string()),
+                labels: self
+                    .labels_at(address)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                line: self.source_map.get(&address).map(|line| line + 1),
+            })
+            .collect()
+    }
+
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        for word in self.disassembly() {
+            for label in word.labels.iter() {
+                writeln!(out, "{}:", label).expect("Write to string failed");
+            }
+            match word.line {
+                Some(line) => writeln!(
+                    out,
+                    "    {:02X}: {:04X}    {:<20} ; line {}",
+                    word.address, word.word, word.mnemonic, line
+                ),
+                None => writeln!(
+                    out,
+                    "    {:02X}: {:04X}    {}",
+                    word.address, word.word, word.mnemonic
+                ),
+            }
+            .expect("Write to string failed");
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::RiscProgram;
+
+    #[test]
+    fn binary_round_trips() {
+        let mut program = RiscProgram::new(4, 2);
+        program.code[0] = 0x2064;
+        program.code[3] = 0xC003;
+        program.data[1] = 0xAB;
+
+        let binary = program.to_binary();
+        assert_eq!(binary.len(), 10);
+        let loaded = RiscProgram::from_binary(&binary, 4).expect("Binary load failed");
+        assert_eq!(loaded.code, program.code);
+        assert_eq!(loaded.data, program.data);
+        assert_eq!(program.code_memh(), "2064\n0000\n0000\nC003\n");
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let mut program = RiscProgram::new(2, 1);
+        program.code[0] = 0xFF00;
+        program.source_map.insert(0, 3);
+        let loaded = RiscProgram::from_json(&program.to_json()).expect("Json load failed");
+        assert_eq!(loaded, program);
+        assert!(program.disassemble().contains("NOP"));
+        let listing = program.disassembly();
+        assert_eq!(listing.len(), 1);
+        assert_eq!((listing[0].word, listing[0].line), (0xFF00, Some(4)));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::testing::SuiteReport;
+use std::fmt::Write;
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|chr| match chr {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            chr if chr.is_control() && chr != '\n' && chr != '\t' => {
+                format!("&#x{:X};", chr as u32)
+            }
+            chr => chr.to_string(),
+        })
+        .collect()
+}
+
+// JUnit XML as read by CI servers, one testsuite per test file.
+pub fn junit_xml(suites: &[SuiteReport]) -> String {
+    let tests: usize = suites.iter().map(|suite| suite.cases.len()).sum();
+    let errors = |suite: &SuiteReport| {
+        suite
+            .cases
+            .iter()
+            .filter(|case| case.error.is_some())
+            .count()
+    };
+    let total_errors: usize = suites.iter().map(errors).sum();
+    let failures = suites.iter().map(SuiteReport::failed).sum::<usize>() - total_errors;
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuites name=\"neorisc\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        tests, failures, total_errors
+    )
+    .expect("Write to string");
+
+    for suite in suites.iter() {
+        let errors = errors(suite);
+        writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.6}\">",
+            escape(&suite.name),
+            suite.cases.len(),
+            suite.failed() - errors,
+            errors,
+            suite.seconds
+        )
+        .expect("Write to string");
+        for case in suite.cases.iter() {
+            write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\"",
+                escape(&case.name),
+                escape(&suite.name),
+                case.seconds
+            )
+            .expect("Write to string");
+            if case.passed() {
+                xml.push_str("/>\n");
+                continue;
+            }
+
+            xml.push_str(">\n");
+            if let Some(error) = &case.error {
+                writeln!(
+                    xml,
+                    "      <error message=\"{}\"/>",
+                    escape(error.lines().next().unwrap_or_default())
+                )
+                .expect("Write to string");
+            } else {
+                writeln!(
+                    xml,
+                    "      <failure message=\"{} mismatch(es)\">{}</failure>",
+                    case.failures.len(),
+                    escape(&case.failures.join("\n"))
+                )
+                .expect("Write to string");
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::junit::junit_xml;
+    use crate::testing::{CaseReport, SuiteReport};
+
+    #[test]
+    fn failures_reported() {
+        let case = |name: &str, failures: Vec<String>| CaseReport {
+            name: name.to_string(),
+            cycles: 10,
+            seconds: 0.5,
+            failures,
+            error: None,
+        };
+        let suite = SuiteReport {
+            name: "lab<1>".to_string(),
+            source: "lab.s".to_string(),
+            seconds: 1.0,
+            cases: vec![
+                case("ok", vec![]),
+                case("bad", vec!["r0: expected 0x01, got 0x02".to_string()]),
+            ],
+        };
+
+        let xml = junit_xml(&[suite]);
+        assert!(
+            xml.contains("<testsuites name=\"neorisc\" tests=\"2\" failures=\"1\" errors=\"0\">")
+        );
+        assert!(xml
+            .contains("<testsuite name=\"lab&lt;1&gt;\" tests=\"2\" failures=\"1\" errors=\"0\""));
+        assert!(
+            xml.contains("<testcase name=\"ok\" classname=\"lab&lt;1&gt;\" time=\"0.500000\"/>")
+        );
+        assert!(xml
+            .contains("<failure message=\"1 mismatch(es)\">r0: expected 0x01, got 0x02</failure>"));
+    }
+}
@@ -0,0 +1,605 @@
+pub mod junit;
+
+use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+use crate::debugger::Debugger;
+use crate::error::TestSpecError;
+use crate::interpreter::board::{
+    attach_lab_board, Buttons, Leds, SevenSegmentDisplay, Switches, BUTTON_COUNT,
+};
+use crate::interpreter::bus::Bus;
+use crate::interpreter::snapshot::Snapshot;
+use crate::interpreter::uart::{SerialBuffer, Uart, UART_ADDRESS, UART_REGISTER_COUNT};
+use crate::interpreter::RunOutcome;
+use crate::lang::LangLiteral;
+use crate::program::RiscProgram;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub const DEFAULT_TEST_CYCLES: u64 = 100_000;
+pub const TEST_FILE_SUFFIX: &str = ".test.toml";
+
+fn default_cycles() -> u64 {
+    DEFAULT_TEST_CYCLES
+}
+
+fn default_halts() -> bool {
+    true
+}
+
+// A byte, a byte sequence or the bytes of a string, stored from the given address on.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum MemoryValue {
+    Byte(u8),
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+impl MemoryValue {
+    pub fn bytes(&self) -> Vec<u8> {
+        match self {
+            MemoryValue::Byte(byte) => vec![*byte],
+            MemoryValue::Bytes(bytes) => bytes.clone(),
+            MemoryValue::Text(text) => text.as_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    #[serde(default)]
+    pub registers: BTreeMap<String, u8>,
+    #[serde(default)]
+    pub memory: BTreeMap<String, MemoryValue>,
+    // Keys z, c, n, v, ie and if.
+    #[serde(default)]
+    pub flags: BTreeMap<String, bool>,
+    pub leds: Option<u8>,
+    pub display: Option<String>,
+    pub uart_output: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: String,
+    pub max_cycles: Option<u64>,
+    // Snapshot to start from, relative to the test file; the other inputs are applied on top.
+    pub state: Option<PathBuf>,
+    #[serde(default)]
+    pub registers: BTreeMap<String, u8>,
+    #[serde(default)]
+    pub memory: BTreeMap<String, MemoryValue>,
+    pub switches: Option<u8>,
+    // Buttons held down from the start, bit n is BTn.
+    pub buttons: Option<u8>,
+    pub uart_input: Option<String>,
+    pub halts: Option<bool>,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+impl TestCase {
+    fn uses_board(&self) -> bool {
+        self.switches.is_some()
+            || self.buttons.is_some()
+            || self.expect.leds.is_some()
+            || self.expect.display.is_some()
+    }
+
+    fn uses_uart(&self) -> bool {
+        self.uart_input.is_some() || self.expect.uart_output.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestFile {
+    // Source path relative to the test file, defaults to the test file name with `.s`.
+    pub source: Option<String>,
+    #[serde(default = "default_cycles")]
+    pub max_cycles: u64,
+    // Whether cases must halt within their cycle budget unless they say otherwise.
+    #[serde(default = "default_halts")]
+    pub halts: bool,
+    #[serde(default, rename = "case")]
+    pub cases: Vec<TestCase>,
+}
+
+impl TestFile {
+    pub fn parse(text: &str) -> Result<Self, TestSpecError> {
+        toml::from_str(text).map_err(|err| TestSpecError::InvalidTestFile(err.to_string()))
+    }
+}
+
+fn suite_name(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    file_name
+        .strip_suffix(TEST_FILE_SUFFIX)
+        .or_else(|| file_name.strip_suffix(".toml"))
+        .unwrap_or(&file_name)
+        .to_string()
+}
+
+pub struct TestSuite {
+    pub name: String,
+    pub path: PathBuf,
+    pub source: PathBuf,
+    pub spec: TestFile,
+}
+
+impl TestSuite {
+    pub fn load(path: &Path) -> Result<Self, TestSpecError> {
+        let text = fs::read_to_string(path).map_err(|err| TestSpecError::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        })?;
+        let mut spec = TestFile::parse(&text)?;
+        let name = suite_name(path);
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        for case in spec.cases.iter_mut() {
+            case.state = case.state.take().map(|state| directory.join(state));
+        }
+        let source = match &spec.source {
+            Some(source) => directory.join(source),
+            None => directory.join(format!("{}.s", name)),
+        };
+
+        Ok(Self {
+            name,
+            path: path.to_path_buf(),
+            source,
+            spec,
+        })
+    }
+
+    pub fn run(&self, config: RiscCompilerConfig) -> SuiteReport {
+        let started = Instant::now();
+        let program = fs::read_to_string(&self.source)
+            .map_err(|err| format!("{}: {}", self.source.display(), err))
+            .and_then(|source| {
+                let mut compiler = RiscCompiler::new(source, config);
+                compiler.compile();
+                let diagnostics = compiler.diagnostics();
+                match compiler.program() {
+                    Some(program) if diagnostics.is_empty() => Ok(program.clone()),
+                    _ => Err(diagnostics
+                        .iter()
+                        .map(|diagnostic| diagnostic.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n")),
+                }
+            });
+
+        let cases = self
+            .spec
+            .cases
+            .iter()
+            .map(|case| match &program {
+                Ok(program) => run_case(program, case, &self.spec),
+                Err(error) => CaseReport::error(&case.name, error.clone()),
+            })
+            .collect();
+
+        SuiteReport {
+            name: self.name.clone(),
+            source: self.source.display().to_string(),
+            seconds: started.elapsed().as_secs_f64(),
+            cases,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseReport {
+    pub name: String,
+    pub cycles: u64,
+    pub seconds: f64,
+    // Mismatches between the expected and the final state.
+    pub failures: Vec<String>,
+    // Problems that kept the case from running.
+    pub error: Option<String>,
+}
+
+impl CaseReport {
+    fn error(name: &str, error: String) -> Self {
+        Self {
+            name: name.to_string(),
+            cycles: 0,
+            seconds: 0.0,
+            failures: vec![],
+            error: Some(error),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty() && self.error.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SuiteReport {
+    pub name: String,
+    pub source: String,
+    pub seconds: f64,
+    pub cases: Vec<CaseReport>,
+}
+
+impl SuiteReport {
+    // A test file that could not be loaded, reported as a single errored case.
+    pub fn load_failed(path: &Path, error: &TestSpecError) -> Self {
+        Self {
+            name: suite_name(path),
+            source: path.display().to_string(),
+            seconds: 0.0,
+            cases: vec![CaseReport::error("load", error.to_string())],
+        }
+    }
+
+    pub fn failed(&self) -> usize {
+        self.cases.iter().filter(|case| !case.passed()).count()
+    }
+}
+
+fn resolve_register(name: &str) -> Result<usize, TestSpecError> {
+    match LangLiteral::from_string(name) {
+        Ok(LangLiteral::Register(register)) => Ok(register as usize),
+        _ => Err(TestSpecError::UnknownRegister(name.to_string())),
+    }
+}
+
+fn resolve_address(debugger: &Debugger, location: &str) -> Result<u8, TestSpecError> {
+    debugger
+        .resolve_address(location)
+        .ok_or_else(|| TestSpecError::UnknownAddress(location.to_string()))
+}
+
+fn flag_value(debugger: &Debugger, name: &str) -> Result<bool, TestSpecError> {
+    let flags = debugger.interpreter.flags;
+    match name.to_lowercase().as_str() {
+        "z" => Ok(flags.z),
+        "c" => Ok(flags.c),
+        "n" => Ok(flags.n),
+        "v" => Ok(flags.v),
+        "ie" => Ok(flags.ie),
+        "if" => Ok(flags.if_),
+        _ => Err(TestSpecError::UnknownFlag(name.to_string())),
+    }
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("0x{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn prepare(debugger: &mut Debugger, case: &TestCase) -> Result<SerialBuffer, TestSpecError> {
+    let serial = SerialBuffer::new();
+    let snapshot = case.state.as_deref().map(Snapshot::load).transpose()?;
+    let has_device = |name: &str| {
+        snapshot
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.has_device(name))
+    };
+    let bus = &mut debugger.interpreter.bus;
+    if case.uses_board() || has_device("leds") {
+        attach_lab_board(bus).expect("Lab board address map overlaps");
+    }
+    if case.uses_uart() || has_device("uart") {
+        bus.attach(UART_ADDRESS, UART_REGISTER_COUNT, Uart::new(serial.clone()))
+            .expect("UART address overlaps");
+    }
+    // The program under test replaces the code saved in the snapshot.
+    if let Some(snapshot) = &snapshot {
+        snapshot.restore(&mut debugger.interpreter)?;
+        debugger.interpreter.code = debugger.program.code.clone();
+        debugger.interpreter.source_map = debugger.program.source_map.clone();
+    }
+
+    let bus = &mut debugger.interpreter.bus;
+    if let Some(switches) = case.switches {
+        bus.device_mut::<Switches>()
+            .expect("Switches missing")
+            .value = switches;
+    }
+    if let Some(pressed) = case.buttons {
+        let buttons = bus.device_mut::<Buttons>().expect("Buttons missing");
+        for index in 0..BUTTON_COUNT {
+            buttons.set_pressed(index, pressed & (1 << index) != 0);
+        }
+    }
+    if let Some(input) = &case.uart_input {
+        serial.push_input(input.as_bytes());
+    }
+
+    for (name, value) in case.registers.iter() {
+        debugger.interpreter.registers[resolve_register(name)?] = *value;
+    }
+    for (location, value) in case.memory.iter() {
+        let address = resolve_address(debugger, location)?;
+        for (offset, byte) in value.bytes().into_iter().enumerate() {
+            debugger
+                .interpreter
+                .bus
+                .write(address.wrapping_add(offset as u8), byte);
+        }
+    }
+
+    Ok(serial)
+}
+
+fn check(
+    debugger: &Debugger,
+    case: &TestCase,
+    serial: &SerialBuffer,
+) -> Result<Vec<String>, TestSpecError> {
+    let mut failures = vec![];
+    let expect = &case.expect;
+    let interp = &debugger.interpreter;
+
+    for (name, expected) in expect.registers.iter() {
+        let actual = interp.registers[resolve_register(name)?];
+        if actual != *expected {
+            failures.push(format!(
+                "{}: expected 0x{:02X}, got 0x{:02X}",
+                name, expected, actual
+            ));
+        }
+    }
+    for (name, expected) in expect.flags.iter() {
+        let actual = flag_value(debugger, name)?;
+        if actual != *expected {
+            failures.push(format!(
+                "flag {}: expected {}, got {}",
+                name.to_uppercase(),
+                *expected as u8,
+                actual as u8
+            ));
+        }
+    }
+    for (location, value) in expect.memory.iter() {
+        let address = resolve_address(debugger, location)?;
+        let expected = value.bytes();
+        let actual: Vec<u8> = (0..expected.len())
+            .map(|offset| interp.bus.peek(address.wrapping_add(offset as u8)))
+            .collect();
+        if actual != expected {
+            failures.push(format!(
+                "[{}] at 0x{:02X}: expected {}, got {}",
+                location,
+                address,
+                format_bytes(&expected),
+                format_bytes(&actual)
+            ));
+        }
+    }
+    if let Some(expected) = expect.leds {
+        let actual = interp.bus.device::<Leds>().map_or(0, |leds| leds.value);
+        if actual != expected {
+            failures.push(format!(
+                "leds: expected {:08b}, got {:08b}",
+                expected, actual
+            ));
+        }
+    }
+    if let Some(expected) = &expect.display {
+        let actual = interp
+            .bus
+            .device::<SevenSegmentDisplay>()
+            .map(SevenSegmentDisplay::text)
+            .unwrap_or_default();
+        if actual.trim() != expected.trim() {
+            failures.push(format!(
+                "display: expected {:?}, got {:?}",
+                expected, actual
+            ));
+        }
+    }
+    if let Some(expected) = &expect.uart_output {
+        let actual = String::from_utf8_lossy(&serial.output()).to_string();
+        if actual != *expected {
+            failures.push(format!(
+                "uart output: expected {:?}, got {:?}",
+                expected, actual
+            ));
+        }
+    }
+
+    Ok(failures)
+}
+
+pub fn run_case(program: &RiscProgram, case: &TestCase, spec: &TestFile) -> CaseReport {
+    let started = Instant::now();
+    let mut debugger = Debugger::new(program.clone(), None);
+    let serial = match prepare(&mut debugger, case) {
+        Ok(serial) => serial,
+        Err(err) => return CaseReport::error(&case.name, err.to_string()),
+    };
+
+    let budget = case.max_cycles.unwrap_or(spec.max_cycles);
+    let mut failures = vec![];
+    match debugger.interpreter.run(budget) {
+        Ok(RunOutcome::Halted) => {}
+        Ok(RunOutcome::CycleLimit) if case.halts.unwrap_or(spec.halts) => {
+            failures.push(format!("did not halt within {} cycles", budget))
+        }
+        Ok(RunOutcome::CycleLimit) => {}
+        Err(err) => failures.push(format!("fault: {}", err)),
+    }
+
+    let (failures, error) = match check(&debugger, case, &serial) {
+        Ok(mismatches) => {
+            failures.extend(mismatches);
+            (failures, None)
+        }
+        Err(err) => (failures, Some(err.to_string())),
+    };
+
+    CaseReport {
+        name: case.name.clone(),
+        cycles: debugger.interpreter.cycles,
+        seconds: started.elapsed().as_secs_f64(),
+        failures,
+        error,
+    }
+}
+
+// Test files among the paths; directories are searched recursively and `.s` files
+// stand for the test file next to them.
+pub fn discover(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut found = vec![];
+    for path in paths.iter() {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path())
+                })
+                .into_iter()
+                .flatten()
+                .filter(|entry| {
+                    entry.is_dir() || entry.to_string_lossy().ends_with(TEST_FILE_SUFFIX)
+                })
+                .collect();
+            entries.sort();
+            found.extend(discover(&entries));
+        } else if path.extension().is_some_and(|extension| extension == "s") {
+            found.push(path.with_extension(&TEST_FILE_SUFFIX[1..]));
+        } else {
+            found.push(path.clone());
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{RiscCompiler, RiscCompilerConfig};
+    use crate::interpreter::snapshot::Snapshot;
+    use crate::interpreter::RiscInterpreter;
+    use crate::testing::{run_case, SuiteReport, TestFile, TestSuite};
+    use std::{env, fs, process};
+
+    const SOURCE: &str = "DATA
+input: DB 0
+result: DB 0
+CODE
+    MOV r0, input
+    ADD r0, r1
+    MOV result, r0
+    CMP r3, #0
+    JZ end
+wait: MOV r2, 0x89
+    TST r2, #0x01
+    JZ wait
+    MOV r2, 0x8B
+    MOV 0x8B, r2
+end: JMP end
+";
+
+    fn run(spec: &str) -> Vec<(bool, Vec<String>)> {
+        let mut compiler = RiscCompiler::new(SOURCE.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        assert!(compiler.diagnostics().is_empty());
+        let program = compiler.program().expect("Didn't generate");
+        let spec = TestFile::parse(spec).expect("Invalid test file");
+        spec.cases
+            .iter()
+            .map(|case| {
+                let report = run_case(program, case, &spec);
+                (report.passed(), report.failures)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expectations_checked() {
+        let reports = run(r#"
+[[case]]
+name = "adds"
+registers = { r1 = 3 }
+memory = { input = 4 }
+expect.registers = { r0 = 7 }
+expect.memory = { result = 7 }
+
+[[case]]
+name = "wrong"
+memory = { input = 1 }
+expect.memory = { result = [2] }
+expect.flags = { n = true }
+"#);
+        assert_eq!(reports[0], (true, vec![]));
+        assert!(!reports[1].0);
+        assert_eq!(
+            reports[1].1,
+            vec![
+                "flag N: expected 1, got 0".to_string(),
+                "[result] at 0x01: expected 0x02, got 0x01".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn uart_and_cycle_budget() {
+        let reports = run(r#"
+max_cycles = 100
+
+[[case]]
+name = "echo"
+registers = { r3 = 1 }
+uart_input = "x"
+expect.uart_output = "x"
+
+[[case]]
+name = "budget"
+max_cycles = 2
+"#);
+        assert_eq!(reports[0], (true, vec![]));
+        assert_eq!(
+            reports[1].1,
+            vec!["did not halt within 2 cycles".to_string()]
+        );
+    }
+
+    #[test]
+    fn starts_from_prepared_state() {
+        let mut compiler = RiscCompiler::new(SOURCE.to_string(), RiscCompilerConfig::default());
+        compiler.compile();
+        let mut interp = RiscInterpreter::new(compiler.program().expect("Didn't generate"));
+        interp.registers[1] = 2;
+        interp.bus.memory[0] = 9;
+        let path = env::temp_dir().join(format!("neorisc-prepared-{}.json", process::id()));
+        Snapshot::capture(&interp).save(&path).expect("Save failed");
+
+        let reports = run(&format!(
+            "[[case]]\nname = \"prepared\"\nstate = {:?}\nregisters = {{ r1 = 3 }}\nexpect.registers = {{ r0 = 12 }}\n",
+            path.display().to_string()
+        ));
+        fs::remove_file(&path).expect("Remove failed");
+        assert_eq!(reports[0], (true, vec![]));
+    }
+
+    #[test]
+    fn invalid_spec_rejected() {
+        assert!(TestFile::parse("[[case]]\nname = \"x\"\nregs = {}\n").is_err());
+
+        let path = env::temp_dir().join(format!("neorisc-broken-{}.test.toml", process::id()));
+        fs::write(&path, "[[case]]\nname = 1\n").expect("Write failed");
+        let error = TestSuite::load(&path).err().expect("Loaded a broken file");
+        fs::remove_file(&path).expect("Remove failed");
+        let report = SuiteReport::load_failed(&path, &error);
+        assert_eq!(report.name, format!("neorisc-broken-{}", process::id()));
+        assert_eq!(report.failed(), 1);
+        assert!(report.cases[0].error.is_some());
+    }
+}